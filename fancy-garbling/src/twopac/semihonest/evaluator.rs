@@ -135,4 +135,4 @@ impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT> FancyReveal for Evaluator<C,
     }
 }
 
-impl<C: AbstractChannel, RNG, OT> SemiHonest for Evaluator<C, RNG, OT> {}
+impl<C: AbstractChannel, RNG, OT: SemiHonest> SemiHonest for Evaluator<C, RNG, OT> {}
@@ -0,0 +1,73 @@
+// -*- mode: rust; -*-
+//
+// This file is part of ocelot.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Private equality test and comparison benchmarks using `criterion`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use ocelot::compare::{KkrtCompareReceiver, KkrtCompareSender};
+use scuttlebutt::{AesRng, Channel};
+use std::{
+    io::{BufReader, BufWriter},
+    os::unix::net::UnixStream,
+    time::Duration,
+};
+
+fn rand_u64_vec(size: usize) -> Vec<u64> {
+    (0..size).map(|_| rand::random::<u64>()).collect()
+}
+
+fn _bench_compare(xs: Vec<u64>, ys: Vec<u64>, lt: bool) {
+    let (sender, receiver) = UnixStream::pair().unwrap();
+    let handle = std::thread::spawn(move || {
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(sender.try_clone().unwrap());
+        let writer = BufWriter::new(sender);
+        let mut channel = Channel::new(reader, writer);
+        let mut cmp = KkrtCompareSender::init(&mut channel, 64, &mut rng).unwrap();
+        if lt {
+            cmp.less_than(&mut channel, &xs, &mut rng).unwrap();
+        } else {
+            cmp.equal(&mut channel, &xs, &mut rng).unwrap();
+        }
+    });
+    let mut rng = AesRng::new();
+    let reader = BufReader::new(receiver.try_clone().unwrap());
+    let writer = BufWriter::new(receiver);
+    let mut channel = Channel::new(reader, writer);
+    let mut cmp = KkrtCompareReceiver::init(&mut channel, 64, &mut rng).unwrap();
+    if lt {
+        cmp.less_than(&mut channel, &ys, &mut rng).unwrap();
+    } else {
+        cmp.equal(&mut channel, &ys, &mut rng).unwrap();
+    }
+    handle.join().unwrap();
+}
+
+fn bench_compare(c: &mut Criterion) {
+    c.bench_function("compare::equal (n = 2^10)", move |bench| {
+        let xs = rand_u64_vec(1 << 10);
+        let ys = rand_u64_vec(1 << 10);
+        bench.iter(move || {
+            let result = _bench_compare(xs.clone(), ys.clone(), false);
+            criterion::black_box(result)
+        })
+    });
+    c.bench_function("compare::less_than (n = 2^10)", move |bench| {
+        let xs = rand_u64_vec(1 << 10);
+        let ys = rand_u64_vec(1 << 10);
+        bench.iter(move || {
+            let result = _bench_compare(xs.clone(), ys.clone(), true);
+            criterion::black_box(result)
+        })
+    });
+}
+
+criterion_group! {
+    name = compare;
+    config = Criterion::default().warm_up_time(Duration::from_millis(100)).sample_size(10);
+    targets = bench_compare
+}
+criterion_main!(compare);
@@ -96,6 +96,26 @@ fn bench_oprf(c: &mut Criterion) {
             criterion::black_box(result);
         })
     });
+    c.bench_function("oprf::oos (initialization)", move |bench| {
+        bench.iter(|| {
+            let result = _bench_oprf_init::<oprf::OosSender, oprf::OosReceiver>();
+            criterion::black_box(result);
+        })
+    });
+    let inputs = rand_block_vec(1 << 16);
+    c.bench_function("oprf::oos (n = 2^16)", move |bench| {
+        bench.iter(|| {
+            let result = _bench_oprf::<oprf::OosSender, oprf::OosReceiver>(inputs.clone());
+            criterion::black_box(result);
+        })
+    });
+    let inputs = rand_block_vec(1 << 12);
+    c.bench_function("oprf::dh (n = 2^12)", move |bench| {
+        bench.iter(|| {
+            let result = _bench_oprf::<oprf::DhSender, oprf::DhReceiver>(inputs.clone());
+            criterion::black_box(result);
+        })
+    });
 }
 
 fn bench_oprf_compute(c: &mut Criterion) {
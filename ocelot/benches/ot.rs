@@ -9,6 +9,7 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 use ocelot::ot::{
     self,
+    bit,
     CorrelatedReceiver,
     CorrelatedSender,
     RandomReceiver,
@@ -105,6 +106,31 @@ fn _bench_block_rot<
     handle.join().unwrap();
 }
 
+fn _bench_bit_ot<
+    OTSender: RandomSender<Msg = Block> + Send + 'static,
+    OTReceiver: RandomReceiver<Msg = Block>,
+>(
+    bs: &[bool],
+    ms: Vec<(bool, bool)>,
+) {
+    let (sender, receiver) = UnixStream::pair().unwrap();
+    let handle = std::thread::spawn(move || {
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(sender.try_clone().unwrap());
+        let writer = BufWriter::new(sender);
+        let mut channel = Channel::new(reader, writer);
+        let mut ot = bit::Sender::<OTSender>::init(&mut channel, &mut rng).unwrap();
+        ot.send_bits(&mut channel, &ms, &mut rng).unwrap();
+    });
+    let mut rng = AesRng::new();
+    let reader = BufReader::new(receiver.try_clone().unwrap());
+    let writer = BufWriter::new(receiver);
+    let mut channel = Channel::new(reader, writer);
+    let mut ot = bit::Receiver::<OTReceiver>::init(&mut channel, &mut rng).unwrap();
+    ot.receive_bits(&mut channel, &bs, &mut rng).unwrap();
+    handle.join().unwrap();
+}
+
 fn bench_ot(c: &mut Criterion) {
     c.bench_function("ot::ChouOrlandiOT", move |bench| {
         let m0s = rand_block_vec(128);
@@ -128,6 +154,18 @@ fn bench_ot(c: &mut Criterion) {
         let bs = rand_bool_vec(128);
         bench.iter(|| _bench_block_ot::<ot::DummySender, ot::DummyReceiver>(&bs, ms.clone()))
     });
+    c.bench_function("ot::MasnyRindalOT", move |bench| {
+        let m0s = rand_block_vec(128);
+        let m1s = rand_block_vec(128);
+        let ms = m0s
+            .into_iter()
+            .zip(m1s.into_iter())
+            .collect::<Vec<(Block, Block)>>();
+        let bs = rand_bool_vec(128);
+        bench.iter(|| {
+            _bench_block_ot::<ot::MasnyRindalSender, ot::MasnyRindalReceiver>(&bs, ms.clone())
+        })
+    });
     c.bench_function("ot::NaorPinkasOT", move |bench| {
         let m0s = rand_block_vec(128);
         let m1s = rand_block_vec(128);
@@ -189,10 +227,29 @@ fn bench_random_otext(c: &mut Criterion) {
     });
 }
 
+fn bench_bit_otext(c: &mut Criterion) {
+    c.bench_function("bitot::AlszOT", move |bench| {
+        let ms = rand_bool_vec(T)
+            .into_iter()
+            .zip(rand_bool_vec(T))
+            .collect::<Vec<(bool, bool)>>();
+        let bs = rand_bool_vec(T);
+        bench.iter(|| _bench_bit_ot::<ot::AlszSender, ot::AlszReceiver>(&bs, ms.clone()))
+    });
+    c.bench_function("bitot::KosOT", move |bench| {
+        let ms = rand_bool_vec(T)
+            .into_iter()
+            .zip(rand_bool_vec(T))
+            .collect::<Vec<(bool, bool)>>();
+        let bs = rand_bool_vec(T);
+        bench.iter(|| _bench_bit_ot::<ot::KosSender, ot::KosReceiver>(&bs, ms.clone()))
+    });
+}
+
 criterion_group! {
     name = ot;
     config = Criterion::default().warm_up_time(Duration::from_millis(100)).sample_size(10);
-    targets = bench_ot, bench_otext, bench_correlated_otext, bench_random_otext
+    targets = bench_ot, bench_otext, bench_correlated_otext, bench_random_otext, bench_bit_otext
 }
 
 criterion_main!(ot);
@@ -0,0 +1,486 @@
+// -*- mode: rust; -*-
+//
+// This file is part of ocelot.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Batched private equality tests and private comparison ("millionaires'
+//! problem") from 1-out-of-N OT (cf. <https://eprint.iacr.org/2016/599> and
+//! <https://eprint.iacr.org/2020/1002>, §3).
+//!
+//! The sender holds `x`s and the receiver holds `y`s, each an `nbits`-bit
+//! integer. Both are split into `CHUNK_BITS`-bit digits. For each pair of
+//! digits `(x_j, y_j)`, the parties run a 1-out-of-`2^CHUNK_BITS` OT, in which
+//! the sender offers `(s_lt ⊕ [x_j < v], s_eq ⊕ [x_j = v])` for every
+//! possible digit `v` and the receiver picks `v = y_j`. This leaves the
+//! parties with XOR shares of `lt_j = [x_j < y_j]` and `eq_j = [x_j = y_j]`.
+//! The digits are then combined pairwise, in a tree, as
+//!
+//! ```text
+//! lt = lt_hi ⊕ (eq_hi ∧ lt_lo),    eq = eq_hi ∧ eq_lo,
+//! ```
+//!
+//! with the ANDs computed using boolean multiplication triples.
+//!
+//! The 1-out-of-N OTs are instantiated with the KKRT OPRF, where the sender
+//! masks the message for `v` with the OPRF output on `v`. The outputs are XOR
+//! shares of the result, which the parties can keep for use in a larger
+//! computation, or open with `reveal`.
+//!
+//! These protocols are semi-honest secure.
+
+use crate::{
+    errors::Error,
+    oprf::{self, Receiver as OprfReceiver, Sender as OprfSender},
+    triple::{self, Triple, TripleGenerator},
+    utils,
+};
+use rand::{CryptoRng, Rng};
+use scuttlebutt::{AbstractChannel, Block, Block512, SemiHonest};
+
+/// The number of bits in each digit.
+pub const CHUNK_BITS: usize = 4;
+// The number of possible digits, i.e., the `N` in 1-out-of-N OT.
+const N: usize = 1 << CHUNK_BITS;
+
+/// Comparison sender using the KKRT OPRF and ALSZ-based AND triples.
+pub type KkrtCompareSender = Sender<oprf::KkrtSender, triple::AndTripleSender>;
+/// Comparison receiver using the KKRT OPRF and ALSZ-based AND triples.
+pub type KkrtCompareReceiver = Receiver<oprf::KkrtReceiver, triple::AndTripleReceiver>;
+
+/// Private comparison sender.
+pub struct Sender<
+    OPRF: OprfSender<Seed = Block512, Input = Block, Output = Block512> + SemiHonest,
+    TG: TripleGenerator<Item = bool> + SemiHonest,
+> {
+    oprf: OPRF,
+    triples: TG,
+    nbits: usize,
+}
+
+impl<
+        OPRF: OprfSender<Seed = Block512, Input = Block, Output = Block512> + SemiHonest,
+        TG: TripleGenerator<Item = bool> + SemiHonest,
+    > Sender<OPRF, TG>
+{
+    /// Runs the one-time initialization, for inputs of `nbits` bits. The
+    /// receiver must use the same `nbits`, which must be between 1 and 64.
+    pub fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        nbits: usize,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        check_nbits(nbits)?;
+        channel.write_u64(nbits as u64)?;
+        channel.flush()?;
+        let oprf = OPRF::init(channel, rng)?;
+        let triples = TG::init(channel, rng)?;
+        Ok(Self {
+            oprf,
+            triples,
+            nbits,
+        })
+    }
+
+    /// Tests whether `xs[i]` equals the receiver's `ys[i]`, returning shares
+    /// of the results. Only the lowest `nbits` bits of each input are used.
+    pub fn equal<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        xs: &[u64],
+        rng: &mut RNG,
+    ) -> Result<Vec<bool>, Error> {
+        let (_, eqs) = self.compare(channel, xs, false, rng)?;
+        Ok(eqs)
+    }
+
+    /// Tests whether `xs[i]` is less than the receiver's `ys[i]`, returning
+    /// shares of the results. Only the lowest `nbits` bits of each input are
+    /// used.
+    pub fn less_than<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        xs: &[u64],
+        rng: &mut RNG,
+    ) -> Result<Vec<bool>, Error> {
+        let (lts, _) = self.compare(channel, xs, true, rng)?;
+        Ok(lts)
+    }
+
+    /// Opens the shares `shares`, revealing the results to both parties.
+    pub fn reveal<C: AbstractChannel>(
+        &mut self,
+        channel: &mut C,
+        shares: &[bool],
+    ) -> Result<Vec<bool>, Error> {
+        open(channel, true, shares)
+    }
+
+    fn compare<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        xs: &[u64],
+        with_lt: bool,
+        rng: &mut RNG,
+    ) -> Result<(Vec<bool>, Vec<bool>), Error> {
+        let ndigits = ndigits(self.nbits);
+        let seeds = self.oprf.send(channel, xs.len() * ndigits, rng)?;
+        let mut lts = Vec::with_capacity(seeds.len());
+        let mut eqs = Vec::with_capacity(seeds.len());
+        for (x, seeds) in xs.iter().zip(seeds.chunks(ndigits)) {
+            for (j, seed) in seeds.iter().enumerate() {
+                let digit = digit(*x, j, self.nbits);
+                let (lt, eq) = (rng.gen::<bool>(), rng.gen::<bool>());
+                let mut table = 0u32;
+                for v in 0..N {
+                    let bits = (lt ^ (digit < v)) as u32 | ((eq ^ (digit == v)) as u32) << 1;
+                    let mask = self.oprf.compute(*seed, Block::from(v as u128));
+                    table |= (bits ^ u32::from(mask.prefix(1)[0] & 3)) << (2 * v);
+                }
+                channel.write_bytes(&table.to_le_bytes())?;
+                lts.push(lt);
+                eqs.push(eq);
+            }
+        }
+        channel.flush()?;
+        let ntriples = ntriples(xs.len(), ndigits, with_lt);
+        let triples = self.triples.generate(channel, ntriples, rng)?;
+        combine(channel, true, lts, eqs, ndigits, with_lt, &triples)
+    }
+}
+
+/// Private comparison receiver.
+pub struct Receiver<
+    OPRF: OprfReceiver<Seed = Block512, Input = Block, Output = Block512> + SemiHonest,
+    TG: TripleGenerator<Item = bool> + SemiHonest,
+> {
+    oprf: OPRF,
+    triples: TG,
+    nbits: usize,
+}
+
+impl<
+        OPRF: OprfReceiver<Seed = Block512, Input = Block, Output = Block512> + SemiHonest,
+        TG: TripleGenerator<Item = bool> + SemiHonest,
+    > Receiver<OPRF, TG>
+{
+    /// Runs the one-time initialization, for inputs of `nbits` bits. Fails if
+    /// the sender uses a different `nbits`.
+    pub fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        nbits: usize,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        check_nbits(nbits)?;
+        let nbits_ = channel.read_u64()? as usize;
+        if nbits_ != nbits {
+            return Err(Error::Other(format!(
+                "comparison bit length mismatch: expected {}, got {}",
+                nbits, nbits_
+            )));
+        }
+        let oprf = OPRF::init(channel, rng)?;
+        let triples = TG::init(channel, rng)?;
+        Ok(Self {
+            oprf,
+            triples,
+            nbits,
+        })
+    }
+
+    /// Tests whether the sender's `xs[i]` equals `ys[i]`, returning shares of
+    /// the results. Only the lowest `nbits` bits of each input are used.
+    pub fn equal<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        ys: &[u64],
+        rng: &mut RNG,
+    ) -> Result<Vec<bool>, Error> {
+        let (_, eqs) = self.compare(channel, ys, false, rng)?;
+        Ok(eqs)
+    }
+
+    /// Tests whether the sender's `xs[i]` is less than `ys[i]`, returning
+    /// shares of the results. Only the lowest `nbits` bits of each input are
+    /// used.
+    pub fn less_than<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        ys: &[u64],
+        rng: &mut RNG,
+    ) -> Result<Vec<bool>, Error> {
+        let (lts, _) = self.compare(channel, ys, true, rng)?;
+        Ok(lts)
+    }
+
+    /// Opens the shares `shares`, revealing the results to both parties.
+    pub fn reveal<C: AbstractChannel>(
+        &mut self,
+        channel: &mut C,
+        shares: &[bool],
+    ) -> Result<Vec<bool>, Error> {
+        open(channel, false, shares)
+    }
+
+    fn compare<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        ys: &[u64],
+        with_lt: bool,
+        rng: &mut RNG,
+    ) -> Result<(Vec<bool>, Vec<bool>), Error> {
+        let (nbits, ndigits) = (self.nbits, ndigits(self.nbits));
+        let digits = ys
+            .iter()
+            .flat_map(|y| (0..ndigits).map(move |j| digit(*y, j, nbits)))
+            .collect::<Vec<usize>>();
+        let inputs = digits
+            .iter()
+            .map(|v| Block::from(*v as u128))
+            .collect::<Vec<Block>>();
+        let outputs = self.oprf.receive(channel, &inputs, rng)?;
+        let mut lts = Vec::with_capacity(digits.len());
+        let mut eqs = Vec::with_capacity(digits.len());
+        for (v, output) in digits.iter().zip(outputs.iter()) {
+            let mut table = [0u8; 4];
+            channel.read_bytes(&mut table)?;
+            let table = u32::from_le_bytes(table);
+            let bits = ((table >> (2 * v)) & 3) ^ u32::from(output.prefix(1)[0] & 3);
+            lts.push(bits & 1 == 1);
+            eqs.push(bits & 2 == 2);
+        }
+        let ntriples = ntriples(ys.len(), ndigits, with_lt);
+        let triples = self.triples.generate(channel, ntriples, rng)?;
+        combine(channel, false, lts, eqs, ndigits, with_lt, &triples)
+    }
+}
+
+#[inline]
+fn check_nbits(nbits: usize) -> Result<(), Error> {
+    if nbits == 0 || nbits > 64 {
+        return Err(Error::Other(format!(
+            "invalid comparison bit length {} (must be between 1 and 64)",
+            nbits
+        )));
+    }
+    Ok(())
+}
+
+#[inline]
+fn ndigits(nbits: usize) -> usize {
+    nbits.div_ceil(CHUNK_BITS)
+}
+
+// The `j`th digit (least significant first) of the lowest `nbits` bits of `x`.
+#[inline]
+fn digit(x: u64, j: usize, nbits: usize) -> usize {
+    let x = if nbits == 64 {
+        x
+    } else {
+        x & ((1 << nbits) - 1)
+    };
+    ((x >> (CHUNK_BITS * j)) as usize) & (N - 1)
+}
+
+// The number of ANDs needed to combine `ndigits` digits for each of `n`
+// inputs.
+fn ntriples(n: usize, mut ndigits: usize, with_lt: bool) -> usize {
+    let per_pair = if with_lt { 2 } else { 1 };
+    let mut count = 0;
+    while ndigits > 1 {
+        count += n * (ndigits / 2) * per_pair;
+        ndigits = ndigits.div_ceil(2);
+    }
+    count
+}
+
+// Combine the shares of the per-digit results, stored least significant digit
+// first with `ndigits` digits per input, into shares of the results for the
+// whole inputs. Each level of the tree halves the number of digits, using one
+// batch of ANDs.
+fn combine<C: AbstractChannel>(
+    channel: &mut C,
+    first: bool,
+    mut lts: Vec<bool>,
+    mut eqs: Vec<bool>,
+    mut ndigits: usize,
+    with_lt: bool,
+    mut triples: &[Triple<bool>],
+) -> Result<(Vec<bool>, Vec<bool>), Error> {
+    while ndigits > 1 {
+        let npairs = ndigits / 2;
+        let mut xs = Vec::with_capacity(2 * eqs.len());
+        let mut ys = Vec::with_capacity(2 * eqs.len());
+        for i in (0..eqs.len()).step_by(ndigits) {
+            for k in (i..i + 2 * npairs).step_by(2) {
+                xs.push(eqs[k + 1]);
+                ys.push(eqs[k]);
+                if with_lt {
+                    xs.push(eqs[k + 1]);
+                    ys.push(lts[k]);
+                }
+            }
+        }
+        let (triples_, rest) = triples.split_at(xs.len());
+        triples = rest;
+        let zs = and(channel, first, &xs, &ys, triples_)?;
+        let mut zs = zs.into_iter();
+        let mut lts_ = Vec::with_capacity(lts.len() / 2 + 1);
+        let mut eqs_ = Vec::with_capacity(eqs.len() / 2 + 1);
+        for i in (0..eqs.len()).step_by(ndigits) {
+            for k in (i..i + 2 * npairs).step_by(2) {
+                eqs_.push(zs.next().unwrap());
+                if with_lt {
+                    lts_.push(lts[k + 1] ^ zs.next().unwrap());
+                }
+            }
+            if ndigits % 2 == 1 {
+                eqs_.push(eqs[i + ndigits - 1]);
+                if with_lt {
+                    lts_.push(lts[i + ndigits - 1]);
+                }
+            }
+        }
+        lts = lts_;
+        eqs = eqs_;
+        ndigits = ndigits.div_ceil(2);
+    }
+    Ok((lts, eqs))
+}
+
+// Compute shares of `xs[i] ∧ ys[i]` using Beaver triples.
+fn and<C: AbstractChannel>(
+    channel: &mut C,
+    first: bool,
+    xs: &[bool],
+    ys: &[bool],
+    triples: &[Triple<bool>],
+) -> Result<Vec<bool>, Error> {
+    let des = xs
+        .iter()
+        .zip(triples.iter())
+        .map(|(x, t)| x ^ t.a)
+        .chain(ys.iter().zip(triples.iter()).map(|(y, t)| y ^ t.b))
+        .collect::<Vec<bool>>();
+    let des = open(channel, first, &des)?;
+    let (ds, es) = des.split_at(xs.len());
+    Ok(triples
+        .iter()
+        .zip(ds.iter().zip(es.iter()))
+        .map(|(t, (d, e))| t.c ^ (d & t.b) ^ (e & t.a) ^ (first & d & e))
+        .collect())
+}
+
+// Open the XOR-shared bits `bs`, with the first party sending its shares
+// first.
+fn open<C: AbstractChannel>(channel: &mut C, first: bool, bs: &[bool]) -> Result<Vec<bool>, Error> {
+    let bytes = utils::boolvec_to_u8vec(bs);
+    if first {
+        channel.write_bytes(&bytes)?;
+        channel.flush()?;
+    }
+    let mut bytes_ = vec![0u8; bytes.len()];
+    channel.read_bytes(&mut bytes_)?;
+    if !first {
+        channel.write_bytes(&bytes)?;
+        channel.flush()?;
+    }
+    Ok(bs
+        .iter()
+        .zip(utils::u8vec_to_boolvec(&bytes_))
+        .map(|(b, b_)| b ^ b_)
+        .collect())
+}
+
+impl<
+        OPRF: OprfSender<Seed = Block512, Input = Block, Output = Block512> + SemiHonest,
+        TG: TripleGenerator<Item = bool> + SemiHonest,
+    > SemiHonest for Sender<OPRF, TG>
+{
+}
+impl<
+        OPRF: OprfReceiver<Seed = Block512, Input = Block, Output = Block512> + SemiHonest,
+        TG: TripleGenerator<Item = bool> + SemiHonest,
+    > SemiHonest for Receiver<OPRF, TG>
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scuttlebutt::{AesRng, Channel};
+    use std::{
+        io::{BufReader, BufWriter},
+        os::unix::net::UnixStream,
+    };
+
+    fn _test_compare(nbits: usize, n: usize, reveal: bool) {
+        let mut rng = AesRng::new();
+        let mask = if nbits == 64 { !0 } else { (1u64 << nbits) - 1 };
+        // Make sure some of the inputs are equal, or differ in only one digit.
+        let xs = (0..n).map(|_| rng.gen::<u64>()).collect::<Vec<u64>>();
+        let ys = xs
+            .iter()
+            .enumerate()
+            .map(|(i, x)| match i % 3 {
+                0 => *x,
+                1 => *x ^ (1 << rng.gen_range(0, nbits)),
+                _ => rng.gen::<u64>(),
+            })
+            .collect::<Vec<u64>>();
+        let xs_ = xs.clone();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut cmp = KkrtCompareSender::init(&mut channel, nbits, &mut rng).unwrap();
+            let eqs = cmp.equal(&mut channel, &xs_, &mut rng).unwrap();
+            let lts = cmp.less_than(&mut channel, &xs_, &mut rng).unwrap();
+            if reveal {
+                let eqs_ = cmp.reveal(&mut channel, &eqs).unwrap();
+                let lts_ = cmp.reveal(&mut channel, &lts).unwrap();
+                (eqs_, lts_)
+            } else {
+                (eqs, lts)
+            }
+        });
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut cmp = KkrtCompareReceiver::init(&mut channel, nbits, &mut rng).unwrap();
+        let eqs = cmp.equal(&mut channel, &ys, &mut rng).unwrap();
+        let lts = cmp.less_than(&mut channel, &ys, &mut rng).unwrap();
+        let (eqs, lts) = if reveal {
+            let eqs_ = cmp.reveal(&mut channel, &eqs).unwrap();
+            let lts_ = cmp.reveal(&mut channel, &lts).unwrap();
+            (eqs_, lts_)
+        } else {
+            (eqs, lts)
+        };
+        let (eqs_, lts_) = handle.join().unwrap();
+        for (i, (x, y)) in xs.iter().zip(ys.iter()).enumerate() {
+            let (x, y) = (x & mask, y & mask);
+            if reveal {
+                assert_eq!(eqs[i], eqs_[i]);
+                assert_eq!(lts[i], lts_[i]);
+                assert_eq!(eqs[i], x == y);
+                assert_eq!(lts[i], x < y);
+            } else {
+                assert_eq!(eqs[i] ^ eqs_[i], x == y);
+                assert_eq!(lts[i] ^ lts_[i], x < y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        _test_compare(64, 100, false);
+        _test_compare(64, 100, true);
+        _test_compare(32, 100, true);
+        _test_compare(13, 100, false);
+        _test_compare(1, 10, true);
+        _test_compare(64, 0, true);
+    }
+}
@@ -18,5 +18,155 @@ mod errors;
 mod utils;
 
 pub use crate::errors::Error;
+pub mod compare;
 pub mod oprf;
 pub mod ot;
+pub mod params;
+pub mod shuffle;
+pub mod triple;
+pub mod vole;
+
+use rand::{CryptoRng, Rng};
+use scuttlebutt::{AbstractChannel, Block, Malicious, SemiHonest};
+
+// Generic constructors for the OT extension and OPRF protocols over any base
+// OT. Each is bounded on the security marker of the base OT, so that building
+// a maliciously secure protocol from a semi-honest one fails to compile.
+
+/// Initializes a semi-honest (ALSZ) OT extension sender over the base OT
+/// receiver `OT`.
+pub fn ot_extension_sender<OT, C, RNG>(
+    channel: &mut C,
+    rng: &mut RNG,
+) -> Result<ot::alsz::Sender<OT>, Error>
+where
+    OT: ot::Receiver<Msg = Block> + SemiHonest,
+    C: AbstractChannel,
+    RNG: CryptoRng + Rng,
+{
+    <ot::alsz::Sender<OT> as ot::Sender>::init(channel, rng)
+}
+
+/// Initializes a semi-honest (ALSZ) OT extension receiver over the base OT
+/// sender `OT`.
+pub fn ot_extension_receiver<OT, C, RNG>(
+    channel: &mut C,
+    rng: &mut RNG,
+) -> Result<ot::alsz::Receiver<OT>, Error>
+where
+    OT: ot::Sender<Msg = Block> + SemiHonest,
+    C: AbstractChannel,
+    RNG: CryptoRng + Rng,
+{
+    <ot::alsz::Receiver<OT> as ot::Receiver>::init(channel, rng)
+}
+
+/// Initializes a maliciously secure (KOS) OT extension sender over the base
+/// OT receiver `OT`, which must itself be maliciously secure:
+///
+/// ```compile_fail
+/// use ocelot::{ot::NaorPinkasReceiver, Error};
+/// use rand::{CryptoRng, Rng};
+/// use scuttlebutt::AbstractChannel;
+///
+/// fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+///     channel: &mut C,
+///     rng: &mut RNG,
+/// ) -> Result<(), Error> {
+///     ocelot::malicious_ot_extension_sender::<NaorPinkasReceiver, _, _>(channel, rng)?;
+///     Ok(())
+/// }
+/// ```
+pub fn malicious_ot_extension_sender<OT, C, RNG>(
+    channel: &mut C,
+    rng: &mut RNG,
+) -> Result<ot::kos::Sender<OT>, Error>
+where
+    OT: ot::Receiver<Msg = Block> + Malicious,
+    C: AbstractChannel,
+    RNG: CryptoRng + Rng,
+{
+    <ot::kos::Sender<OT> as ot::Sender>::init(channel, rng)
+}
+
+/// Initializes a maliciously secure (KOS) OT extension receiver over the
+/// base OT sender `OT`, which must itself be maliciously secure.
+pub fn malicious_ot_extension_receiver<OT, C, RNG>(
+    channel: &mut C,
+    rng: &mut RNG,
+) -> Result<ot::kos::Receiver<OT>, Error>
+where
+    OT: ot::Sender<Msg = Block> + Malicious,
+    C: AbstractChannel,
+    RNG: CryptoRng + Rng,
+{
+    <ot::kos::Receiver<OT> as ot::Receiver>::init(channel, rng)
+}
+
+/// Initializes a semi-honest (KKRT) OPRF sender over the OT receiver `OT`.
+pub fn oprf_sender<OT, C, RNG>(
+    channel: &mut C,
+    rng: &mut RNG,
+) -> Result<oprf::kkrt::Sender<OT>, Error>
+where
+    OT: ot::Receiver<Msg = Block> + SemiHonest,
+    C: AbstractChannel,
+    RNG: CryptoRng + Rng,
+{
+    <oprf::kkrt::Sender<OT> as oprf::Sender>::init(channel, rng)
+}
+
+/// Initializes a semi-honest (KKRT) OPRF receiver over the OT sender `OT`.
+pub fn oprf_receiver<OT, C, RNG>(
+    channel: &mut C,
+    rng: &mut RNG,
+) -> Result<oprf::kkrt::Receiver<OT>, Error>
+where
+    OT: ot::Sender<Msg = Block> + SemiHonest,
+    C: AbstractChannel,
+    RNG: CryptoRng + Rng,
+{
+    <oprf::kkrt::Receiver<OT> as oprf::Receiver>::init(channel, rng)
+}
+
+/// Initializes a maliciously secure (OOS) OPRF sender over the OT receiver
+/// `OT`, which must itself be maliciously secure:
+///
+/// ```compile_fail
+/// use ocelot::{ot::AlszReceiver, Error};
+/// use rand::{CryptoRng, Rng};
+/// use scuttlebutt::AbstractChannel;
+///
+/// fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+///     channel: &mut C,
+///     rng: &mut RNG,
+/// ) -> Result<(), Error> {
+///     ocelot::malicious_oprf_sender::<AlszReceiver, _, _>(channel, rng)?;
+///     Ok(())
+/// }
+/// ```
+pub fn malicious_oprf_sender<OT, C, RNG>(
+    channel: &mut C,
+    rng: &mut RNG,
+) -> Result<oprf::oos::Sender<OT>, Error>
+where
+    OT: ot::Receiver<Msg = Block> + Malicious,
+    C: AbstractChannel,
+    RNG: CryptoRng + Rng,
+{
+    <oprf::oos::Sender<OT> as oprf::Sender>::init(channel, rng)
+}
+
+/// Initializes a maliciously secure (OOS) OPRF receiver over the OT sender
+/// `OT`, which must itself be maliciously secure.
+pub fn malicious_oprf_receiver<OT, C, RNG>(
+    channel: &mut C,
+    rng: &mut RNG,
+) -> Result<oprf::oos::Receiver<OT>, Error>
+where
+    OT: ot::Sender<Msg = Block> + Malicious,
+    C: AbstractChannel,
+    RNG: CryptoRng + Rng,
+{
+    <oprf::oos::Receiver<OT> as oprf::Receiver>::init(channel, rng)
+}
@@ -0,0 +1,510 @@
+// -*- mode: rust; -*-
+//
+// This file is part of ocelot.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Implementation of the Diffie-Hellman based oblivious PRF
+//! `F_k(x) = H(x, k · H'(x))` (cf. <https://eprint.iacr.org/2014/650>,
+//! Figure 3), with optional verifiability.
+//!
+//! The receiver hashes each input `x` to a point `H'(x)` on the Ristretto
+//! group, blinds it by a random scalar `r`, and sends `r · H'(x)`. The sender
+//! raises every point it receives to its key `k`, and the receiver unblinds the
+//! result by multiplying by `r^-1`.
+//!
+//! Unlike the OT-based OPRFs, the sender's key is a single scalar that is not
+//! tied to any particular receiver, so a sender can keep one key around and
+//! evaluate it for many receivers, or for the same receiver over many
+//! sessions; see `Sender::new` and `Sender::key`.
+//!
+//! In verifiable mode, the sender publishes `K = k · G` at the start of each
+//! session and proves, for each batch, that it used the key `k` for every
+//! point in the batch. This is done with a single Chaum-Pedersen proof of
+//! equality of discrete logarithms between `K` and a random linear combination
+//! of the batch, made non-interactive with the Fiat-Shamir transform. A
+//! receiver who knows `K` in advance can pin it with
+//! `Receiver::init_with_public_key`.
+//!
+//! The `oprf::Sender` and `oprf::Receiver` traits always run in verifiable
+//! mode, and the receiver's `init` fails if the sender does not, so that a
+//! cheating sender cannot silently turn verification off. A sender that does
+//! not prove its outputs must be created with `Sender::new` or
+//! `Sender::init_with_key`, and its receiver with `Receiver::init_unverified`.
+
+use crate::{
+    errors::Error,
+    oprf::{ObliviousPrf, Receiver as OprfReceiver, Sender as OprfSender},
+    utils,
+};
+use curve25519_dalek::{
+    constants::RISTRETTO_BASEPOINT_TABLE,
+    ristretto::RistrettoPoint,
+    scalar::Scalar,
+    traits::VartimeMultiscalarMul,
+};
+use rand::{CryptoRng, Rng};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use scuttlebutt::{AbstractChannel, Block, Block512, SemiHonest};
+use sha2::{Digest, Sha512};
+use std::io::ErrorKind;
+
+// Hash an input to a point on the curve.
+#[inline]
+fn hash_to_curve(input: &Block) -> RistrettoPoint {
+    let mut hasher = Sha512::new();
+    hasher.input(b"ocelot dh-oprf hash-to-curve");
+    hasher.input(input.as_ref());
+    RistrettoPoint::from_hash(hasher)
+}
+
+// Hash an input and the corresponding (unblinded) point into an OPRF output.
+#[inline]
+fn hash_output(input: &Block, pt: &RistrettoPoint) -> Block512 {
+    let mut hasher = Sha512::new();
+    hasher.input(b"ocelot dh-oprf output");
+    hasher.input(input.as_ref());
+    hasher.input(pt.compress().as_bytes());
+    let mut output = [0u8; 64];
+    output.copy_from_slice(&hasher.result());
+    Block512::from(output)
+}
+
+// Compute the random linear combinations of the blinded inputs `xs` and the
+// sender's replies `ys` that the batched equality proof is about. The weights
+// are derived from a hash of the whole batch, so neither party controls them.
+fn combine(
+    public: &RistrettoPoint,
+    xs: &[RistrettoPoint],
+    ys: &[RistrettoPoint],
+) -> (RistrettoPoint, RistrettoPoint) {
+    let mut hasher = Sha512::new();
+    hasher.input(b"ocelot dh-oprf batch");
+    hasher.input(public.compress().as_bytes());
+    for pt in xs.iter().chain(ys.iter()) {
+        hasher.input(pt.compress().as_bytes());
+    }
+    let seed = hasher.result();
+    let weights = (0..xs.len())
+        .map(|i| {
+            let mut hasher = Sha512::new();
+            hasher.input(seed);
+            hasher.input((i as u64).to_le_bytes());
+            Scalar::from_hash(hasher)
+        })
+        .collect::<Vec<Scalar>>();
+    (
+        RistrettoPoint::vartime_multiscalar_mul(&weights, xs),
+        RistrettoPoint::vartime_multiscalar_mul(&weights, ys),
+    )
+}
+
+// Compute the Fiat-Shamir challenge for a proof that `log_G(public) = log_x(y)`
+// with commitments `a` and `b`.
+fn challenge(
+    public: &RistrettoPoint,
+    x: &RistrettoPoint,
+    y: &RistrettoPoint,
+    a: &RistrettoPoint,
+    b: &RistrettoPoint,
+) -> Scalar {
+    let mut hasher = Sha512::new();
+    hasher.input(b"ocelot dh-oprf dleq");
+    for pt in [public, x, y, a, b].iter() {
+        hasher.input(pt.compress().as_bytes());
+    }
+    Scalar::from_hash(hasher)
+}
+
+#[inline]
+fn write_scalar<C: AbstractChannel>(channel: &mut C, s: &Scalar) -> Result<(), Error> {
+    channel.write_bytes(s.as_bytes())?;
+    Ok(())
+}
+
+#[inline]
+fn read_scalar<C: AbstractChannel>(channel: &mut C) -> Result<Scalar, Error> {
+    let mut bytes = [0u8; 32];
+    channel.read_bytes(&mut bytes)?;
+    Scalar::from_canonical_bytes(bytes).ok_or_else(|| {
+        Error::from(std::io::Error::new(
+            ErrorKind::InvalidData,
+            "invalid scalar",
+        ))
+    })
+}
+
+/// Diffie-Hellman oblivious PRF sender.
+pub struct Sender {
+    key: Scalar,
+    public: RistrettoPoint,
+    verifiable: bool,
+}
+
+impl Sender {
+    /// Creates a sender which evaluates the PRF with key `key`. If `verifiable`
+    /// is `true`, the sender proves to the receiver that every output was
+    /// computed with this key.
+    pub fn new(key: Scalar, verifiable: bool) -> Self {
+        let public = &key * &RISTRETTO_BASEPOINT_TABLE;
+        Self {
+            key,
+            public,
+            verifiable,
+        }
+    }
+
+    /// Runs the initialization for a session with a new receiver, using the
+    /// key `key`. This is what should be used when the key persists across
+    /// sessions.
+    pub fn init_with_key<C: AbstractChannel>(
+        channel: &mut C,
+        key: Scalar,
+        verifiable: bool,
+    ) -> Result<Self, Error> {
+        let sender = Self::new(key, verifiable);
+        sender.start(channel)?;
+        Ok(sender)
+    }
+
+    /// Returns the PRF key.
+    pub fn key(&self) -> Scalar {
+        self.key
+    }
+
+    /// Returns the public key `k · G` corresponding to the PRF key.
+    pub fn public_key(&self) -> RistrettoPoint {
+        self.public
+    }
+
+    // Tell the receiver which mode we're running in.
+    fn start<C: AbstractChannel>(&self, channel: &mut C) -> Result<(), Error> {
+        channel.write_bool(self.verifiable)?;
+        if self.verifiable {
+            channel.write_pt(&self.public)?;
+        }
+        channel.flush()?;
+        Ok(())
+    }
+}
+
+impl ObliviousPrf for Sender {
+    type Seed = ();
+    type Input = Block;
+    type Output = Block512;
+}
+
+impl OprfSender for Sender {
+    fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        mut rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        Self::init_with_key(channel, Scalar::random(&mut rng), true)
+    }
+
+    fn send<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        m: usize,
+        mut rng: &mut RNG,
+    ) -> Result<Vec<Self::Seed>, Error> {
+        let xs = (0..m)
+            .map(|_| channel.read_pt())
+            .collect::<Result<Vec<RistrettoPoint>, _>>()?;
+        let key = self.key;
+        let ys = utils::range(m)
+            .map(|i| key * xs[i])
+            .collect::<Vec<RistrettoPoint>>();
+        for y in ys.iter() {
+            channel.write_pt(y)?;
+        }
+        if self.verifiable {
+            let (x, y) = combine(&self.public, &xs, &ys);
+            let t = Scalar::random(&mut rng);
+            let a = &t * &RISTRETTO_BASEPOINT_TABLE;
+            let b = t * x;
+            let c = challenge(&self.public, &x, &y, &a, &b);
+            let s = t - c * self.key;
+            write_scalar(channel, &c)?;
+            write_scalar(channel, &s)?;
+        }
+        channel.flush()?;
+        Ok(vec![(); m])
+    }
+
+    fn compute(&self, _: Self::Seed, input: Self::Input) -> Self::Output {
+        let y = self.key * hash_to_curve(&input);
+        hash_output(&input, &y)
+    }
+}
+
+/// Diffie-Hellman oblivious PRF receiver.
+pub struct Receiver {
+    // The sender's public key, if running in verifiable mode.
+    public: Option<RistrettoPoint>,
+}
+
+impl Receiver {
+    /// Runs the initialization for a session with a sender whose public key is
+    /// known to be `public`, failing unless the sender runs in verifiable mode
+    /// with that key.
+    pub fn init_with_public_key<C: AbstractChannel>(
+        channel: &mut C,
+        public: RistrettoPoint,
+    ) -> Result<Self, Error> {
+        let receiver = Self::start(channel)?;
+        match receiver.public {
+            Some(public_) if public_ == public => Ok(receiver),
+            Some(_) => Err(Error::Other("unexpected public key".to_string())),
+            None => Err(Error::Other("sender is not verifiable".to_string())),
+        }
+    }
+
+    /// Runs the initialization for a session with a sender in either mode.
+    /// Unlike `init`, this accepts a sender that does not prove its outputs,
+    /// in which case nothing guarantees that they were all computed with the
+    /// same key.
+    pub fn init_unverified<C: AbstractChannel>(channel: &mut C) -> Result<Self, Error> {
+        Self::start(channel)
+    }
+
+    /// Returns the sender's public key, if running in verifiable mode.
+    pub fn public_key(&self) -> Option<RistrettoPoint> {
+        self.public
+    }
+
+    fn start<C: AbstractChannel>(channel: &mut C) -> Result<Self, Error> {
+        let public = if channel.read_bool()? {
+            Some(channel.read_pt()?)
+        } else {
+            None
+        };
+        Ok(Self { public })
+    }
+}
+
+impl ObliviousPrf for Receiver {
+    type Seed = ();
+    type Input = Block;
+    type Output = Block512;
+}
+
+impl OprfReceiver for Receiver {
+    fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        _: &mut RNG,
+    ) -> Result<Self, Error> {
+        let receiver = Self::start(channel)?;
+        if receiver.public.is_none() {
+            return Err(Error::from(std::io::Error::new(
+                ErrorKind::InvalidData,
+                "sender is not verifiable",
+            )));
+        }
+        Ok(receiver)
+    }
+
+    fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        inputs: &[Self::Input],
+        mut rng: &mut RNG,
+    ) -> Result<Vec<Self::Output>, Error> {
+        let m = inputs.len();
+        let rs = (0..m)
+            .map(|_| Scalar::random(&mut rng))
+            .collect::<Vec<Scalar>>();
+        let xs = utils::range(m)
+            .map(|i| rs[i] * hash_to_curve(&inputs[i]))
+            .collect::<Vec<RistrettoPoint>>();
+        for x in xs.iter() {
+            channel.write_pt(x)?;
+        }
+        channel.flush()?;
+        let ys = (0..m)
+            .map(|_| channel.read_pt())
+            .collect::<Result<Vec<RistrettoPoint>, _>>()?;
+        if let Some(public) = self.public {
+            let c = read_scalar(channel)?;
+            let s = read_scalar(channel)?;
+            let (x, y) = combine(&public, &xs, &ys);
+            let a = &s * &RISTRETTO_BASEPOINT_TABLE + c * public;
+            let b = s * x + c * y;
+            if challenge(&public, &x, &y, &a, &b) != c {
+                return Err(Error::from(std::io::Error::new(
+                    ErrorKind::InvalidData,
+                    "DLEQ proof failed",
+                )));
+            }
+        }
+        let out = utils::range(m)
+            .map(|i| hash_output(&inputs[i], &(rs[i].invert() * ys[i])))
+            .collect::<Vec<Self::Output>>();
+        Ok(out)
+    }
+}
+
+impl std::fmt::Display for Sender {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "DH-OPRF Sender")
+    }
+}
+
+impl std::fmt::Display for Receiver {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "DH-OPRF Receiver")
+    }
+}
+
+impl SemiHonest for Sender {}
+impl SemiHonest for Receiver {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scuttlebutt::{AesRng, Channel};
+    use std::{
+        io::{BufReader, BufWriter},
+        os::unix::net::UnixStream,
+    };
+
+    fn rand_block_vec(size: usize) -> Vec<Block> {
+        (0..size).map(|_| rand::random::<Block>()).collect()
+    }
+
+    fn _test_oprf(n: usize, key: Option<Scalar>, verifiable: bool) {
+        let inputs = rand_block_vec(n);
+        let inputs_ = inputs.clone();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut oprf = match key {
+                Some(key) => Sender::init_with_key(&mut channel, key, verifiable).unwrap(),
+                None => Sender::init(&mut channel, &mut rng).unwrap(),
+            };
+            let seeds = oprf.send(&mut channel, n, &mut rng).unwrap();
+            inputs_
+                .into_iter()
+                .zip(seeds)
+                .map(|(input, seed)| oprf.compute(seed, input))
+                .collect::<Vec<Block512>>()
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut oprf = if verifiable {
+            Receiver::init(&mut channel, &mut rng).unwrap()
+        } else {
+            Receiver::init_unverified(&mut channel).unwrap()
+        };
+        assert_eq!(oprf.public_key().is_some(), verifiable);
+        let outputs = oprf.receive(&mut channel, &inputs, &mut rng).unwrap();
+        let results = handle.join().unwrap();
+        assert_eq!(results, outputs);
+    }
+
+    #[test]
+    fn test_oprf() {
+        _test_oprf(0, None, true);
+        _test_oprf(1, None, true);
+        _test_oprf(100, None, true);
+    }
+
+    #[test]
+    fn test_unverified_oprf() {
+        let key = Scalar::random(&mut AesRng::new());
+        _test_oprf(0, Some(key), false);
+        _test_oprf(100, Some(key), false);
+    }
+
+    #[test]
+    fn test_verifiable_oprf() {
+        let key = Scalar::random(&mut AesRng::new());
+        _test_oprf(1, Some(key), true);
+        _test_oprf(100, Some(key), true);
+    }
+
+    #[test]
+    fn test_persistent_key() {
+        // The same key gives the same outputs to different receivers.
+        let key = Scalar::random(&mut AesRng::new());
+        let input = rand::random::<Block>();
+        let run = move || {
+            let (sender, receiver) = UnixStream::pair().unwrap();
+            let handle = std::thread::spawn(move || {
+                let mut rng = AesRng::new();
+                let reader = BufReader::new(sender.try_clone().unwrap());
+                let writer = BufWriter::new(sender);
+                let mut channel = Channel::new(reader, writer);
+                let mut oprf = Sender::init_with_key(&mut channel, key, true).unwrap();
+                oprf.send(&mut channel, 1, &mut rng).unwrap();
+            });
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(receiver.try_clone().unwrap());
+            let writer = BufWriter::new(receiver);
+            let mut channel = Channel::new(reader, writer);
+            let public = &key * &RISTRETTO_BASEPOINT_TABLE;
+            let mut oprf = Receiver::init_with_public_key(&mut channel, public).unwrap();
+            let output = oprf.receive(&mut channel, &[input], &mut rng).unwrap();
+            handle.join().unwrap();
+            output[0]
+        };
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn test_cheating_sender() {
+        // A sender that switches keys after announcing its public key is
+        // caught by the proof.
+        let n = 10;
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut oprf =
+                Sender::init_with_key(&mut channel, Scalar::random(&mut rng), true).unwrap();
+            oprf.key = Scalar::random(&mut rng);
+            oprf.send(&mut channel, n, &mut rng).unwrap();
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut oprf = Receiver::init(&mut channel, &mut rng).unwrap();
+        let inputs = rand_block_vec(n);
+        match oprf.receive(&mut channel, &inputs, &mut rng) {
+            Err(Error::IoError(e)) => assert_eq!(e.kind(), ErrorKind::InvalidData),
+            _ => panic!("cheating sender not caught"),
+        }
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_downgrade() {
+        // A sender that does not prove its outputs is rejected by `init`.
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            Sender::init_with_key(&mut channel, Scalar::random(&mut rng), false).unwrap();
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        match Receiver::init(&mut channel, &mut rng) {
+            Err(Error::IoError(e)) => assert_eq!(e.kind(), ErrorKind::InvalidData),
+            _ => panic!("unverifiable sender accepted"),
+        }
+        handle.join().unwrap();
+    }
+}
@@ -7,6 +7,23 @@
 //! Implementation of the batched, related-key oblivious pseudorandom function
 //! (BaRK-OPRF) protocol of Kolesnikov, Kumaresan, Rosulek, and Trieu (cf.
 //! <https://eprint.iacr.org/2016/799>, Figure 2).
+//!
+//! Besides the batch API of `oprf::Sender` and `oprf::Receiver`, this module
+//! provides chunked sessions (see `Sender::session` and `Receiver::session`),
+//! in which the receiver streams its inputs in batches of bounded size and
+//! both parties get the results of each batch as it completes. This keeps the
+//! memory used by the protocol proportional to the batch size rather than to
+//! the total number of inputs.
+//!
+//! The pseudorandom code has a fixed width of 512 bits, so the
+//! `SecurityParams` given to `init_with_params` (see `params`) instead bound
+//! the total number of inputs the receiver may evaluate the OPRF on. Both
+//! parties count the inputs and fail once the bound is exceeded.
+//!
+//! `init_with_params` starts with the sender sending its `SecurityParams` for
+//! the receiver to check. `init` uses the defaults without sending them, so it
+//! keeps the wire format of KKRT peers that predate `SecurityParams`. Both
+//! parties must initialize the same way.
 
 #![allow(non_upper_case_globals)]
 
@@ -15,8 +32,11 @@ use crate::{
     errors::Error,
     oprf::{ObliviousPrf, Receiver as OprfReceiver, Sender as OprfSender},
     ot::{Receiver as OtReceiver, Sender as OtSender},
+    params::SecurityParams,
     utils,
 };
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use rand::{CryptoRng, Rng, RngCore, SeedableRng};
 use scuttlebutt::{
     cointoss,
@@ -29,6 +49,30 @@ use scuttlebutt::{
 };
 use std::{convert::TryInto, marker::PhantomData};
 
+// Returns the maximum number of inputs allowed by `params`.
+fn max_inputs(params: SecurityParams) -> Result<u64, Error> {
+    match params.kkrt_max_inputs() {
+        0 => Err(Error::Other(format!(
+            "KKRT does not support security parameters {}",
+            params
+        ))),
+        n => Ok(n),
+    }
+}
+
+// Adds `m` inputs to the `count` run so far, failing if this exceeds
+// `max_inputs`.
+fn count_inputs(count: u64, m: usize, max_inputs: u64) -> Result<u64, Error> {
+    let count = count.saturating_add(m as u64);
+    if count > max_inputs {
+        return Err(Error::Other(format!(
+            "KKRT supports at most {} inputs with these security parameters",
+            max_inputs
+        )));
+    }
+    Ok(count)
+}
+
 /// KKRT oblivious PRF sender.
 pub struct Sender<OT: OtReceiver + SemiHonest> {
     _ot: PhantomData<OT>,
@@ -36,6 +80,8 @@ pub struct Sender<OT: OtReceiver + SemiHonest> {
     s_: [u8; 64],
     code: PseudorandomCode,
     rngs: Vec<AesRng>,
+    count: u64,
+    max_inputs: u64,
 }
 
 impl<OT: OtReceiver<Msg = Block> + SemiHonest> ObliviousPrf for Sender<OT> {
@@ -50,25 +96,23 @@ impl<OT: OtReceiver<Msg = Block> + SemiHonest> OprfSender for Sender<OT> {
         C: AbstractChannel,
         RNG: CryptoRng + Rng,
     {
-        let mut ot = OT::init(channel, rng)?;
-        let mut s_ = [0u8; 64];
-        rng.fill_bytes(&mut s_);
-        let s = utils::u8vec_to_boolvec(&s_);
-        let seeds = (0..4).map(|_| rng.gen()).collect::<Vec<Block>>();
-        let keys = cointoss::send(channel, &seeds)?;
-        let code = PseudorandomCode::new(keys[0], keys[1], keys[2], keys[3]);
-        let ks = ot.receive(channel, &s, rng)?;
-        let rngs = ks
-            .into_iter()
-            .map(AesRng::from_seed)
-            .collect::<Vec<AesRng>>();
-        Ok(Self {
-            _ot: PhantomData::<OT>,
-            s,
-            s_,
-            code,
-            rngs,
-        })
+        let ot = OT::init(channel, rng)?;
+        Self::init_with_ot(channel, ot, SecurityParams::default(), rng)
+    }
+
+    fn init_with_params<C, RNG>(
+        channel: &mut C,
+        params: SecurityParams,
+        rng: &mut RNG,
+    ) -> Result<Self, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        max_inputs(params)?;
+        params.send(channel)?;
+        let ot = OT::init_with_params(channel, params, rng)?;
+        Self::init_with_ot(channel, ot, params, rng)
     }
 
     fn send<C, RNG>(
@@ -81,26 +125,32 @@ impl<OT: OtReceiver<Msg = Block> + SemiHonest> OprfSender for Sender<OT> {
         C: AbstractChannel,
         RNG: CryptoRng + Rng,
     {
+        self.count = count_inputs(self.count, m, self.max_inputs)?;
         // Round up if necessary so that `m mod 16 ≡ 0`.
         let nrows = if m % 16 != 0 { m + (16 - m % 16) } else { m };
         const ncols: usize = 512;
-        let mut t0 = vec![0u8; nrows / 8];
-        let mut t1 = vec![0u8; nrows / 8];
+        // The receiver sends `t0` and `t1` for each row, one after the other,
+        // so read them all up front and then process the rows.
+        let mut ts = vec![0u8; 2 * nrows * ncols / 8];
+        channel.read_bytes(&mut ts)?;
         let mut qs = vec![0u8; nrows * ncols / 8];
-        for (j, b) in self.s.iter().enumerate() {
-            let range = j * nrows / 8..(j + 1) * nrows / 8;
-            let mut q = &mut qs[range];
-            self.rngs[j].fill_bytes(&mut q);
-            channel.read_bytes(&mut t0)?;
-            channel.read_bytes(&mut t1)?;
-            scutils::xor_inplace(&mut q, if *b { &t1 } else { &t0 });
-        }
+        let s = &self.s;
+        utils::chunks_mut(&mut qs, nrows / 8)
+            .zip(utils::iter_mut(&mut self.rngs))
+            .zip(utils::chunks(&ts, 2 * nrows / 8))
+            .enumerate()
+            .for_each(|(j, ((q, rng), t))| {
+                rng.fill_bytes(q);
+                let (t0, t1) = t.split_at(nrows / 8);
+                scutils::xor_inplace(q, if s[j] { t1 } else { t0 });
+            });
         let qs = utils::transpose(&qs, ncols, nrows);
         let seeds = qs
             .chunks(ncols / 8)
+            .take(m)
             .map(|q| q.try_into().unwrap())
             .collect::<Vec<Self::Seed>>();
-        Ok(seeds[0..m].to_vec())
+        Ok(seeds)
     }
 
     fn compute(&self, seed: Self::Seed, input: Self::Input) -> Self::Output {
@@ -127,11 +177,137 @@ impl<OT: OtReceiver<Msg = Block> + SemiHonest> Sender<OT> {
     }
 }
 
+impl<OT: OtReceiver<Msg = Block> + SemiHonest> Sender<OT> {
+    // Runs the rest of the initialization on top of `ot`, once the
+    // parameters have been agreed on.
+    fn init_with_ot<C, RNG>(
+        channel: &mut C,
+        mut ot: OT,
+        params: SecurityParams,
+        rng: &mut RNG,
+    ) -> Result<Self, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        let max_inputs = max_inputs(params)?;
+        let mut s_ = [0u8; 64];
+        rng.fill_bytes(&mut s_);
+        let s = utils::u8vec_to_boolvec(&s_);
+        let seeds = (0..4).map(|_| rng.gen()).collect::<Vec<Block>>();
+        let keys = cointoss::send(channel, &seeds)?;
+        let code = PseudorandomCode::new(keys[0], keys[1], keys[2], keys[3]);
+        let ks = ot.receive(channel, &s, rng)?;
+        let rngs = ks
+            .into_iter()
+            .map(AesRng::from_seed)
+            .collect::<Vec<AesRng>>();
+        Ok(Self {
+            _ot: PhantomData::<OT>,
+            s,
+            s_,
+            code,
+            rngs,
+            count: 0,
+            max_inputs,
+        })
+    }
+
+    /// Starts a chunked session in which the receiver sends its inputs in
+    /// chunks of at most `chunk_size` elements. The receiver must use the same
+    /// chunk size.
+    pub fn session<C: AbstractChannel>(
+        &mut self,
+        channel: &mut C,
+        chunk_size: usize,
+    ) -> Result<SenderSession<'_, OT>, Error> {
+        if chunk_size == 0 {
+            return Err(Error::InvalidInputLength);
+        }
+        channel.write_usize(chunk_size)?;
+        channel.flush()?;
+        Ok(SenderSession {
+            oprf: self,
+            chunk_size,
+            position: 0,
+            done: false,
+        })
+    }
+}
+
+/// A chunked KKRT session from the sender's point-of-view.
+///
+/// The session can be left idle between chunks for as long as needed, and
+/// resumes with the next chunk the receiver sends.
+pub struct SenderSession<'a, OT: OtReceiver<Msg = Block> + SemiHonest> {
+    oprf: &'a mut Sender<OT>,
+    chunk_size: usize,
+    position: usize,
+    done: bool,
+}
+
+impl<'a, OT: OtReceiver<Msg = Block> + SemiHonest> SenderSession<'a, OT> {
+    /// Runs the OPRF on the receiver's next chunk, returning one seed per
+    /// input of that chunk, or `None` once the receiver has finished the
+    /// session.
+    pub fn next_chunk<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Option<Vec<Block512>>, Error> {
+        if self.done {
+            return Ok(None);
+        }
+        let m = channel.read_usize()?;
+        if m == 0 {
+            self.done = true;
+            return Ok(None);
+        }
+        if m > self.chunk_size {
+            return Err(Error::InvalidInputLength);
+        }
+        let seeds = self.oprf.send(channel, m, rng)?;
+        self.position += m;
+        Ok(Some(seeds))
+    }
+
+    /// Runs the session until the receiver finishes it, calling `f` on the
+    /// OPRF and the seeds of each chunk.
+    pub fn run<C, RNG, F>(mut self, channel: &mut C, rng: &mut RNG, mut f: F) -> Result<(), Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+        F: FnMut(&Sender<OT>, Vec<Block512>) -> Result<(), Error>,
+    {
+        while let Some(seeds) = self.next_chunk(channel, rng)? {
+            f(&*self.oprf, seeds)?;
+        }
+        Ok(())
+    }
+
+    /// Computes the oblivious PRF on seed `seed` and input `input`.
+    pub fn compute(&self, seed: Block512, input: Block) -> Block512 {
+        self.oprf.compute(seed, input)
+    }
+
+    /// Returns the number of OPRF instances run so far in this session.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Returns whether the receiver has finished the session.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+}
+
 /// KKRT oblivious PRF receiver.
 pub struct Receiver<OT: OtSender + SemiHonest> {
     _ot: PhantomData<OT>,
     code: PseudorandomCode,
     rngs: Vec<(AesRng, AesRng)>,
+    count: u64,
+    max_inputs: u64,
 }
 
 impl<OT: OtSender<Msg = Block> + SemiHonest> ObliviousPrf for Receiver<OT> {
@@ -145,7 +321,80 @@ impl<OT: OtSender<Msg = Block> + SemiHonest> OprfReceiver for Receiver<OT> {
         channel: &mut C,
         rng: &mut RNG,
     ) -> Result<Self, Error> {
-        let mut ot = OT::init(channel, rng)?;
+        let ot = OT::init(channel, rng)?;
+        Self::init_with_ot(channel, ot, SecurityParams::default(), rng)
+    }
+
+    fn init_with_params<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        params: SecurityParams,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        max_inputs(params)?;
+        params.receive(channel)?;
+        let ot = OT::init_with_params(channel, params, rng)?;
+        Self::init_with_ot(channel, ot, params, rng)
+    }
+
+    fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        inputs: &[Self::Input],
+        rng: &mut RNG,
+    ) -> Result<Vec<Self::Output>, Error> {
+        let m = inputs.len();
+        self.count = count_inputs(self.count, m, self.max_inputs)?;
+        // Round up if necessary so that `m mod 16 ≡ 0`.
+        let nrows = if m % 16 != 0 { m + (16 - m % 16) } else { m };
+        const ncols: usize = 512;
+        let mut t0s = vec![0u8; nrows * ncols / 8];
+        rng.fill_bytes(&mut t0s);
+        let out = t0s
+            .chunks(ncols / 8)
+            .map(|c| c.try_into().unwrap())
+            .collect::<Vec<Block512>>();
+        let mut t1s = t0s.clone();
+        let code = &self.code;
+        // Compute `C(input) ⊕ t_{0,j}` for each 512-bit chunk `t_{1,j}`.
+        utils::chunks_mut(&mut t1s[0..m * ncols / 8], ncols / 8)
+            .zip(utils::chunks(inputs, 1))
+            .for_each(|(t1, input)| {
+                let mut c = Block512::default();
+                code.encode(input[0], (&mut c).into());
+                scutils::xor_inplace(t1, c.as_ref());
+            });
+        let t0s = utils::transpose(&t0s, nrows, ncols);
+        let t1s = utils::transpose(&t1s, nrows, ncols);
+        // Each row produces `t0` followed by `t1`; writing all rows at once is
+        // equivalent to writing them one by one.
+        let mut ts = vec![0u8; 2 * nrows * ncols / 8];
+        utils::chunks_mut(&mut ts, 2 * nrows / 8)
+            .zip(utils::iter_mut(&mut self.rngs))
+            .enumerate()
+            .for_each(|(j, (t, rngs))| {
+                let range = j * nrows / 8..(j + 1) * nrows / 8;
+                let (t0, t1) = t.split_at_mut(nrows / 8);
+                rngs.0.fill_bytes(t0);
+                scutils::xor_inplace(t0, &t0s[range.clone()]);
+                rngs.1.fill_bytes(t1);
+                scutils::xor_inplace(t1, &t1s[range]);
+            });
+        channel.write_bytes(&ts)?;
+        channel.flush()?;
+        Ok(out[0..m].to_vec())
+    }
+}
+
+impl<OT: OtSender<Msg = Block> + SemiHonest> Receiver<OT> {
+    // Runs the rest of the initialization on top of `ot`, once the
+    // parameters have been agreed on.
+    fn init_with_ot<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        mut ot: OT,
+        params: SecurityParams,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let max_inputs = max_inputs(params)?;
         let seeds = (0..4).map(|_| rng.gen()).collect::<Vec<Block>>();
         let keys = cointoss::receive(channel, &seeds)?;
         let code = PseudorandomCode::new(keys[0], keys[1], keys[2], keys[3]);
@@ -166,51 +415,104 @@ impl<OT: OtSender<Msg = Block> + SemiHonest> OprfReceiver for Receiver<OT> {
             _ot: PhantomData::<OT>,
             code,
             rngs,
+            count: 0,
+            max_inputs,
         })
     }
 
-    fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
+    /// Starts a chunked session in which inputs are sent in chunks of at most
+    /// `chunk_size` elements. Fails if the sender uses a different chunk size.
+    pub fn session<C: AbstractChannel>(
         &mut self,
         channel: &mut C,
-        inputs: &[Self::Input],
+        chunk_size: usize,
+    ) -> Result<ReceiverSession<'_, OT>, Error> {
+        let chunk_size_ = channel.read_usize()?;
+        if chunk_size_ != chunk_size {
+            return Err(Error::Other(format!(
+                "OPRF chunk size mismatch: expected {}",
+                chunk_size
+            )));
+        }
+        Ok(ReceiverSession {
+            oprf: self,
+            chunk_size,
+            position: 0,
+        })
+    }
+}
+
+/// A chunked KKRT session from the receiver's point-of-view.
+///
+/// The session must be ended with `finish`, which tells the sender that no
+/// more chunks are coming.
+pub struct ReceiverSession<'a, OT: OtSender<Msg = Block> + SemiHonest> {
+    oprf: &'a mut Receiver<OT>,
+    chunk_size: usize,
+    position: usize,
+}
+
+impl<'a, OT: OtSender<Msg = Block> + SemiHonest> ReceiverSession<'a, OT> {
+    /// Runs the OPRF on the chunk `inputs`, which must contain at most
+    /// `chunk_size` elements, returning the OPRF outputs.
+    pub fn next_chunk<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        inputs: &[Block],
         rng: &mut RNG,
-    ) -> Result<Vec<Self::Output>, Error> {
-        let m = inputs.len();
-        // Round up if necessary so that `m mod 16 ≡ 0`.
-        let nrows = if m % 16 != 0 { m + (16 - m % 16) } else { m };
-        const ncols: usize = 512;
-        let mut t0s = vec![0u8; nrows * ncols / 8];
-        rng.fill_bytes(&mut t0s);
-        let out = t0s
-            .chunks(ncols / 8)
-            .map(|c| c.try_into().unwrap())
-            .collect::<Vec<Block512>>();
-        let mut t1s = t0s.clone();
-        let mut c = Block512::default();
-        for (j, input) in inputs.iter().enumerate() {
-            // Compute `C(input) ⊕ t_{0,j}`. Thus, `range` is a 512-bit chunk.
-            let range = j * ncols / 8..(j + 1) * ncols / 8;
-            let mut t1 = &mut t1s[range];
-            self.code.encode(*input, (&mut c).into());
-            scutils::xor_inplace(&mut t1, c.as_ref());
+    ) -> Result<Vec<Block512>, Error> {
+        if inputs.len() > self.chunk_size {
+            return Err(Error::InvalidInputLength);
         }
-        let t0s = utils::transpose(&t0s, nrows, ncols);
-        let t1s = utils::transpose(&t1s, nrows, ncols);
-        let mut t = vec![0u8; nrows / 8];
-        for j in 0..self.rngs.len() {
-            let range = j * nrows / 8..(j + 1) * nrows / 8;
-            let t0 = &t0s[range];
-            let range = j * nrows / 8..(j + 1) * nrows / 8;
-            let t1 = &t1s[range];
-            self.rngs[j].0.fill_bytes(&mut t);
-            scutils::xor_inplace(&mut t, &t0);
-            channel.write_bytes(&t)?;
-            self.rngs[j].1.fill_bytes(&mut t);
-            scutils::xor_inplace(&mut t, &t1);
-            channel.write_bytes(&t)?;
+        if inputs.is_empty() {
+            // An empty chunk would end the session on the sender's side.
+            return Ok(vec![]);
         }
+        channel.write_usize(inputs.len())?;
+        let outputs = self.oprf.receive(channel, inputs, rng)?;
+        self.position += inputs.len();
+        Ok(outputs)
+    }
+
+    /// Runs the OPRF on every input in `inputs`, one chunk at a time, calling
+    /// `f` on each chunk and its OPRF outputs, and then finishes the session.
+    pub fn run<C, RNG, I, F>(
+        mut self,
+        channel: &mut C,
+        inputs: I,
+        rng: &mut RNG,
+        mut f: F,
+    ) -> Result<(), Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+        I: IntoIterator<Item = Block>,
+        F: FnMut(&[Block], Vec<Block512>) -> Result<(), Error>,
+    {
+        let mut inputs = inputs.into_iter();
+        let mut chunk = Vec::with_capacity(self.chunk_size);
+        loop {
+            chunk.clear();
+            chunk.extend(inputs.by_ref().take(self.chunk_size));
+            if chunk.is_empty() {
+                break;
+            }
+            let outputs = self.next_chunk(channel, &chunk, rng)?;
+            f(&chunk, outputs)?;
+        }
+        self.finish(channel)
+    }
+
+    /// Ends the session.
+    pub fn finish<C: AbstractChannel>(self, channel: &mut C) -> Result<(), Error> {
+        channel.write_usize(0)?;
         channel.flush()?;
-        Ok(out[0..m].to_vec())
+        Ok(())
+    }
+
+    /// Returns the number of OPRF instances run so far in this session.
+    pub fn position(&self) -> usize {
+        self.position
     }
 }
 
@@ -281,4 +583,157 @@ mod tests {
         _test_oprf(11);
         _test_oprf(64);
     }
+
+    // See `ot::tests::test_input_lengths`.
+    #[test]
+    fn test_input_lengths() {
+        _test_oprf(0);
+        _test_oprf((1 << 14) + 3);
+    }
+
+    fn _test_oprf_session(n: usize, chunk_size: usize) {
+        let selections = rand_block_vec(n);
+        let selections_ = selections.clone();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut oprf = oprf::KkrtSender::init(&mut channel, &mut rng).unwrap();
+            let mut results = Vec::with_capacity(n);
+            let mut inputs = selections_.into_iter();
+            oprf.session(&mut channel, chunk_size)
+                .unwrap()
+                .run(&mut channel, &mut rng, |oprf, seeds| {
+                    assert!(seeds.len() <= chunk_size);
+                    for (seed, input) in seeds.into_iter().zip(inputs.by_ref()) {
+                        results.push(oprf.compute(seed, input));
+                    }
+                    Ok(())
+                })
+                .unwrap();
+            // The OPRF is still usable after the session ends.
+            let seeds = oprf.send(&mut channel, 1, &mut rng).unwrap();
+            (results, oprf.compute(seeds[0], Block::default()))
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut oprf = oprf::KkrtReceiver::init(&mut channel, &mut rng).unwrap();
+        let mut outputs = Vec::with_capacity(n);
+        let mut session = oprf.session(&mut channel, chunk_size).unwrap();
+        assert!(session
+            .next_chunk(&mut channel, &selections[0..0], &mut rng)
+            .unwrap()
+            .is_empty());
+        session
+            .run(
+                &mut channel,
+                selections.iter().cloned(),
+                &mut rng,
+                |chunk, out| {
+                    assert_eq!(chunk.len(), out.len());
+                    outputs.extend(out);
+                    Ok(())
+                },
+            )
+            .unwrap();
+        let output = oprf
+            .receive(&mut channel, &[Block::default()], &mut rng)
+            .unwrap();
+        let (results, result) = handle.join().unwrap();
+        assert_eq!(results, outputs);
+        assert_eq!(result, output[0]);
+    }
+
+    #[test]
+    fn test_oprf_session() {
+        _test_oprf_session(0, 16);
+        _test_oprf_session(100, 16);
+        _test_oprf_session(100, 7);
+        _test_oprf_session(1000, 1000);
+    }
+
+    #[test]
+    fn test_oprf_session_mismatch() {
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut oprf = oprf::KkrtSender::init(&mut channel, &mut rng).unwrap();
+            let _ = oprf.session(&mut channel, 16);
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut oprf = oprf::KkrtReceiver::init(&mut channel, &mut rng).unwrap();
+        assert!(oprf.session(&mut channel, 32).is_err());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_params_mismatch() {
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let _ = oprf::KkrtSender::init(&mut channel, &mut rng);
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let params = SecurityParams::new(112, 40).unwrap();
+        assert!(oprf::KkrtReceiver::init_with_params(&mut channel, params, &mut rng).is_err());
+        drop(channel);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_input_limit() {
+        // These parameters only allow a single input.
+        let params = SecurityParams::new(128, 101).unwrap();
+        assert_eq!(params.kkrt_max_inputs(), 1);
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut oprf =
+                oprf::KkrtSender::init_with_params(&mut channel, params, &mut rng).unwrap();
+            oprf.send(&mut channel, 1, &mut rng).unwrap();
+            assert!(oprf.send(&mut channel, 1, &mut rng).is_err());
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut oprf =
+            oprf::KkrtReceiver::init_with_params(&mut channel, params, &mut rng).unwrap();
+        let inputs = rand_block_vec(1);
+        oprf.receive(&mut channel, &inputs, &mut rng).unwrap();
+        assert!(oprf.receive(&mut channel, &inputs, &mut rng).is_err());
+        handle.join().unwrap();
+        let params = SecurityParams::new(128, 103).unwrap();
+        assert!(oprf::KkrtReceiver::init_with_params(&mut channel, params, &mut rng).is_err());
+    }
+
+    #[test]
+    fn test_security_markers() {
+        fn semi_honest<T: SemiHonest>() {}
+        semi_honest::<oprf::KkrtSender>();
+        semi_honest::<oprf::KkrtReceiver>();
+        semi_honest::<oprf::KkrtNaorPinkasSender>();
+        semi_honest::<oprf::KkrtNaorPinkasReceiver>();
+        semi_honest::<oprf::KkrtMasnyRindalSender>();
+        semi_honest::<oprf::KkrtMasnyRindalReceiver>();
+    }
 }
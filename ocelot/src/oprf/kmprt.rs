@@ -6,16 +6,49 @@
 
 //! Implementation of the hash-based multi-use OPPRF of Kolesnikov, Matania,
 //! Pinkas, Rosulek, and Trieu (cf. <https://eprint.iacr.org/2017/799>).
+//!
+//! The sender hashes its points into bins and runs one OPRF instance per bin.
+//! How it then programs the points of each bin into the hint it sends the
+//! receiver is chosen by `Backend`:
+//!
+//! * `Backend::Table`: the table-based one-time OPPRF of Figure 6 of the
+//!   paper. Each bin gets a table whose size is a power of two above the
+//!   maximum bin size, and the sender may need to retry several hash keys (or
+//!   grow the table) before the points of a bin land in distinct entries.
+//! * `Backend::Polynomial`: each bin gets the polynomial over `GF(2^128)`
+//!   interpolating its points (padded with random points up to the maximum bin
+//!   size), so the hint of a bin is exactly the maximum bin size.
+//! * `Backend::Okvs`: the points of all bins go into a single oblivious
+//!   key-value store, keyed by the point and the bin it was placed in. Each
+//!   bin is padded with random points up to the maximum bin size, so the store
+//!   has about `1.3` entries per point of the padded bins, and its size does
+//!   not depend on how many points the sender programs.
+//!
+//! Neither of the last two ever retries.
+//!
+//! `init` uses `Backend::Table` and sends nothing beyond what the OPRF
+//! needs, as KMPRT did before backends existed. `init_with_backend` and
+//! `init_with_params` first send the backend for the receiver to check.
+//!
+//! For large sets, `Sender::session` and `Receiver::session` run the OPPRF in
+//! chunks. Both parties split their inputs into a fixed number of partitions
+//! using a hash keyed by the sender, and each chunk runs the OPPRF above on a
+//! single partition, with the receiver's partition padded to a fixed size so
+//! that the partition sizes stay hidden. Each party then only needs to hold
+//! one partition in memory at a time.
 
 use crate::{
     errors::Error,
     oprf::{Receiver as OprfReceiver, Sender as OprfSender},
+    params::SecurityParams,
 };
 use rand::{CryptoRng, Rng};
 use scuttlebutt::{AbstractChannel, Aes128, Block, Block512, SemiHonest};
 use std::collections::HashSet;
 
 mod cuckoo;
+mod okvs;
+mod polynomial;
 
 impl From<cuckoo::Error> for Error {
     fn from(e: cuckoo::Error) -> Error {
@@ -23,9 +56,61 @@ impl From<cuckoo::Error> for Error {
     }
 }
 
-// Number of times to iterate when creating the sender's hash table.
+impl From<okvs::EncodingError> for Error {
+    fn from(e: okvs::EncodingError) -> Error {
+        Error::Other(format!("{}", e))
+    }
+}
+
+/// The method used to program the OPPRF.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// Table-based programming, one table per bin.
+    #[default]
+    Table,
+    /// Polynomial interpolation over `GF(2^128)`, one polynomial per bin.
+    Polynomial,
+    /// A single oblivious key-value store over all bins.
+    Okvs,
+}
+
+impl Backend {
+    fn to_u8(self) -> u8 {
+        match self {
+            Backend::Table => 0,
+            Backend::Polynomial => 1,
+            Backend::Okvs => 2,
+        }
+    }
+}
+
+// Number of times to iterate when creating the sender's hash table. This only
+// affects how often the sender fails, not security.
 const N_TABLE_LOOPS: usize = 128;
 
+// Check that `params` can be used with KMPRT, whose hashing parameters are
+// chosen for a failure probability of `2^-40`.
+fn check_params(params: SecurityParams) -> Result<(), Error> {
+    if params.statistical() > 40 {
+        return Err(Error::Other(format!(
+            "KMPRT supports statistical security of at most 40, got {}",
+            params.statistical()
+        )));
+    }
+    Ok(())
+}
+
+// Receive the sender's backend, failing if it differs from `backend`.
+fn receive_backend<C: AbstractChannel>(channel: &mut C, backend: Backend) -> Result<(), Error> {
+    if channel.read_u8()? != backend.to_u8() {
+        return Err(Error::Other(format!(
+            "OPPRF backend mismatch: expected {:?}",
+            backend
+        )));
+    }
+    Ok(())
+}
+
 // Hash `x` with key `k`, producing a result in the range `[0..range-1]`. We use
 // the Davies-Meyer-esque single-block-length compression function
 // under-the-hood, and we pre-key `k`.
@@ -99,6 +184,12 @@ impl Parameters {
             h2,
         })
     }
+
+    // The number of points in the OKVS, which is the total size of the bins
+    // once padded to their maximum size.
+    fn okvs_npoints(&self) -> usize {
+        self.m1 * self.beta1 + self.m2 * self.beta2
+    }
 }
 
 /// KMPRT hashing-based OPPRF sender.
@@ -106,21 +197,61 @@ impl Parameters {
 /// This implements the hashing-based OPPRF sender in Figure 7 of the paper. It
 /// uses the table-based one-time OPPRF under-the-hood (Figure 6 of the paper),
 /// which itself uses an OPRF.
-pub struct Sender<OPRF> {
+pub struct Sender<OPRF: OprfSender + SemiHonest> {
     oprf: OPRF,
+    backend: Backend,
 }
 
 impl<OPRF: OprfSender<Seed = Block512, Input = Block, Output = Block512> + SemiHonest>
     Sender<OPRF>
 {
-    /// Initialize the OPPRF sender.
+    /// Initialize the OPPRF sender, using the default backend. The receiver
+    /// must use `init` too.
     pub fn init<C, RNG>(channel: &mut C, rng: &mut RNG) -> Result<Self, Error>
     where
         C: AbstractChannel,
         RNG: CryptoRng + Rng,
     {
         let oprf = OPRF::init(channel, rng)?;
-        Ok(Self { oprf })
+        let backend = Backend::default();
+        Ok(Self { oprf, backend })
+    }
+
+    /// Initialize the OPPRF sender, using `backend` to program the OPPRF. The
+    /// receiver must use `init_with_backend` with the same backend.
+    pub fn init_with_backend<C, RNG>(
+        channel: &mut C,
+        backend: Backend,
+        rng: &mut RNG,
+    ) -> Result<Self, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        channel.write_u8(backend.to_u8())?;
+        channel.flush()?;
+        let oprf = OPRF::init(channel, rng)?;
+        Ok(Self { oprf, backend })
+    }
+
+    /// Initialize the OPPRF sender, using `backend` to program the OPPRF and
+    /// security parameters `params`, which are passed on to the underlying
+    /// OPRF. The receiver must use the same backend and parameters.
+    pub fn init_with_params<C, RNG>(
+        channel: &mut C,
+        backend: Backend,
+        params: SecurityParams,
+        rng: &mut RNG,
+    ) -> Result<Self, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        check_params(params)?;
+        channel.write_u8(backend.to_u8())?;
+        channel.flush()?;
+        let oprf = OPRF::init_with_params(channel, params, rng)?;
+        Ok(Self { oprf, backend })
     }
 
     /// Run the OPPRF for `ninputs` inputs with the pairs given in
@@ -177,16 +308,68 @@ impl<OPRF: OprfSender<Seed = Block512, Input = Block, Output = Block512> + SemiH
             }
         }
         let seeds = self.oprf.send(channel, bins.len(), rng)?;
-        // Run the one-time OPPRF on each bin.
-        for (j, (bin, seed)) in bins.into_iter().zip(seeds.into_iter()).enumerate() {
-            // `beta` is the maximum number of entries a bin could have.
-            let beta = if j < params.m1 {
-                params.beta1
-            } else {
-                params.beta2
-            };
-
-            self.process_oprf_output(channel, seed, bin, beta, rng)?;
+        match self.backend {
+            Backend::Table => {
+                // Run the one-time OPPRF on each bin.
+                for (j, (bin, seed)) in bins.into_iter().zip(seeds.into_iter()).enumerate() {
+                    // `beta` is the maximum number of entries a bin could have.
+                    let beta = if j < params.m1 {
+                        params.beta1
+                    } else {
+                        params.beta2
+                    };
+
+                    self.process_oprf_output(channel, seed, bin, beta, rng)?;
+                }
+            }
+            Backend::Polynomial => {
+                for (j, (bin, seed)) in bins.into_iter().zip(seeds.into_iter()).enumerate() {
+                    let beta = if j < params.m1 {
+                        params.beta1
+                    } else {
+                        params.beta2
+                    };
+                    assert!(bin.len() <= beta);
+                    // Pad the bin with random points, so that the polynomial
+                    // always has degree `β - 1` and looks random.
+                    let points = bin
+                        .into_iter()
+                        .map(|(x, y)| (x, y ^ self.oprf.compute(seed, x)))
+                        .chain((0..beta).map(|_| (rng.gen(), rng.gen())))
+                        .take(beta)
+                        .collect::<Vec<(Block, Block512)>>();
+                    for coeff in polynomial::interpolate(&points).iter() {
+                        channel.write_block512(coeff)?;
+                    }
+                }
+                channel.flush()?;
+            }
+            Backend::Okvs => {
+                let mut points = Vec::with_capacity(params.okvs_npoints());
+                for (j, (bin, seed)) in bins.into_iter().zip(seeds.into_iter()).enumerate() {
+                    let beta = if j < params.m1 {
+                        params.beta1
+                    } else {
+                        params.beta2
+                    };
+                    assert!(bin.len() <= beta);
+                    // Pad the bin with random points, as for `Polynomial`.
+                    let npad = beta - bin.len();
+                    points.extend(
+                        bin.into_iter()
+                            .map(|(x, y)| (x, j, y ^ self.oprf.compute(seed, x))),
+                    );
+                    points.extend((0..npad).map(|_| (rng.gen(), j, rng.gen())));
+                }
+                let key = rng.gen::<Block>();
+                let okvs = okvs::Okvs::new(key, points.len());
+                let table = okvs.encode(&points, rng)?;
+                channel.write_block(&key)?;
+                for entry in table.iter() {
+                    channel.write_block512(entry)?;
+                }
+                channel.flush()?;
+            }
         }
         Ok(())
     }
@@ -291,6 +474,114 @@ impl<OPRF: OprfSender<Seed = Block512, Input = Block, Output = Block512> + SemiH
     }
 }
 
+impl<OPRF: OprfSender<Seed = Block512, Input = Block, Output = Block512> + SemiHonest>
+    Sender<OPRF>
+{
+    /// Starts a chunked session over `nchunks` partitions, in each of which
+    /// the receiver has at most `chunk_size` inputs. The receiver must use the
+    /// same parameters.
+    pub fn session<C, RNG>(
+        &mut self,
+        channel: &mut C,
+        nchunks: usize,
+        chunk_size: usize,
+        rng: &mut RNG,
+    ) -> Result<SenderSession<'_, OPRF>, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        if nchunks == 0 {
+            return Err(Error::InvalidInputLength);
+        }
+        let _ = Parameters::new(chunk_size)?;
+        let key = rng.gen::<Block>();
+        channel.write_usize(nchunks)?;
+        channel.write_usize(chunk_size)?;
+        channel.write_block(&key)?;
+        channel.flush()?;
+        Ok(SenderSession {
+            opprf: self,
+            key: Aes128::new(key),
+            nchunks,
+            chunk_size,
+            index: 0,
+        })
+    }
+}
+
+/// A chunked KMPRT session from the sender's point-of-view.
+///
+/// The `i`th call to `next_chunk` must be given exactly the points whose
+/// partition is `i`. The session can be left idle between chunks for as long
+/// as needed.
+pub struct SenderSession<'a, OPRF: OprfSender + SemiHonest> {
+    opprf: &'a mut Sender<OPRF>,
+    key: Aes128,
+    nchunks: usize,
+    chunk_size: usize,
+    index: usize,
+}
+
+impl<'a, OPRF: OprfSender<Seed = Block512, Input = Block, Output = Block512> + SemiHonest>
+    SenderSession<'a, OPRF>
+{
+    /// Returns the partition of `x`.
+    pub fn partition(&self, x: Block) -> usize {
+        hash_input_keyed(&self.key, x, self.nchunks)
+    }
+
+    /// Runs the OPPRF on the next partition, with the pairs given in `points`
+    /// as the programmed points.
+    pub fn next_chunk<C, RNG>(
+        &mut self,
+        channel: &mut C,
+        points: &[(Block, Block512)],
+        rng: &mut RNG,
+    ) -> Result<(), Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        check_partition(
+            &self.key,
+            self.nchunks,
+            self.index,
+            points.iter().map(|(x, _)| *x),
+        )?;
+        self.opprf.send(channel, points, self.chunk_size, rng)?;
+        self.index += 1;
+        Ok(())
+    }
+
+    /// Returns the partition the next chunk must contain.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns whether every partition has been processed.
+    pub fn is_done(&self) -> bool {
+        self.index == self.nchunks
+    }
+}
+
+// Checks that the session isn't over and that each of `xs` lies in partition
+// `index`.
+fn check_partition<I: Iterator<Item = Block>>(
+    key: &Aes128,
+    nchunks: usize,
+    index: usize,
+    mut xs: I,
+) -> Result<(), Error> {
+    if index == nchunks {
+        return Err(Error::Other("OPPRF session already finished".to_string()));
+    }
+    if xs.any(|x| hash_input_keyed(key, x, nchunks) != index) {
+        return Err(Error::Other(format!("input not in partition {}", index)));
+    }
+    Ok(())
+}
+
 /// KMPRT oblivious programmable PRF receiver.
 ///
 /// This implements the hashing-based OPPRF receiver in Figure 7 of the paper. It
@@ -298,19 +589,57 @@ impl<OPRF: OprfSender<Seed = Block512, Input = Block, Output = Block512> + SemiH
 /// which itself uses an OPRF.
 pub struct Receiver<OPRF: OprfReceiver + SemiHonest> {
     oprf: OPRF,
+    backend: Backend,
 }
 
 impl<OPRF: OprfReceiver<Seed = Block512, Input = Block, Output = Block512> + SemiHonest>
     Receiver<OPRF>
 {
-    /// Initialize the OPPRF receiver.
+    /// Initialize the OPPRF receiver, using the default backend. The sender
+    /// must use `init` too.
     pub fn init<C, RNG>(channel: &mut C, rng: &mut RNG) -> Result<Self, Error>
     where
         C: AbstractChannel,
         RNG: CryptoRng + Rng,
     {
         let oprf = OPRF::init(channel, rng)?;
-        Ok(Self { oprf })
+        let backend = Backend::default();
+        Ok(Self { oprf, backend })
+    }
+
+    /// Initialize the OPPRF receiver, using `backend` to program the OPPRF.
+    /// Fails if the sender uses a different backend.
+    pub fn init_with_backend<C, RNG>(
+        channel: &mut C,
+        backend: Backend,
+        rng: &mut RNG,
+    ) -> Result<Self, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        receive_backend(channel, backend)?;
+        let oprf = OPRF::init(channel, rng)?;
+        Ok(Self { oprf, backend })
+    }
+
+    /// Initialize the OPPRF receiver, using `backend` to program the OPPRF and
+    /// security parameters `params`, which are passed on to the underlying
+    /// OPRF. Fails if the sender uses a different backend or parameters.
+    pub fn init_with_params<C, RNG>(
+        channel: &mut C,
+        backend: Backend,
+        params: SecurityParams,
+        rng: &mut RNG,
+    ) -> Result<Self, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        check_params(params)?;
+        receive_backend(channel, backend)?;
+        let oprf = OPRF::init_with_params(channel, params, rng)?;
+        Ok(Self { oprf, backend })
     }
 
     /// Run the OPPRF on inputs provided by the `inputs` slice.
@@ -367,24 +696,167 @@ impl<OPRF: OprfReceiver<Seed = Block512, Input = Block, Output = Block512> + Sem
             .collect::<Vec<Block>>();
         let oprf_outputs = self.oprf.receive(channel, &items, rng)?;
 
-        let zero = Block512::default();
-        for (item, output) in table.items.into_iter().zip(oprf_outputs.into_iter()) {
-            let m = channel.read_usize()?;
-            let v = channel.read_block()?;
-            let h = hash_output(v, output, m);
-            let mut output = output;
-            for i in 0..m {
-                let entry = channel.read_block512()?;
-                output ^= if i == h { entry } else { zero };
+        match self.backend {
+            Backend::Table => {
+                let zero = Block512::default();
+                for (item, output) in table.items.into_iter().zip(oprf_outputs.into_iter()) {
+                    let m = channel.read_usize()?;
+                    let v = channel.read_block()?;
+                    let h = hash_output(v, output, m);
+                    let mut output = output;
+                    for i in 0..m {
+                        let entry = channel.read_block512()?;
+                        output ^= if i == h { entry } else { zero };
+                    }
+                    if let Some(item) = item {
+                        outputs[item.index] = output;
+                    }
+                }
             }
-            if let Some(item) = item {
-                outputs[item.index] = output;
+            Backend::Polynomial => {
+                let mut coeffs = Vec::with_capacity(params.beta2);
+                for (j, (item, output)) in table
+                    .items
+                    .into_iter()
+                    .zip(oprf_outputs.into_iter())
+                    .enumerate()
+                {
+                    let beta = if j < params.m1 {
+                        params.beta1
+                    } else {
+                        params.beta2
+                    };
+                    coeffs.clear();
+                    for _ in 0..beta {
+                        coeffs.push(channel.read_block512()?);
+                    }
+                    if let Some(item) = item {
+                        outputs[item.index] = output ^ polynomial::evaluate(&coeffs, item.entry);
+                    }
+                }
+            }
+            Backend::Okvs => {
+                let key = channel.read_block()?;
+                let okvs = okvs::Okvs::new(key, params.okvs_npoints());
+                let table_ = (0..okvs.size())
+                    .map(|_| channel.read_block512())
+                    .collect::<Result<Vec<Block512>, _>>()?;
+                for (j, (item, output)) in table
+                    .items
+                    .into_iter()
+                    .zip(oprf_outputs.into_iter())
+                    .enumerate()
+                {
+                    if let Some(item) = item {
+                        outputs[item.index] = output ^ okvs.decode(&table_, item.entry, j);
+                    }
+                }
             }
         }
         Ok(outputs)
     }
 }
 
+impl<OPRF: OprfReceiver<Seed = Block512, Input = Block, Output = Block512> + SemiHonest>
+    Receiver<OPRF>
+{
+    /// Starts a chunked session over `nchunks` partitions, in each of which
+    /// we have at most `chunk_size` inputs. Fails if the sender uses different
+    /// parameters.
+    pub fn session<C: AbstractChannel>(
+        &mut self,
+        channel: &mut C,
+        nchunks: usize,
+        chunk_size: usize,
+    ) -> Result<ReceiverSession<'_, OPRF>, Error> {
+        let nchunks_ = channel.read_usize()?;
+        let chunk_size_ = channel.read_usize()?;
+        let key = channel.read_block()?;
+        if nchunks_ != nchunks || chunk_size_ != chunk_size {
+            return Err(Error::Other(format!(
+                "OPPRF session mismatch: expected {} chunks of size {}",
+                nchunks, chunk_size
+            )));
+        }
+        Ok(ReceiverSession {
+            opprf: self,
+            key: Aes128::new(key),
+            nchunks,
+            chunk_size,
+            index: 0,
+        })
+    }
+}
+
+/// A chunked KMPRT session from the receiver's point-of-view.
+///
+/// The `i`th call to `next_chunk` must be given exactly the inputs whose
+/// partition is `i`.
+pub struct ReceiverSession<'a, OPRF: OprfReceiver + SemiHonest> {
+    opprf: &'a mut Receiver<OPRF>,
+    key: Aes128,
+    nchunks: usize,
+    chunk_size: usize,
+    index: usize,
+}
+
+impl<'a, OPRF: OprfReceiver<Seed = Block512, Input = Block, Output = Block512> + SemiHonest>
+    ReceiverSession<'a, OPRF>
+{
+    /// Returns the partition of `x`.
+    pub fn partition(&self, x: Block) -> usize {
+        hash_input_keyed(&self.key, x, self.nchunks)
+    }
+
+    /// Runs the OPPRF on the next partition, whose inputs are `inputs`,
+    /// returning the OPPRF outputs.
+    pub fn next_chunk<C, RNG>(
+        &mut self,
+        channel: &mut C,
+        inputs: &[Block],
+        rng: &mut RNG,
+    ) -> Result<Vec<Block512>, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        if inputs.len() > self.chunk_size {
+            return Err(Error::InvalidInputLength);
+        }
+        check_partition(&self.key, self.nchunks, self.index, inputs.iter().cloned())?;
+        // Pad with random inputs, so the sender doesn't learn the size of the
+        // partition.
+        let padded = inputs
+            .iter()
+            .cloned()
+            .chain((inputs.len()..self.chunk_size).map(|_| rng.gen()))
+            .collect::<Vec<Block>>();
+        let mut outputs = self.opprf.receive(channel, &padded, rng)?;
+        outputs.truncate(inputs.len());
+        self.index += 1;
+        Ok(outputs)
+    }
+
+    /// Returns the partition the next chunk must contain.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns whether every partition has been processed.
+    pub fn is_done(&self) -> bool {
+        self.index == self.nchunks
+    }
+}
+
+impl<OPRF: OprfSender<Seed = Block512, Input = Block, Output = Block512> + SemiHonest> SemiHonest
+    for Sender<OPRF>
+{
+}
+impl<OPRF: OprfReceiver<Seed = Block512, Input = Block, Output = Block512> + SemiHonest> SemiHonest
+    for Receiver<OPRF>
+{
+}
+
 //
 // Tests.
 //
@@ -393,13 +865,22 @@ impl<OPRF: OprfReceiver<Seed = Block512, Input = Block, Output = Block512> + Sem
 mod tests {
     use super::*;
     use crate::oprf::{KmprtReceiver, KmprtSender};
-    use scuttlebutt::{AesRng, Channel};
+    use scuttlebutt::{AesRng, Channel, TrackChannel};
     use std::{
         io::{BufReader, BufWriter},
         os::unix::net::UnixStream,
     };
 
     fn _test_opprf_points(ninputs: usize, npoints: usize, npoints_bound: usize) {
+        _test_opprf_points_with(ninputs, npoints, npoints_bound, Backend::Table)
+    }
+
+    fn _test_opprf_points_with(
+        ninputs: usize,
+        npoints: usize,
+        npoints_bound: usize,
+        backend: Backend,
+    ) {
         assert!(ninputs <= npoints);
         assert!(npoints <= npoints_bound);
         let mut rng = AesRng::new();
@@ -421,7 +902,7 @@ mod tests {
             let reader = BufReader::new(sender.try_clone().unwrap());
             let writer = BufWriter::new(sender);
             let mut channel = Channel::new(reader, writer);
-            let mut oprf = KmprtSender::init(&mut channel, &mut rng).unwrap();
+            let mut oprf = KmprtSender::init_with_backend(&mut channel, backend, &mut rng).unwrap();
             let _ = oprf
                 .send(&mut channel, &points_, ninputs, &mut rng)
                 .unwrap();
@@ -430,7 +911,7 @@ mod tests {
         let reader = BufReader::new(receiver.try_clone().unwrap());
         let writer = BufWriter::new(receiver);
         let mut channel = Channel::new(reader, writer);
-        let mut oprf = KmprtReceiver::init(&mut channel, &mut rng).unwrap();
+        let mut oprf = KmprtReceiver::init_with_backend(&mut channel, backend, &mut rng).unwrap();
         let outputs = oprf.receive(&mut channel, &xs, &mut rng).unwrap();
         handle.join().unwrap();
         let mut okay = true;
@@ -454,6 +935,138 @@ mod tests {
         // Settings for PSTY with `n = 2^16`.
         _test_opprf_points(83231, 196608, 196608);
     }
+
+    #[test]
+    fn test_opprf_polynomial() {
+        _test_opprf_points_with(1, 8, 8, Backend::Polynomial);
+        _test_opprf_points_with(21, 48, 48, Backend::Polynomial);
+        _test_opprf_points_with(326, 768, 768, Backend::Polynomial);
+        _test_opprf_points_with(5202, 12288, 12288, Backend::Polynomial);
+    }
+
+    #[test]
+    fn test_opprf_okvs() {
+        _test_opprf_points_with(1, 8, 8, Backend::Okvs);
+        _test_opprf_points_with(21, 48, 48, Backend::Okvs);
+        _test_opprf_points_with(326, 768, 768, Backend::Okvs);
+        _test_opprf_points_with(5202, 12288, 12288, Backend::Okvs);
+        _test_opprf_points_with(83231, 196608, 196608, Backend::Okvs);
+    }
+
+    fn _test_opprf_session(ninputs: usize, npoints: usize, nchunks: usize, backend: Backend) {
+        let mut rng = AesRng::new();
+        let points = (0..npoints)
+            .map(|_| (rng.gen::<Block>(), rng.gen()))
+            .collect::<Vec<(Block, Block512)>>();
+        let xs = points[0..ninputs]
+            .iter()
+            .map(|(x, _)| *x)
+            .collect::<Vec<Block>>();
+        // Leave enough room for the partitions to be unbalanced.
+        let chunk_size = 2 * ninputs / nchunks + 16;
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let points_ = points.clone();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut oprf = KmprtSender::init_with_backend(&mut channel, backend, &mut rng).unwrap();
+            let mut session = oprf
+                .session(&mut channel, nchunks, chunk_size, &mut rng)
+                .unwrap();
+            while !session.is_done() {
+                let chunk = points_
+                    .iter()
+                    .filter(|(x, _)| session.partition(*x) == session.index())
+                    .cloned()
+                    .collect::<Vec<(Block, Block512)>>();
+                session.next_chunk(&mut channel, &chunk, &mut rng).unwrap();
+            }
+        });
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut oprf = KmprtReceiver::init_with_backend(&mut channel, backend, &mut rng).unwrap();
+        let mut session = oprf.session(&mut channel, nchunks, chunk_size).unwrap();
+        let mut count = 0;
+        while !session.is_done() {
+            let chunk = xs
+                .iter()
+                .filter(|x| session.partition(**x) == session.index())
+                .cloned()
+                .collect::<Vec<Block>>();
+            let outputs = session.next_chunk(&mut channel, &chunk, &mut rng).unwrap();
+            for (x, y) in chunk.iter().zip(outputs.iter()) {
+                let (_, y_) = points.iter().find(|(x_, _)| x_ == x).unwrap();
+                assert_eq!(y, y_);
+            }
+            count += chunk.len();
+        }
+        assert_eq!(count, ninputs);
+        assert!(session.next_chunk(&mut channel, &[], &mut rng).is_err());
+        handle.join().unwrap();
+    }
+
+    // Returns the number of kilobits the receiver reads while running the
+    // OPPRF on `ninputs` inputs against `npoints` programmed points.
+    fn _hint_size(ninputs: usize, npoints: usize, backend: Backend) -> f64 {
+        let mut rng = AesRng::new();
+        let points = (0..npoints)
+            .map(|_| (rng.gen::<Block>(), rng.gen()))
+            .collect::<Vec<(Block, Block512)>>();
+        let xs = (0..ninputs).map(|_| rng.gen()).collect::<Vec<Block>>();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut oprf = KmprtSender::init_with_backend(&mut channel, backend, &mut rng).unwrap();
+            oprf.send(&mut channel, &points, ninputs, &mut rng).unwrap();
+        });
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = TrackChannel::new(reader, writer);
+        let mut oprf = KmprtReceiver::init_with_backend(&mut channel, backend, &mut rng).unwrap();
+        channel.clear();
+        oprf.receive(&mut channel, &xs, &mut rng).unwrap();
+        handle.join().unwrap();
+        channel.kilobits_read()
+    }
+
+    #[test]
+    fn test_okvs_hides_npoints() {
+        assert_eq!(
+            _hint_size(64, 1, Backend::Okvs),
+            _hint_size(64, 128, Backend::Okvs)
+        );
+    }
+
+    #[test]
+    fn test_opprf_session() {
+        _test_opprf_session(1, 8, 1, Backend::Table);
+        _test_opprf_session(326, 768, 4, Backend::Table);
+        _test_opprf_session(326, 768, 4, Backend::Okvs);
+        _test_opprf_session(5202, 12288, 16, Backend::Polynomial);
+    }
+
+    #[test]
+    fn test_backend_mismatch() {
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let _ = KmprtSender::init_with_backend(&mut channel, Backend::Okvs, &mut rng);
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        assert!(KmprtReceiver::init(&mut channel, &mut rng).is_err());
+    }
 }
 
 //
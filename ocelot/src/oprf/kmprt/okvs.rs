@@ -0,0 +1,274 @@
+// -*- mode: rust; -*-
+//
+// This file is part of ocelot.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! A linear oblivious key-value store, built as a garbled cuckoo table with
+//! three hash functions plus a small dense part (cf. PaXoS,
+//! <https://eprint.iacr.org/2020/193>).
+//!
+//! Each key `x` is mapped to a row consisting of three positions in a sparse
+//! part of size `m ≈ 1.3n` and `DENSE` random bits selecting positions in the
+//! dense part. Decoding `x` XORs together the entries at the selected
+//! positions. Encoding peels the hypergraph formed by the sparse positions,
+//! which succeeds for all but a tiny "core" of rows when `m > 1.23n`, solves
+//! that core (together with the dense part) by Gaussian elimination, and then
+//! fills in the peeled rows in reverse order. This never needs to retry with
+//! new hash functions; encoding only fails if the core has rank deficiency
+//! even with the dense columns, which happens with probability around
+//! `2^-DENSE`.
+
+use rand::{CryptoRng, Rng};
+use scuttlebutt::{Aes128, Block, Block512};
+
+// Size of the sparse part relative to the number of keys.
+const EXPANSION: f64 = 1.3;
+// The number of dense columns.
+const DENSE: usize = 64;
+
+pub struct Okvs {
+    aes: Aes128,
+    // Size of each of the three segments of the sparse part. Each hash
+    // function picks a position in its own segment, so that a row never hits
+    // the same position twice.
+    segment: usize,
+}
+
+#[derive(Debug)]
+pub struct EncodingError;
+
+impl std::fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "OKVS encoding failed")
+    }
+}
+
+impl Okvs {
+    /// Create a store for `n` keys using hash key `key`.
+    pub fn new(key: Block, n: usize) -> Self {
+        let segment = std::cmp::max(1, ((n as f64) * EXPANSION / 3.0).ceil() as usize);
+        Self {
+            aes: Aes128::new(key),
+            segment,
+        }
+    }
+
+    /// The number of entries in an encoding.
+    pub fn size(&self) -> usize {
+        3 * self.segment + DENSE
+    }
+
+    // Compute the row for key `x` with tweak `tweak`.
+    #[inline]
+    fn row(&self, x: Block, tweak: usize) -> ([usize; 3], u64) {
+        let h = self.aes.encrypt(x) ^ x ^ Block::from(tweak as u128);
+        let a = u128::from(self.aes.encrypt(h) ^ h);
+        let b = u128::from(self.aes.encrypt(h.flip()) ^ h);
+        let segment = self.segment as u128;
+        let positions = [
+            (a as u64 as u128 % segment) as usize,
+            ((a >> 64) % segment) as usize + self.segment,
+            (b as u64 as u128 % segment) as usize + 2 * self.segment,
+        ];
+        (positions, (b >> 64) as u64)
+    }
+
+    /// Decode the value for key `x` with tweak `tweak` from `table`.
+    pub fn decode(&self, table: &[Block512], x: Block, tweak: usize) -> Block512 {
+        let (positions, dense) = self.row(x, tweak);
+        let mut out = table[positions[0]] ^ table[positions[1]] ^ table[positions[2]];
+        let base = 3 * self.segment;
+        for (i, entry) in table[base..base + DENSE].iter().enumerate() {
+            if (dense >> i) & 1 == 1 {
+                out ^= *entry;
+            }
+        }
+        out
+    }
+
+    /// Encode the `(key, tweak, value)` triples in `points`. The `(key,
+    /// tweak)` pairs must be distinct.
+    pub fn encode<RNG: CryptoRng + Rng>(
+        &self,
+        points: &[(Block, usize, Block512)],
+        rng: &mut RNG,
+    ) -> Result<Vec<Block512>, EncodingError> {
+        let m = 3 * self.segment;
+        let rows = points
+            .iter()
+            .map(|(x, tweak, _)| self.row(*x, *tweak))
+            .collect::<Vec<([usize; 3], u64)>>();
+        // Peel the hypergraph: repeatedly find a position hit by exactly one
+        // remaining row, and remove that row.
+        let mut degree = vec![0usize; m];
+        // XOR of the indices of the remaining rows hitting each position, so
+        // that when the degree is one this is the index of that row.
+        let mut incident = vec![0usize; m];
+        for (i, (positions, _)) in rows.iter().enumerate() {
+            for p in positions.iter() {
+                degree[*p] += 1;
+                incident[*p] ^= i;
+            }
+        }
+        let mut queue = (0..m).filter(|p| degree[*p] == 1).collect::<Vec<usize>>();
+        let mut peeled = vec![false; rows.len()];
+        let mut order = Vec::with_capacity(rows.len());
+        while let Some(p) = queue.pop() {
+            if degree[p] != 1 {
+                continue;
+            }
+            let i = incident[p];
+            peeled[i] = true;
+            order.push((i, p));
+            for q in rows[i].0.iter() {
+                degree[*q] -= 1;
+                incident[*q] ^= i;
+                if degree[*q] == 1 {
+                    queue.push(*q);
+                }
+            }
+        }
+        let mut table = (0..m + DENSE)
+            .map(|_| rng.gen::<Block512>())
+            .collect::<Vec<Block512>>();
+        // Solve for the rows left in the core.
+        let core = (0..rows.len())
+            .filter(|i| !peeled[*i])
+            .collect::<Vec<usize>>();
+        if !core.is_empty() {
+            self.solve_core(&rows, points, &core, &mut table)?;
+        }
+        // Fill in the peeled rows, last peeled first. When a row is peeled, no
+        // row peeled later (nor any row in the core) uses its position `p`,
+        // and every other position it uses is either fixed already or is the
+        // position of a row peeled later.
+        for (i, p) in order.into_iter().rev() {
+            table[p] = Block512::default();
+            let (positions, dense) = rows[i];
+            let mut value = points[i].2;
+            for q in positions.iter() {
+                value ^= table[*q];
+            }
+            for (j, entry) in table[m..m + DENSE].iter().enumerate() {
+                if (dense >> j) & 1 == 1 {
+                    value ^= *entry;
+                }
+            }
+            table[p] = value;
+        }
+        Ok(table)
+    }
+
+    // Solve the rows in `core` by Gaussian elimination over the columns they
+    // use, leaving all other entries of `table` untouched.
+    fn solve_core(
+        &self,
+        rows: &[([usize; 3], u64)],
+        points: &[(Block, usize, Block512)],
+        core: &[usize],
+        table: &mut [Block512],
+    ) -> Result<(), EncodingError> {
+        let m = 3 * self.segment;
+        // The columns used by the core: its sparse positions, then the dense
+        // columns.
+        let mut columns = core
+            .iter()
+            .flat_map(|i| rows[*i].0.iter().cloned())
+            .collect::<Vec<usize>>();
+        columns.sort();
+        columns.dedup();
+        columns.extend(m..m + DENSE);
+        let nwords = columns.len().div_ceil(64);
+        let mut matrix = core
+            .iter()
+            .map(|i| {
+                let (positions, dense) = rows[*i];
+                let mut bits = vec![0u64; nwords];
+                for p in positions.iter() {
+                    let c = columns.binary_search(p).unwrap();
+                    bits[c / 64] |= 1 << (c % 64);
+                }
+                for j in 0..DENSE {
+                    if (dense >> j) & 1 == 1 {
+                        let c = columns.len() - DENSE + j;
+                        bits[c / 64] |= 1 << (c % 64);
+                    }
+                }
+                (bits, points[*i].2)
+            })
+            .collect::<Vec<(Vec<u64>, Block512)>>();
+        // Reduce to reduced row echelon form.
+        let mut pivots = Vec::with_capacity(matrix.len());
+        for r in 0..matrix.len() {
+            let c = (0..columns.len())
+                .find(|c| (matrix[r].0[c / 64] >> (c % 64)) & 1 == 1)
+                .ok_or(EncodingError)?;
+            let (bits, value) = matrix[r].clone();
+            for (r_, row) in matrix.iter_mut().enumerate() {
+                if r_ != r && (row.0[c / 64] >> (c % 64)) & 1 == 1 {
+                    for (w, w_) in row.0.iter_mut().zip(bits.iter()) {
+                        *w ^= *w_;
+                    }
+                    row.1 ^= value;
+                }
+            }
+            pivots.push(c);
+        }
+        // Non-pivot columns keep their random values; solve for the pivots.
+        for ((bits, value), c) in matrix.iter().zip(pivots.iter()) {
+            let mut value = *value;
+            for (c_, column) in columns.iter().enumerate() {
+                if c_ != *c && (bits[c_ / 64] >> (c_ % 64)) & 1 == 1 {
+                    value ^= table[*column];
+                }
+            }
+            table[columns[*c]] = value;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scuttlebutt::AesRng;
+
+    fn _test_okvs(n: usize) {
+        let mut rng = AesRng::new();
+        let points = (0..n)
+            .map(|i| (rng.gen::<Block>(), i % 5, rng.gen::<Block512>()))
+            .collect::<Vec<(Block, usize, Block512)>>();
+        let okvs = Okvs::new(rng.gen(), n);
+        let table = okvs.encode(&points, &mut rng).unwrap();
+        assert_eq!(table.len(), okvs.size());
+        for (x, tweak, y) in points.iter() {
+            assert_eq!(okvs.decode(&table, *x, *tweak), *y);
+        }
+    }
+
+    #[test]
+    fn test_okvs() {
+        _test_okvs(0);
+        _test_okvs(1);
+        _test_okvs(2);
+        _test_okvs(10);
+        _test_okvs(1000);
+        _test_okvs(100_000);
+    }
+
+    #[test]
+    fn test_okvs_core() {
+        // With a sparse part this small, peeling leaves a large core, which the
+        // dense columns have to absorb.
+        let mut rng = AesRng::new();
+        let points = (0..40)
+            .map(|i| (rng.gen::<Block>(), i, rng.gen::<Block512>()))
+            .collect::<Vec<(Block, usize, Block512)>>();
+        let okvs = Okvs::new(rng.gen(), 3);
+        let table = okvs.encode(&points, &mut rng).unwrap();
+        for (x, tweak, y) in points.iter() {
+            assert_eq!(okvs.decode(&table, *x, *tweak), *y);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+// -*- mode: rust; -*-
+//
+// This file is part of ocelot.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Polynomials over `GF(2^128)`, evaluated on `Block`s and taking values in
+//! `Block512`s. A `Block512` is treated as four independent elements of
+//! `GF(2^128)`, so each polynomial is really four polynomials sharing the same
+//! evaluation points.
+
+use scuttlebutt::{Block, Block512};
+
+#[inline]
+fn scale(y: Block512, c: Block) -> [Block; 4] {
+    let ys: [Block; 4] = y.into();
+    [
+        ys[0].gfmul(c),
+        ys[1].gfmul(c),
+        ys[2].gfmul(c),
+        ys[3].gfmul(c),
+    ]
+}
+
+// Invert all of `xs` with a single field inversion.
+fn batch_invert(xs: &[Block]) -> Vec<Block> {
+    let mut prefix = Vec::with_capacity(xs.len());
+    let mut acc = Block::from(1u128);
+    for x in xs.iter() {
+        prefix.push(acc);
+        acc = acc.gfmul(*x);
+    }
+    let mut inv = acc.gfinv();
+    let mut out = vec![Block::default(); xs.len()];
+    for i in (0..xs.len()).rev() {
+        out[i] = inv.gfmul(prefix[i]);
+        inv = inv.gfmul(xs[i]);
+    }
+    out
+}
+
+/// Compute the coefficients (lowest degree first) of the unique polynomial of
+/// degree less than `points.len()` passing through all of `points`. The
+/// `x`-coordinates must be distinct.
+pub fn interpolate(points: &[(Block, Block512)]) -> Vec<Block512> {
+    let n = points.len();
+    // Compute `M(X) = ∏ (X - x_i)`.
+    let mut m = vec![Block::default(); n + 1];
+    m[0] = Block::from(1u128);
+    for (i, (x, _)) in points.iter().enumerate() {
+        for k in (1..=i + 1).rev() {
+            m[k] = m[k - 1] ^ m[k].gfmul(*x);
+        }
+        m[0] = m[0].gfmul(*x);
+    }
+    // For each point, compute `q_i(X) = M(X) / (X - x_i)` and the weight
+    // `w_i = q_i(x_i)`, so that `P(X) = ∑ y_i / w_i · q_i(X)`.
+    let mut qs = vec![Block::default(); n * n];
+    let mut ws = Vec::with_capacity(n);
+    for (q, (x, _)) in qs.chunks_mut(n).zip(points.iter()) {
+        q[n - 1] = m[n];
+        for k in (1..n).rev() {
+            q[k - 1] = m[k] ^ x.gfmul(q[k]);
+        }
+        ws.push(
+            q.iter()
+                .rev()
+                .fold(Block::default(), |acc, c| acc.gfmul(*x) ^ *c),
+        );
+    }
+    let ws = batch_invert(&ws);
+    let mut coeffs = vec![[Block::default(); 4]; n];
+    for ((q, (_, y)), w) in qs.chunks(n).zip(points.iter()).zip(ws.into_iter()) {
+        let c = scale(*y, w);
+        for (coeff, q) in coeffs.iter_mut().zip(q.iter()) {
+            for (coeff, c) in coeff.iter_mut().zip(c.iter()) {
+                *coeff ^= c.gfmul(*q);
+            }
+        }
+    }
+    coeffs.into_iter().map(Block512::from).collect()
+}
+
+/// Evaluate the polynomial with coefficients `coeffs` at `x`.
+pub fn evaluate(coeffs: &[Block512], x: Block) -> Block512 {
+    let mut acc = [Block::default(); 4];
+    for coeff in coeffs.iter().rev() {
+        let coeff: [Block; 4] = (*coeff).into();
+        for (acc, c) in acc.iter_mut().zip(coeff.iter()) {
+            *acc = acc.gfmul(x) ^ *c;
+        }
+    }
+    Block512::from(acc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interpolate() {
+        for n in [1, 2, 3, 30, 63].iter() {
+            let points = (0..*n)
+                .map(|_| rand::random::<(Block, Block512)>())
+                .collect::<Vec<(Block, Block512)>>();
+            let coeffs = interpolate(&points);
+            assert_eq!(coeffs.len(), *n);
+            for (x, y) in points.iter() {
+                assert_eq!(evaluate(&coeffs, *x), *y);
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+// -*- mode: rust; -*-
+//
+// This file is part of ocelot.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+use rand::{RngCore, SeedableRng};
+use scuttlebutt::{utils as scutils, AesRng, Block};
+
+/// The length of a codeword, in bits.
+pub const CODEWORD_LENGTH: usize = 768;
+/// The length of a codeword, in bytes.
+pub const CODEWORD_BYTES: usize = CODEWORD_LENGTH / 8;
+
+/// A random binary linear code mapping 128-bit messages to 768-bit codewords.
+///
+/// The generator matrix is expanded from a seed, which both parties obtain by
+/// coin-tossing. A random `[768, 128]` code has minimum distance at least 128
+/// except with probability around `2^-140`, which is what the OOS OPRF needs.
+///
+/// Encoding uses one lookup table per message byte, each holding the 256
+/// combinations of the corresponding eight rows of the generator matrix.
+pub struct LinearCode {
+    tables: Vec<u8>,
+}
+
+impl LinearCode {
+    pub fn new(seed: Block) -> Self {
+        let mut rng = AesRng::from_seed(seed);
+        let mut tables = vec![0u8; 16 * 256 * CODEWORD_BYTES];
+        for table in tables.chunks_mut(256 * CODEWORD_BYTES) {
+            let mut rows = [[0u8; CODEWORD_BYTES]; 8];
+            for row in rows.iter_mut() {
+                rng.fill_bytes(row);
+            }
+            // Entry `v` is entry `v` with its top bit cleared, plus the row
+            // corresponding to that bit.
+            for v in 1..256 {
+                let top = 7 - (v as u8).leading_zeros() as usize;
+                let (lower, upper) = table.split_at_mut(v * CODEWORD_BYTES);
+                let prev = v ^ (1 << top);
+                let entry = &mut upper[0..CODEWORD_BYTES];
+                entry.copy_from_slice(&lower[prev * CODEWORD_BYTES..(prev + 1) * CODEWORD_BYTES]);
+                scutils::xor_inplace(entry, &rows[top]);
+            }
+        }
+        Self { tables }
+    }
+
+    /// Encode `m`, XORing the resulting codeword into `out`.
+    #[inline]
+    pub fn encode_xor(&self, m: Block, out: &mut [u8]) {
+        debug_assert_eq!(out.len(), CODEWORD_BYTES);
+        for (i, byte) in m.as_ref().iter().enumerate() {
+            let offset = (i * 256 + *byte as usize) * CODEWORD_BYTES;
+            scutils::xor_inplace(out, &self.tables[offset..offset + CODEWORD_BYTES]);
+        }
+    }
+
+    /// Encode `m` into `out`.
+    #[inline]
+    pub fn encode(&self, m: Block, out: &mut [u8]) {
+        for x in out.iter_mut() {
+            *x = 0;
+        }
+        self.encode_xor(m, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linear() {
+        let code = LinearCode::new(rand::random::<Block>());
+        let (a, b) = (rand::random::<Block>(), rand::random::<Block>());
+        let mut ca = [0u8; CODEWORD_BYTES];
+        let mut cb = [0u8; CODEWORD_BYTES];
+        let mut cab = [0u8; CODEWORD_BYTES];
+        code.encode(a, &mut ca);
+        code.encode(b, &mut cb);
+        code.encode(a ^ b, &mut cab);
+        scutils::xor_inplace(&mut ca, &cb);
+        assert_eq!(ca.to_vec(), cab.to_vec());
+        code.encode(Block::default(), &mut ca);
+        assert_eq!(ca.to_vec(), vec![0u8; CODEWORD_BYTES]);
+    }
+}
@@ -6,18 +6,41 @@
 
 //! Oblivious PRF traits + instantiations.
 
+pub mod dh;
 pub mod kkrt;
 pub mod kmprt;
+mod linear_code;
+pub mod oos;
 mod prc;
 
-use crate::{errors::Error, ot};
+use crate::{errors::Error, ot, params::SecurityParams};
 use rand::{CryptoRng, Rng};
 use scuttlebutt::AbstractChannel;
 
+/// Diffie-Hellman oblivious PRF sender.
+pub type DhSender = dh::Sender;
+/// Diffie-Hellman oblivious PRF receiver.
+pub type DhReceiver = dh::Receiver;
 /// KKRT oblivious PRF sender using ALSZ OT extension with Chou-Orlandi as the base OT.
 pub type KkrtSender = kkrt::Sender<ot::AlszReceiver>;
 /// KKRT oblivious PRF receiver using ALSZ OT extension with Chou-Orlandi as the base OT.
 pub type KkrtReceiver = kkrt::Receiver<ot::AlszSender>;
+/// KKRT oblivious PRF sender using ALSZ OT extension with Naor-Pinkas as the base OT.
+pub type KkrtNaorPinkasSender = kkrt::Sender<ot::AlszNaorPinkasReceiver>;
+/// KKRT oblivious PRF receiver using ALSZ OT extension with Naor-Pinkas as the base OT.
+pub type KkrtNaorPinkasReceiver = kkrt::Receiver<ot::AlszNaorPinkasSender>;
+/// KKRT oblivious PRF sender using ALSZ OT extension with Masny-Rindal as the base OT.
+pub type KkrtMasnyRindalSender = kkrt::Sender<ot::AlszMasnyRindalReceiver>;
+/// KKRT oblivious PRF receiver using ALSZ OT extension with Masny-Rindal as the base OT.
+pub type KkrtMasnyRindalReceiver = kkrt::Receiver<ot::AlszMasnyRindalSender>;
+/// OOS malicious OPRF sender using KOS OT extension with Chou-Orlandi as the base OT.
+pub type OosSender = oos::Sender<ot::KosReceiver>;
+/// OOS malicious OPRF receiver using KOS OT extension with Chou-Orlandi as the base OT.
+pub type OosReceiver = oos::Receiver<ot::KosSender>;
+/// OOS malicious OPRF sender using KOS OT extension with Masny-Rindal as the base OT.
+pub type OosMasnyRindalSender = oos::Sender<ot::KosMasnyRindalReceiver>;
+/// OOS malicious OPRF receiver using KOS OT extension with Masny-Rindal as the base OT.
+pub type OosMasnyRindalReceiver = oos::Receiver<ot::KosMasnyRindalSender>;
 /// KMPRT hash-based OPPRF sender, using KKRT as the underlying OPRF.
 pub type KmprtSender = kmprt::Sender<KkrtSender>;
 /// KMPRT hash-based OPPRF receiver, using KKRT as the underlying OPRF.
@@ -46,6 +69,16 @@ where
         channel: &mut C,
         rng: &mut RNG,
     ) -> Result<Self, Error>;
+    /// Runs any one-time initialization, using the security parameters
+    /// `params`. Protocols whose security does not depend on `params` just
+    /// call `init`.
+    fn init_with_params<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        _: SecurityParams,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        Self::init(channel, rng)
+    }
     /// Runs `m` OPRF instances as the sender, returning the OPRF seeds.
     fn send<C: AbstractChannel, RNG: CryptoRng + Rng>(
         &mut self,
@@ -67,6 +100,16 @@ where
         channel: &mut C,
         rng: &mut RNG,
     ) -> Result<Self, Error>;
+    /// Runs any one-time initialization, using the security parameters
+    /// `params`. Protocols whose security does not depend on `params` just
+    /// call `init`.
+    fn init_with_params<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        _: SecurityParams,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        Self::init(channel, rng)
+    }
     /// Runs the oblivious PRF on inputs `inputs`, returning the OPRF outputs.
     fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
         &mut self,
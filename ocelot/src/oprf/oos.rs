@@ -0,0 +1,564 @@
+// -*- mode: rust; -*-
+//
+// This file is part of ocelot.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Implementation of the batched oblivious PRF of Orrù, Orsini, and Scholl
+//! (cf. <https://eprint.iacr.org/2016/933>), which is secure against a
+//! malicious receiver.
+//!
+//! The protocol is KKRT with two changes: the pseudorandom code is replaced by a
+//! linear code, and the receiver proves that it used that code consistently
+//! with a KOS-style check. Each of the `σ` checks reveals a random linear
+//! combination of the receiver's rows, masked by a padding row which is used
+//! in that check only. Since the code is linear, the sender can verify each
+//! combination against its own rows, and a receiver who deviates passes each
+//! check with probability at most one half. The statistical security level `σ`
+//! comes from the `SecurityParams` given to `init_with_params` (see `params`),
+//! and must be at most 64.
+//!
+//! `init_with_params` begins with the sender sending its parameters, which the
+//! receiver checks against its own. `init` uses the defaults and starts
+//! directly with the base OTs, as OOS peers that predate `SecurityParams` do.
+//! Both parties must initialize the same way.
+//!
+//! The underlying OT must be maliciously secure, and using a semi-honest one
+//! fails to compile:
+//!
+//! ```compile_fail
+//! use ocelot::{oprf::oos, ot::AlszReceiver};
+//!
+//! fn evaluate(_: oos::Sender<AlszReceiver>) {}
+//! ```
+
+use super::linear_code::{LinearCode, CODEWORD_BYTES, CODEWORD_LENGTH};
+use crate::{
+    errors::Error,
+    oprf::{ObliviousPrf, Receiver as OprfReceiver, Sender as OprfSender},
+    ot::{Receiver as OtReceiver, Sender as OtSender},
+    params::SecurityParams,
+    utils,
+};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use rand::{CryptoRng, Rng, RngCore, SeedableRng};
+use scuttlebutt::{
+    cointoss,
+    utils as scutils,
+    AbstractChannel,
+    AesRng,
+    Block,
+    Block512,
+    Malicious,
+    SemiHonest,
+};
+use sha2::{Digest, Sha512};
+use std::{io::ErrorKind, marker::PhantomData};
+
+/// OOS oblivious PRF seed.
+///
+/// This consists of a row of the sender's OT extension matrix, alongside the
+/// index of that row, which is used to domain-separate the final hash.
+#[derive(Clone, Copy, Debug)]
+pub struct Seed {
+    index: u64,
+    q: [u8; CODEWORD_BYTES],
+}
+
+// The number of rows needed to run `m` OPRF instances: one per instance, one
+// per consistency check, rounded up so that `nrows mod 8 ≡ 0`.
+#[inline]
+fn nrows(m: usize, checks: usize) -> usize {
+    let nrows = m + checks;
+    if nrows % 8 != 0 {
+        nrows + (8 - nrows % 8)
+    } else {
+        nrows
+    }
+}
+
+// Derive the consistency check challenges from the coin-tossed seed. Bit `l`
+// of the `j`th challenge says whether row `j` is part of check `l`.
+fn challenges(seed: Block, m: usize, checks: usize) -> Vec<u64> {
+    let mask = if checks < 64 { (1 << checks) - 1 } else { !0 };
+    let mut rng = AesRng::from_seed(seed);
+    (0..m)
+        .map(|_| rng.next_u64() & mask)
+        .collect()
+}
+
+// Compute the linear combinations of the rows of `rows` (each of which is
+// `width` bytes long) that are checked by the consistency check. Check `l`
+// contains each row `j < m` whose challenge has bit `l` set, and also the
+// padding row `m + l`.
+fn combine(rows: &[u8], width: usize, chis: &[u64], m: usize, checks: usize) -> Vec<u8> {
+    let mut out = rows[m * width..(m + checks) * width].to_vec();
+    for (row, chi) in rows.chunks(width).zip(chis.iter()) {
+        for (l, out) in out.chunks_mut(width).enumerate() {
+            if (chi >> l) & 1 == 1 {
+                scutils::xor_inplace(out, row);
+            }
+        }
+    }
+    out
+}
+
+// Check that `params` can be used with OOS.
+fn check_params(params: SecurityParams) -> Result<(), Error> {
+    if params.oos_checks() > 64 {
+        return Err(Error::Other(format!(
+            "OOS supports statistical security of at most 64, got {}",
+            params.statistical()
+        )));
+    }
+    Ok(())
+}
+
+// Hash the final row for instance `index` into an OPRF output.
+#[inline]
+fn hash(index: u64, row: &[u8]) -> Block512 {
+    let mut hasher = Sha512::new();
+    hasher.input(index.to_le_bytes());
+    hasher.input(row);
+    let mut output = [0u8; 64];
+    output.copy_from_slice(&hasher.result());
+    Block512::from(output)
+}
+
+/// OOS oblivious PRF sender.
+pub struct Sender<OT: OtReceiver + Malicious> {
+    _ot: PhantomData<OT>,
+    s: Vec<bool>,
+    s_: [u8; CODEWORD_BYTES],
+    code: LinearCode,
+    rngs: Vec<AesRng>,
+    counter: u64,
+    params: SecurityParams,
+}
+
+impl<OT: OtReceiver<Msg = Block> + Malicious> Sender<OT> {
+    // Runs the rest of the initialization on top of `ot`, once the
+    // parameters have been agreed on.
+    fn init_with_ot<C, RNG>(
+        channel: &mut C,
+        mut ot: OT,
+        params: SecurityParams,
+        rng: &mut RNG,
+    ) -> Result<Self, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        let mut s_ = [0u8; CODEWORD_BYTES];
+        rng.fill_bytes(&mut s_);
+        let s = utils::u8vec_to_boolvec(&s_);
+        let seed = cointoss::send(channel, &[rng.gen()])?;
+        let code = LinearCode::new(seed[0]);
+        let ks = ot.receive(channel, &s, rng)?;
+        let rngs = ks
+            .into_iter()
+            .map(AesRng::from_seed)
+            .collect::<Vec<AesRng>>();
+        Ok(Self {
+            _ot: PhantomData::<OT>,
+            s,
+            s_,
+            code,
+            rngs,
+            counter: 0,
+            params,
+        })
+    }
+}
+
+impl<OT: OtReceiver<Msg = Block> + Malicious> ObliviousPrf for Sender<OT> {
+    type Seed = Seed;
+    type Input = Block;
+    type Output = Block512;
+}
+
+impl<OT: OtReceiver<Msg = Block> + Malicious> OprfSender for Sender<OT> {
+    fn init<C, RNG>(channel: &mut C, rng: &mut RNG) -> Result<Self, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        let ot = OT::init(channel, rng)?;
+        Self::init_with_ot(channel, ot, SecurityParams::default(), rng)
+    }
+
+    fn init_with_params<C, RNG>(
+        channel: &mut C,
+        params: SecurityParams,
+        rng: &mut RNG,
+    ) -> Result<Self, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        check_params(params)?;
+        params.send(channel)?;
+        let ot = OT::init_with_params(channel, params, rng)?;
+        Self::init_with_ot(channel, ot, params, rng)
+    }
+
+    fn send<C, RNG>(
+        &mut self,
+        channel: &mut C,
+        m: usize,
+        rng: &mut RNG,
+    ) -> Result<Vec<Self::Seed>, Error>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+    {
+        let checks = self.params.oos_checks();
+        let nrows = nrows(m, checks);
+        let ncols = CODEWORD_LENGTH;
+        let mut us = vec![0u8; nrows * ncols / 8];
+        channel.read_bytes(&mut us)?;
+        let mut qs = vec![0u8; nrows * ncols / 8];
+        let s = &self.s;
+        utils::chunks_mut(&mut qs, nrows / 8)
+            .zip(utils::iter_mut(&mut self.rngs))
+            .zip(utils::chunks(&us, nrows / 8))
+            .enumerate()
+            .for_each(|(j, ((q, rng), u))| {
+                rng.fill_bytes(q);
+                if s[j] {
+                    scutils::xor_inplace(q, u);
+                }
+            });
+        let qs = utils::transpose(&qs, ncols, nrows);
+        // Check that the receiver used codewords of the linear code.
+        let seed = cointoss::send(channel, &[rng.gen()])?;
+        let chis = challenges(seed[0], m, checks);
+        let mut ts = vec![0u8; checks * CODEWORD_BYTES];
+        channel.read_bytes(&mut ts)?;
+        let ws = (0..checks)
+            .map(|_| channel.read_block())
+            .collect::<Result<Vec<Block>, _>>()?;
+        let qs_ = combine(&qs, CODEWORD_BYTES, &chis, m, checks);
+        let mut c = [0u8; CODEWORD_BYTES];
+        for ((q, t), w) in qs_
+            .chunks(CODEWORD_BYTES)
+            .zip(ts.chunks(CODEWORD_BYTES))
+            .zip(ws.into_iter())
+        {
+            self.code.encode(w, &mut c);
+            scutils::and_inplace(&mut c, &self.s_);
+            scutils::xor_inplace(&mut c, t);
+            if c[..] != q[..] {
+                return Err(Error::from(std::io::Error::new(
+                    ErrorKind::InvalidData,
+                    "Consistency check failed",
+                )));
+            }
+        }
+        let counter = self.counter;
+        let seeds = qs
+            .chunks(CODEWORD_BYTES)
+            .take(m)
+            .enumerate()
+            .map(|(j, row)| {
+                let mut q = [0u8; CODEWORD_BYTES];
+                q.copy_from_slice(row);
+                Seed {
+                    index: counter + j as u64,
+                    q,
+                }
+            })
+            .collect::<Vec<Self::Seed>>();
+        self.counter += m as u64;
+        Ok(seeds)
+    }
+
+    fn compute(&self, seed: Self::Seed, input: Self::Input) -> Self::Output {
+        let mut c = [0u8; CODEWORD_BYTES];
+        self.code.encode(input, &mut c);
+        scutils::and_inplace(&mut c, &self.s_);
+        scutils::xor_inplace(&mut c, &seed.q);
+        hash(seed.index, &c)
+    }
+}
+
+/// OOS oblivious PRF receiver.
+pub struct Receiver<OT: OtSender + Malicious> {
+    _ot: PhantomData<OT>,
+    code: LinearCode,
+    rngs: Vec<(AesRng, AesRng)>,
+    counter: u64,
+    params: SecurityParams,
+}
+
+impl<OT: OtSender<Msg = Block> + Malicious> Receiver<OT> {
+    // Runs the rest of the initialization on top of `ot`, once the
+    // parameters have been agreed on.
+    fn init_with_ot<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        mut ot: OT,
+        params: SecurityParams,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let seed = cointoss::receive(channel, &[rng.gen()])?;
+        let code = LinearCode::new(seed[0]);
+        let ks = (0..CODEWORD_LENGTH)
+            .map(|_| (rng.gen::<Block>(), rng.gen::<Block>()))
+            .collect::<Vec<(Block, Block)>>();
+        ot.send(channel, &ks, rng)?;
+        let rngs = ks
+            .into_iter()
+            .map(|(k0, k1)| (AesRng::from_seed(k0), AesRng::from_seed(k1)))
+            .collect::<Vec<(AesRng, AesRng)>>();
+        Ok(Self {
+            _ot: PhantomData::<OT>,
+            code,
+            rngs,
+            counter: 0,
+            params,
+        })
+    }
+}
+
+impl<OT: OtSender<Msg = Block> + Malicious> ObliviousPrf for Receiver<OT> {
+    type Seed = Seed;
+    type Input = Block;
+    type Output = Block512;
+}
+
+impl<OT: OtSender<Msg = Block> + Malicious> OprfReceiver for Receiver<OT> {
+    fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let ot = OT::init(channel, rng)?;
+        Self::init_with_ot(channel, ot, SecurityParams::default(), rng)
+    }
+
+    fn init_with_params<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        params: SecurityParams,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        check_params(params)?;
+        params.receive(channel)?;
+        let ot = OT::init_with_params(channel, params, rng)?;
+        Self::init_with_ot(channel, ot, params, rng)
+    }
+
+    fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        inputs: &[Self::Input],
+        rng: &mut RNG,
+    ) -> Result<Vec<Self::Output>, Error> {
+        let m = inputs.len();
+        let checks = self.params.oos_checks();
+        let nrows = nrows(m, checks);
+        let ncols = CODEWORD_LENGTH;
+        // Pad the inputs with random values, which mask the consistency check.
+        let mut ws = inputs.to_vec();
+        ws.extend((m..nrows).map(|_| rng.gen::<Block>()));
+        let mut cs = vec![0u8; nrows * ncols / 8];
+        let code = &self.code;
+        utils::chunks_mut(&mut cs, CODEWORD_BYTES)
+            .zip(utils::chunks(&ws, 1))
+            .for_each(|(c, w)| code.encode(w[0], c));
+        let cs = utils::transpose(&cs, nrows, ncols);
+        let mut ts = vec![0u8; nrows * ncols / 8];
+        let mut us = vec![0u8; nrows * ncols / 8];
+        utils::chunks_mut(&mut ts, nrows / 8)
+            .zip(utils::chunks_mut(&mut us, nrows / 8))
+            .zip(utils::iter_mut(&mut self.rngs))
+            .zip(utils::chunks(&cs, nrows / 8))
+            .for_each(|(((t, u), rngs), c)| {
+                rngs.0.fill_bytes(t);
+                rngs.1.fill_bytes(u);
+                scutils::xor_inplace(u, t);
+                scutils::xor_inplace(u, c);
+            });
+        channel.write_bytes(&us)?;
+        channel.flush()?;
+        let ts = utils::transpose(&ts, ncols, nrows);
+        // Prove that we used codewords of the linear code.
+        let seed = cointoss::receive(channel, &[rng.gen()])?;
+        let chis = challenges(seed[0], m, checks);
+        let ts_ = combine(&ts, CODEWORD_BYTES, &chis, m, checks);
+        let mut ws_ = ws[m..m + checks].to_vec();
+        for (w, chi) in ws.iter().zip(chis.iter()) {
+            for (l, w_) in ws_.iter_mut().enumerate() {
+                if (chi >> l) & 1 == 1 {
+                    *w_ ^= *w;
+                }
+            }
+        }
+        channel.write_bytes(&ts_)?;
+        for w in ws_.iter() {
+            channel.write_block(w)?;
+        }
+        channel.flush()?;
+        let counter = self.counter;
+        let out = utils::range(m)
+            .map(|j| {
+                let t = &ts[j * CODEWORD_BYTES..(j + 1) * CODEWORD_BYTES];
+                hash(counter + j as u64, t)
+            })
+            .collect::<Vec<Self::Output>>();
+        self.counter += m as u64;
+        Ok(out)
+    }
+}
+
+impl<OT: OtReceiver<Msg = Block> + Malicious> SemiHonest for Sender<OT> {}
+impl<OT: OtSender<Msg = Block> + Malicious> SemiHonest for Receiver<OT> {}
+impl<OT: OtReceiver<Msg = Block> + Malicious> Malicious for Sender<OT> {}
+impl<OT: OtSender<Msg = Block> + Malicious> Malicious for Receiver<OT> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oprf;
+    use scuttlebutt::{AesRng, Channel};
+    use std::{
+        io::{BufReader, BufWriter},
+        os::unix::net::UnixStream,
+    };
+
+    fn rand_block_vec(size: usize) -> Vec<Block> {
+        (0..size).map(|_| rand::random::<Block>()).collect()
+    }
+
+    fn _test_oprf(params: SecurityParams, ns: &[usize]) {
+        let selections = ns.iter().map(|n| rand_block_vec(*n)).collect::<Vec<_>>();
+        let selections_ = selections.clone();
+        let ns_ = ns.to_vec();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut oprf =
+                oprf::OosSender::init_with_params(&mut channel, params, &mut rng).unwrap();
+            let mut results = Vec::new();
+            for (n, selections) in ns_.into_iter().zip(selections_.into_iter()) {
+                let seeds = oprf.send(&mut channel, n, &mut rng).unwrap();
+                results.push(
+                    selections
+                        .into_iter()
+                        .zip(seeds.into_iter())
+                        .map(|(inp, seed)| oprf.compute(seed, inp))
+                        .collect::<Vec<Block512>>(),
+                );
+            }
+            results
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut oprf = oprf::OosReceiver::init_with_params(&mut channel, params, &mut rng).unwrap();
+        let outputs = selections
+            .iter()
+            .map(|selections| oprf.receive(&mut channel, selections, &mut rng).unwrap())
+            .collect::<Vec<_>>();
+        let results = handle.join().unwrap();
+        assert_eq!(results, outputs);
+    }
+
+    #[test]
+    fn test_oprf() {
+        let params = SecurityParams::default();
+        _test_oprf(params, &[1]);
+        _test_oprf(params, &[8]);
+        _test_oprf(params, &[11]);
+        _test_oprf(params, &[64]);
+        _test_oprf(params, &[1000]);
+    }
+
+    #[test]
+    fn test_oprf_multiple_batches() {
+        _test_oprf(SecurityParams::default(), &[0, 13, 100, 7]);
+    }
+
+    #[test]
+    fn test_oprf_params() {
+        _test_oprf(SecurityParams::new(128, 64).unwrap(), &[100, 3]);
+        _test_oprf(SecurityParams::new(80, 20).unwrap(), &[100, 3]);
+        let params = SecurityParams::new(128, 65).unwrap();
+        let (sender, _) = UnixStream::pair().unwrap();
+        let reader = BufReader::new(sender.try_clone().unwrap());
+        let writer = BufWriter::new(sender);
+        let mut channel = Channel::new(reader, writer);
+        let mut rng = AesRng::new();
+        assert!(oprf::OosSender::init_with_params(&mut channel, params, &mut rng).is_err());
+    }
+
+    #[test]
+    fn test_oprf_distinct_instances() {
+        // The same input evaluated in two different instances gives unrelated
+        // outputs.
+        let input = rand::random::<Block>();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut oprf = oprf::OosSender::init(&mut channel, &mut rng).unwrap();
+            let _ = oprf.send(&mut channel, 2, &mut rng).unwrap();
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut oprf = oprf::OosReceiver::init(&mut channel, &mut rng).unwrap();
+        let outputs = oprf
+            .receive(&mut channel, &[input, input], &mut rng)
+            .unwrap();
+        handle.join().unwrap();
+        assert_ne!(outputs[0], outputs[1]);
+    }
+
+    #[test]
+    fn test_cheating_receiver() {
+        // A receiver that sends arbitrary columns, rather than encodings of its
+        // inputs, is caught by the consistency check.
+        let m = 16;
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut oprf = oprf::OosSender::init(&mut channel, &mut rng).unwrap();
+            oprf.send(&mut channel, m, &mut rng).is_err()
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let _ = oprf::OosReceiver::init(&mut channel, &mut rng).unwrap();
+        let checks = SecurityParams::default().oos_checks();
+        let mut us = vec![0u8; nrows(m, checks) * CODEWORD_BYTES];
+        rng.fill_bytes(&mut us);
+        channel.write_bytes(&us).unwrap();
+        channel.flush().unwrap();
+        let _ = cointoss::receive(&mut channel, &[rng.gen()]).unwrap();
+        let mut check = vec![0u8; checks * (CODEWORD_BYTES + 16)];
+        rng.fill_bytes(&mut check);
+        channel.write_bytes(&check).unwrap();
+        channel.flush().unwrap();
+        assert!(handle.join().unwrap());
+    }
+
+    #[test]
+    fn test_security_markers() {
+        fn malicious<T: Malicious>() {}
+        malicious::<oprf::OosSender>();
+        malicious::<oprf::OosReceiver>();
+        malicious::<oprf::OosMasnyRindalSender>();
+        malicious::<oprf::OosMasnyRindalReceiver>();
+    }
+}
@@ -0,0 +1,322 @@
+// -*- mode: rust; -*-
+//
+// This file is part of ocelot.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Adapters between chosen-message, correlated, and random OT.
+//!
+//! * `FromChosen` wraps any chosen-message OT and adds correlated and random
+//!   OT on top of it. The sender simply picks the messages itself: for random
+//!   OT both messages are random, and for correlated OT `m_0` is random and `m_1
+//!   = m_0 ⊕ Δ`.
+//! * `FromRandom` wraps any random OT and derives chosen-message and
+//!   correlated OT from it, using the standard derandomization. Given random
+//!   OT outputs `(r_0, r_1)`, the sender sends `m_0 ⊕ H(j, r_0)` and `m_1 ⊕
+//!   H(j, r_1)` for chosen-message OT, and `H(j, r_0) ⊕ H(j, r_1) ⊕ Δ` for
+//!   correlated OT (whose outputs are then `H(j, r_0)` and `H(j, r_0) ⊕ Δ`).
+//!   Here `H` is the tweakable correlation-robust hash `AesHash::tccr_hash`, and
+//!   `j` is a counter over all the OTs run so far.
+//!
+//! Both adapters act as either the sender or the receiver, depending on what
+//! they wrap, and carry over the security level of the wrapped OT.
+
+use crate::{
+    errors::Error,
+    ot::{
+        CorrelatedReceiver,
+        CorrelatedSender,
+        RandomReceiver,
+        RandomSender,
+        Receiver as OtReceiver,
+        Sender as OtSender,
+    },
+    params::SecurityParams,
+};
+use rand::{CryptoRng, Rng};
+use scuttlebutt::{AbstractChannel, Block, Malicious, SemiHonest, AES_HASH};
+
+/// Correlated and random OT from a chosen-message OT.
+pub struct FromChosen<OT> {
+    ot: OT,
+}
+
+impl<OT: OtSender<Msg = Block>> OtSender for FromChosen<OT> {
+    type Msg = Block;
+
+    fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let ot = OT::init(channel, rng)?;
+        Ok(Self { ot })
+    }
+
+    fn init_with_params<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        params: SecurityParams,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let ot = OT::init_with_params(channel, params, rng)?;
+        Ok(Self { ot })
+    }
+
+    fn send<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        inputs: &[(Block, Block)],
+        rng: &mut RNG,
+    ) -> Result<(), Error> {
+        self.ot.send(channel, inputs, rng)
+    }
+}
+
+impl<OT: OtSender<Msg = Block>> CorrelatedSender for FromChosen<OT> {
+    fn send_correlated<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        deltas: &[Block],
+        rng: &mut RNG,
+    ) -> Result<Vec<(Block, Block)>, Error> {
+        let inputs = deltas
+            .iter()
+            .map(|delta| {
+                let m0 = rng.gen::<Block>();
+                (m0, m0 ^ *delta)
+            })
+            .collect::<Vec<(Block, Block)>>();
+        self.ot.send(channel, &inputs, rng)?;
+        Ok(inputs)
+    }
+}
+
+impl<OT: OtSender<Msg = Block>> RandomSender for FromChosen<OT> {
+    fn send_random<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        m: usize,
+        rng: &mut RNG,
+    ) -> Result<Vec<(Block, Block)>, Error> {
+        let inputs = (0..m)
+            .map(|_| (rng.gen::<Block>(), rng.gen::<Block>()))
+            .collect::<Vec<(Block, Block)>>();
+        self.ot.send(channel, &inputs, rng)?;
+        Ok(inputs)
+    }
+}
+
+impl<OT: OtReceiver<Msg = Block>> OtReceiver for FromChosen<OT> {
+    type Msg = Block;
+
+    fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let ot = OT::init(channel, rng)?;
+        Ok(Self { ot })
+    }
+
+    fn init_with_params<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        params: SecurityParams,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let ot = OT::init_with_params(channel, params, rng)?;
+        Ok(Self { ot })
+    }
+
+    fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        inputs: &[bool],
+        rng: &mut RNG,
+    ) -> Result<Vec<Block>, Error> {
+        self.ot.receive(channel, inputs, rng)
+    }
+}
+
+impl<OT: OtReceiver<Msg = Block>> CorrelatedReceiver for FromChosen<OT> {
+    fn receive_correlated<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        inputs: &[bool],
+        rng: &mut RNG,
+    ) -> Result<Vec<Block>, Error> {
+        self.ot.receive(channel, inputs, rng)
+    }
+}
+
+impl<OT: OtReceiver<Msg = Block>> RandomReceiver for FromChosen<OT> {
+    fn receive_random<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        inputs: &[bool],
+        rng: &mut RNG,
+    ) -> Result<Vec<Block>, Error> {
+        self.ot.receive(channel, inputs, rng)
+    }
+}
+
+impl<OT: std::fmt::Display> std::fmt::Display for FromChosen<OT> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.ot)
+    }
+}
+
+/// Chosen-message and correlated OT from a random OT.
+pub struct FromRandom<OT> {
+    ot: OT,
+    counter: u128,
+}
+
+impl<OT: RandomSender<Msg = Block>> OtSender for FromRandom<OT> {
+    type Msg = Block;
+
+    fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let ot = OT::init(channel, rng)?;
+        Ok(Self { ot, counter: 0 })
+    }
+
+    fn init_with_params<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        params: SecurityParams,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let ot = OT::init_with_params(channel, params, rng)?;
+        Ok(Self { ot, counter: 0 })
+    }
+
+    fn send<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        inputs: &[(Block, Block)],
+        rng: &mut RNG,
+    ) -> Result<(), Error> {
+        let pads = self.ot.send_random(channel, inputs.len(), rng)?;
+        for (j, ((m0, m1), (r0, r1))) in inputs.iter().zip(pads).enumerate() {
+            let tweak = Block::from(self.counter + j as u128);
+            channel.write_block(&(*m0 ^ AES_HASH.tccr_hash(tweak, r0)))?;
+            channel.write_block(&(*m1 ^ AES_HASH.tccr_hash(tweak, r1)))?;
+        }
+        channel.flush()?;
+        self.counter += inputs.len() as u128;
+        Ok(())
+    }
+}
+
+impl<OT: RandomSender<Msg = Block>> CorrelatedSender for FromRandom<OT> {
+    fn send_correlated<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        deltas: &[Block],
+        rng: &mut RNG,
+    ) -> Result<Vec<(Block, Block)>, Error> {
+        let pads = self.ot.send_random(channel, deltas.len(), rng)?;
+        let mut out = Vec::with_capacity(deltas.len());
+        for (j, (delta, (r0, r1))) in deltas.iter().zip(pads).enumerate() {
+            let tweak = Block::from(self.counter + j as u128);
+            let x0 = AES_HASH.tccr_hash(tweak, r0);
+            let x1 = x0 ^ *delta;
+            channel.write_block(&(x1 ^ AES_HASH.tccr_hash(tweak, r1)))?;
+            out.push((x0, x1));
+        }
+        channel.flush()?;
+        self.counter += deltas.len() as u128;
+        Ok(out)
+    }
+}
+
+impl<OT: RandomSender<Msg = Block>> RandomSender for FromRandom<OT> {
+    fn send_random<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        m: usize,
+        rng: &mut RNG,
+    ) -> Result<Vec<(Block, Block)>, Error> {
+        self.ot.send_random(channel, m, rng)
+    }
+}
+
+impl<OT: RandomReceiver<Msg = Block>> OtReceiver for FromRandom<OT> {
+    type Msg = Block;
+
+    fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let ot = OT::init(channel, rng)?;
+        Ok(Self { ot, counter: 0 })
+    }
+
+    fn init_with_params<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        params: SecurityParams,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let ot = OT::init_with_params(channel, params, rng)?;
+        Ok(Self { ot, counter: 0 })
+    }
+
+    fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        inputs: &[bool],
+        rng: &mut RNG,
+    ) -> Result<Vec<Block>, Error> {
+        let pads = self.ot.receive_random(channel, inputs, rng)?;
+        let mut out = Vec::with_capacity(inputs.len());
+        for (j, (b, r)) in inputs.iter().zip(pads).enumerate() {
+            let tweak = Block::from(self.counter + j as u128);
+            let c0 = channel.read_block()?;
+            let c1 = channel.read_block()?;
+            out.push(AES_HASH.tccr_hash(tweak, r) ^ if *b { c1 } else { c0 });
+        }
+        self.counter += inputs.len() as u128;
+        Ok(out)
+    }
+}
+
+impl<OT: RandomReceiver<Msg = Block>> CorrelatedReceiver for FromRandom<OT> {
+    fn receive_correlated<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        inputs: &[bool],
+        rng: &mut RNG,
+    ) -> Result<Vec<Block>, Error> {
+        let pads = self.ot.receive_random(channel, inputs, rng)?;
+        let mut out = Vec::with_capacity(inputs.len());
+        for (j, (b, r)) in inputs.iter().zip(pads).enumerate() {
+            let tweak = Block::from(self.counter + j as u128);
+            let c = channel.read_block()?;
+            let x = AES_HASH.tccr_hash(tweak, r);
+            out.push(if *b { x ^ c } else { x });
+        }
+        self.counter += inputs.len() as u128;
+        Ok(out)
+    }
+}
+
+impl<OT: RandomReceiver<Msg = Block>> RandomReceiver for FromRandom<OT> {
+    fn receive_random<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        inputs: &[bool],
+        rng: &mut RNG,
+    ) -> Result<Vec<Block>, Error> {
+        self.ot.receive_random(channel, inputs, rng)
+    }
+}
+
+impl<OT: std::fmt::Display> std::fmt::Display for FromRandom<OT> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.ot)
+    }
+}
+
+impl<OT: SemiHonest> SemiHonest for FromChosen<OT> {}
+impl<OT: Malicious> Malicious for FromChosen<OT> {}
+impl<OT: SemiHonest> SemiHonest for FromRandom<OT> {}
+impl<OT: Malicious> Malicious for FromRandom<OT> {}
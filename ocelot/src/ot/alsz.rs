@@ -21,6 +21,8 @@ use crate::{
     },
     utils,
 };
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use rand::{CryptoRng, Rng, RngCore, SeedableRng};
 use scuttlebutt::{
     utils as scutils,
@@ -57,15 +59,20 @@ impl<OT: OtReceiver<Msg = Block> + SemiHonest> Sender<OT> {
         const nrows: usize = 128;
         let ncols = if m % 8 != 0 { m + (8 - m % 8) } else { m };
         let mut qs = vec![0u8; nrows * ncols / 8];
-        let mut u = vec![0u8; ncols / 8];
-        let zero = vec![0u8; ncols / 8];
-        for (j, (b, rng)) in self.s.iter().zip(self.rngs.iter_mut()).enumerate() {
-            let range = j * ncols / 8..(j + 1) * ncols / 8;
-            let mut q = &mut qs[range];
-            channel.read_bytes(&mut u)?;
-            rng.fill_bytes(&mut q);
-            scutils::xor_inplace(&mut q, if *b { &u } else { &zero });
-        }
+        // The `u`s are sent one row at a time, so reading them all at once
+        // leaves the wire format unchanged.
+        let mut us = vec![0u8; nrows * ncols / 8];
+        channel.read_bytes(&mut us)?;
+        let s = &self.s;
+        utils::chunks_mut(&mut qs, ncols / 8)
+            .zip(utils::iter_mut(&mut self.rngs))
+            .enumerate()
+            .for_each(|(j, (q, rng))| {
+                rng.fill_bytes(q);
+                if s[j] {
+                    scutils::xor_inplace(q, &us[j * ncols / 8..(j + 1) * ncols / 8]);
+                }
+            });
         Ok(utils::transpose(&qs, nrows, ncols))
     }
 }
@@ -103,15 +110,21 @@ impl<OT: OtReceiver<Msg = Block> + SemiHonest> OtSender for Sender<OT> {
     ) -> Result<(), Error> {
         let m = inputs.len();
         let qs = self.send_setup(channel, m)?;
-        for (j, input) in inputs.iter().enumerate() {
-            let q = &qs[j * 16..(j + 1) * 16];
-            let q: [u8; 16] = q.try_into().unwrap();
-            let q = Block::from(q);
-            let y0 = self.hash.cr_hash(Block::from(j as u128), q) ^ input.0;
-            let q = q ^ self.s_;
-            let y1 = self.hash.cr_hash(Block::from(j as u128), q) ^ input.1;
-            channel.write_block(&y0)?;
-            channel.write_block(&y1)?;
+        let (hash, s_) = (&self.hash, self.s_);
+        let ys = utils::range(m)
+            .map(|j| {
+                let q = &qs[j * 16..(j + 1) * 16];
+                let q: [u8; 16] = q.try_into().unwrap();
+                let q = Block::from(q);
+                let y0 = hash.cr_hash(Block::from(j as u128), q) ^ inputs[j].0;
+                let q = q ^ s_;
+                let y1 = hash.cr_hash(Block::from(j as u128), q) ^ inputs[j].1;
+                (y0, y1)
+            })
+            .collect::<Vec<(Block, Block)>>();
+        for (y0, y1) in ys.iter() {
+            channel.write_block(y0)?;
+            channel.write_block(y1)?;
         }
         channel.flush()?;
         Ok(())
@@ -133,17 +146,23 @@ impl<OT: OtReceiver<Msg = Block> + SemiHonest> CorrelatedSender for Sender<OT> {
     ) -> Result<Vec<(Self::Msg, Self::Msg)>, Error> {
         let m = deltas.len();
         let qs = self.send_setup(channel, m)?;
+        let (hash, s_) = (&self.hash, self.s_);
+        let xys = utils::range(m)
+            .map(|j| {
+                let q = &qs[j * 16..(j + 1) * 16];
+                let q: [u8; 16] = q.try_into().unwrap();
+                let q = Block::from(q);
+                let x0 = hash.cr_hash(Block::from(j as u128), q);
+                let x1 = x0 ^ deltas[j];
+                let q = q ^ s_;
+                let y = hash.cr_hash(Block::from(j as u128), q) ^ x1;
+                ((x0, x1), y)
+            })
+            .collect::<Vec<((Block, Block), Block)>>();
         let mut out = Vec::with_capacity(m);
-        for (j, delta) in deltas.iter().enumerate() {
-            let q = &qs[j * 16..(j + 1) * 16];
-            let q: [u8; 16] = q.try_into().unwrap();
-            let q = Block::from(q);
-            let x0 = self.hash.cr_hash(Block::from(j as u128), q);
-            let x1 = x0 ^ *delta;
-            let q = q ^ self.s_;
-            let y = self.hash.cr_hash(Block::from(j as u128), q) ^ x1;
+        for (x, y) in xys.into_iter() {
             channel.write_block(&y)?;
-            out.push((x0, x1));
+            out.push(x);
         }
         channel.flush()?;
         Ok(out)
@@ -158,16 +177,18 @@ impl<OT: OtReceiver<Msg = Block> + SemiHonest> RandomSender for Sender<OT> {
         _: &mut RNG,
     ) -> Result<Vec<(Self::Msg, Self::Msg)>, Error> {
         let qs = self.send_setup(channel, m)?;
-        let mut out = Vec::with_capacity(m);
-        for j in 0..m {
-            let q = &qs[j * 16..(j + 1) * 16];
-            let q: [u8; 16] = q.try_into().unwrap();
-            let q = Block::from(q);
-            let x0 = self.hash.cr_hash(Block::from(j as u128), q);
-            let q = q ^ self.s_;
-            let x1 = self.hash.cr_hash(Block::from(j as u128), q);
-            out.push((x0, x1));
-        }
+        let (hash, s_) = (&self.hash, self.s_);
+        let out = utils::range(m)
+            .map(|j| {
+                let q = &qs[j * 16..(j + 1) * 16];
+                let q: [u8; 16] = q.try_into().unwrap();
+                let q = Block::from(q);
+                let x0 = hash.cr_hash(Block::from(j as u128), q);
+                let q = q ^ s_;
+                let x1 = hash.cr_hash(Block::from(j as u128), q);
+                (x0, x1)
+            })
+            .collect();
         Ok(out)
     }
 }
@@ -182,16 +203,18 @@ impl<OT: OtSender<Msg = Block> + SemiHonest> Receiver<OT> {
         const nrows: usize = 128;
         let ncols = if m % 8 != 0 { m + (8 - m % 8) } else { m };
         let mut ts = vec![0u8; nrows * ncols / 8];
-        let mut g = vec![0u8; ncols / 8];
-        for j in 0..self.rngs.len() {
-            let range = j * ncols / 8..(j + 1) * ncols / 8;
-            let mut t = &mut ts[range];
-            self.rngs[j].0.fill_bytes(&mut t);
-            self.rngs[j].1.fill_bytes(&mut g);
-            scutils::xor_inplace(&mut g, &t);
-            scutils::xor_inplace(&mut g, &r);
-            channel.write_bytes(&g)?;
-        }
+        let mut gs = vec![0u8; nrows * ncols / 8];
+        utils::chunks_mut(&mut ts, ncols / 8)
+            .zip(utils::chunks_mut(&mut gs, ncols / 8))
+            .zip(utils::iter_mut(&mut self.rngs))
+            .for_each(|((t, g), rngs)| {
+                rngs.0.fill_bytes(t);
+                rngs.1.fill_bytes(g);
+                scutils::xor_inplace(g, t);
+                scutils::xor_inplace(g, r);
+            });
+        // Writing all rows at once is equivalent to writing them one by one.
+        channel.write_bytes(&gs)?;
         channel.flush()?;
         Ok(utils::transpose(&ts, nrows, ncols))
     }
@@ -233,16 +256,21 @@ impl<OT: OtSender<Msg = Block> + SemiHonest> OtReceiver for Receiver<OT> {
     ) -> Result<Vec<Self::Msg>, Error> {
         let r = utils::boolvec_to_u8vec(inputs);
         let ts = self.receive_setup(channel, &r, inputs.len())?;
-        let mut out = Vec::with_capacity(inputs.len());
-        for (j, b) in inputs.iter().enumerate() {
-            let t = &ts[j * 16..(j + 1) * 16];
-            let t: [u8; 16] = t.try_into().unwrap();
-            let y0 = channel.read_block()?;
-            let y1 = channel.read_block()?;
-            let y = if *b { y1 } else { y0 };
-            let y = y ^ self.hash.cr_hash(Block::from(j as u128), Block::from(t));
-            out.push(y);
-        }
+        let ys = (0..inputs.len())
+            .map(|j| {
+                let y0 = channel.read_block()?;
+                let y1 = channel.read_block()?;
+                Ok(if inputs[j] { y1 } else { y0 })
+            })
+            .collect::<Result<Vec<Block>, Error>>()?;
+        let hash = &self.hash;
+        let out = utils::range(inputs.len())
+            .map(|j| {
+                let t = &ts[j * 16..(j + 1) * 16];
+                let t: [u8; 16] = t.try_into().unwrap();
+                ys[j] ^ hash.cr_hash(Block::from(j as u128), Block::from(t))
+            })
+            .collect();
         Ok(out)
     }
 }
@@ -256,15 +284,22 @@ impl<OT: OtSender<Msg = Block> + SemiHonest> CorrelatedReceiver for Receiver<OT>
     ) -> Result<Vec<Self::Msg>, Error> {
         let r = utils::boolvec_to_u8vec(inputs);
         let ts = self.receive_setup(channel, &r, inputs.len())?;
-        let mut out = Vec::with_capacity(inputs.len());
-        for (j, b) in inputs.iter().enumerate() {
-            let t = &ts[j * 16..(j + 1) * 16];
-            let t: [u8; 16] = t.try_into().unwrap();
-            let y = channel.read_block()?;
-            let y = if *b { y } else { Block::default() };
-            let h = self.hash.cr_hash(Block::from(j as u128), Block::from(t));
-            out.push(y ^ h);
-        }
+        let ys = inputs
+            .iter()
+            .map(|b| {
+                let y = channel.read_block()?;
+                Ok(if *b { y } else { Block::default() })
+            })
+            .collect::<Result<Vec<Block>, Error>>()?;
+        let hash = &self.hash;
+        let out = utils::range(inputs.len())
+            .map(|j| {
+                let t = &ts[j * 16..(j + 1) * 16];
+                let t: [u8; 16] = t.try_into().unwrap();
+                let h = hash.cr_hash(Block::from(j as u128), Block::from(t));
+                ys[j] ^ h
+            })
+            .collect();
         Ok(out)
     }
 }
@@ -278,13 +313,14 @@ impl<OT: OtSender<Msg = Block> + SemiHonest> RandomReceiver for Receiver<OT> {
     ) -> Result<Vec<Self::Msg>, Error> {
         let r = utils::boolvec_to_u8vec(inputs);
         let ts = self.receive_setup(channel, &r, inputs.len())?;
-        let mut out = Vec::with_capacity(inputs.len());
-        for j in 0..inputs.len() {
-            let t = &ts[j * 16..(j + 1) * 16];
-            let t: [u8; 16] = t.try_into().unwrap();
-            let h = self.hash.cr_hash(Block::from(j as u128), Block::from(t));
-            out.push(h);
-        }
+        let hash = &self.hash;
+        let out = utils::range(inputs.len())
+            .map(|j| {
+                let t = &ts[j * 16..(j + 1) * 16];
+                let t: [u8; 16] = t.try_into().unwrap();
+                hash.cr_hash(Block::from(j as u128), Block::from(t))
+            })
+            .collect();
         Ok(out)
     }
 }
@@ -0,0 +1,315 @@
+// -*- mode: rust; -*-
+//
+// This file is part of ocelot.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Oblivious transfer of short messages, from random OT.
+//!
+//! The `OtSender` and `OtReceiver` traits transfer a full `Block` per choice,
+//! which is wasteful when the messages are single bits (as in the evaluator's
+//! inputs in garbled circuits) or short strings. Here, the parties first run
+//! random OT, giving the sender random pads `(r_0, r_1)` and the receiver
+//! `r_b`, and the sender then sends `m_0 ⊕ r_0` and `m_1 ⊕ r_1`, truncated to
+//! the `ℓ ≤ 128` bits of the messages. These are packed into bytes, with the
+//! bits of the `i`th transfer at positions `2iℓ..2(i+1)ℓ`, and the last byte is
+//! padded with zeros. For `ℓ = 1`, this is 2 bits per transfer rather than 256.
+
+use crate::{
+    errors::Error,
+    ot::{RandomReceiver, RandomSender},
+    params::SecurityParams,
+};
+use rand::{CryptoRng, Rng};
+use scuttlebutt::{AbstractChannel, Block, Malicious, SemiHonest};
+
+// Checks that `nbits` is a valid message length, returning the mask for
+// messages of that length.
+#[inline]
+fn mask(nbits: usize) -> Result<u128, Error> {
+    match nbits {
+        0 => Err(Error::Other(
+            "bit-OT messages must be non-empty".to_string(),
+        )),
+        1..=127 => Ok((1 << nbits) - 1),
+        128 => Ok(!0),
+        _ => Err(Error::Other(format!(
+            "bit-OT messages are at most 128 bits, got {}",
+            nbits
+        ))),
+    }
+}
+
+// The number of bytes needed to pack `n` values of `nbits` bits each.
+#[inline]
+fn packed_len(n: usize, nbits: usize) -> usize {
+    (n * nbits).div_ceil(8)
+}
+
+// Packs the `nbits` low bits of each of the `n` values in `values`.
+fn pack<I: Iterator<Item = u128>>(values: I, n: usize, nbits: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; packed_len(n, nbits)];
+    for (i, value) in values.enumerate() {
+        let offset = i * nbits;
+        for j in 0..nbits {
+            let p = offset + j;
+            bytes[p / 8] |= (((value >> j) & 1) as u8) << (p % 8);
+        }
+    }
+    bytes
+}
+
+// Unpacks the `i`th value of `nbits` bits from `bytes`.
+fn unpack(bytes: &[u8], i: usize, nbits: usize) -> u128 {
+    let offset = i * nbits;
+    (0..nbits).fold(0, |value, j| {
+        let p = offset + j;
+        value | (u128::from((bytes[p / 8] >> (p % 8)) & 1) << j)
+    })
+}
+
+/// Bit-OT sender.
+pub struct Sender<OT: RandomSender<Msg = Block>> {
+    ot: OT,
+}
+
+impl<OT: RandomSender<Msg = Block>> Sender<OT> {
+    /// Runs any one-time initialization.
+    pub fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let ot = OT::init(channel, rng)?;
+        Ok(Self { ot })
+    }
+
+    /// Runs any one-time initialization, passing the security parameters
+    /// `params` on to the underlying random OT.
+    pub fn init_with_params<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        params: SecurityParams,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let ot = OT::init_with_params(channel, params, rng)?;
+        Ok(Self { ot })
+    }
+
+    /// Sends the `nbits`-bit messages `inputs`, where `nbits` is at most 128.
+    /// The receiver must use the same `nbits`.
+    pub fn send<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        nbits: usize,
+        inputs: &[(u128, u128)],
+        rng: &mut RNG,
+    ) -> Result<(), Error> {
+        let mask = mask(nbits)?;
+        if inputs.iter().any(|(m0, m1)| (m0 | m1) & !mask != 0) {
+            return Err(Error::Other(format!(
+                "bit-OT message does not fit in {} bits",
+                nbits
+            )));
+        }
+        let pads = self.ot.send_random(channel, inputs.len(), rng)?;
+        let cs = inputs.iter().zip(pads).flat_map(|((m0, m1), (r0, r1))| {
+            let c0 = m0 ^ (u128::from(r0) & mask);
+            let c1 = m1 ^ (u128::from(r1) & mask);
+            std::iter::once(c0).chain(std::iter::once(c1))
+        });
+        let bytes = pack(cs, 2 * inputs.len(), nbits);
+        channel.write_bytes(&bytes)?;
+        channel.flush()?;
+        Ok(())
+    }
+
+    /// Sends the single-bit messages `inputs`.
+    pub fn send_bits<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        inputs: &[(bool, bool)],
+        rng: &mut RNG,
+    ) -> Result<(), Error> {
+        let inputs = inputs
+            .iter()
+            .map(|(m0, m1)| (u128::from(*m0), u128::from(*m1)))
+            .collect::<Vec<(u128, u128)>>();
+        self.send(channel, 1, &inputs, rng)
+    }
+}
+
+impl<OT: RandomSender<Msg = Block>> std::fmt::Display for Sender<OT> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Bit-OT Sender")
+    }
+}
+
+/// Bit-OT receiver.
+pub struct Receiver<OT: RandomReceiver<Msg = Block>> {
+    ot: OT,
+}
+
+impl<OT: RandomReceiver<Msg = Block>> Receiver<OT> {
+    /// Runs any one-time initialization.
+    pub fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let ot = OT::init(channel, rng)?;
+        Ok(Self { ot })
+    }
+
+    /// Runs any one-time initialization, passing the security parameters
+    /// `params` on to the underlying random OT.
+    pub fn init_with_params<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        params: SecurityParams,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let ot = OT::init_with_params(channel, params, rng)?;
+        Ok(Self { ot })
+    }
+
+    /// Receives the `nbits`-bit messages selected by `choices`, where `nbits`
+    /// is at most 128.
+    pub fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        nbits: usize,
+        choices: &[bool],
+        rng: &mut RNG,
+    ) -> Result<Vec<u128>, Error> {
+        let mask = mask(nbits)?;
+        let pads = self.ot.receive_random(channel, choices, rng)?;
+        let mut bytes = vec![0u8; packed_len(2 * choices.len(), nbits)];
+        channel.read_bytes(&mut bytes)?;
+        Ok(choices
+            .iter()
+            .zip(pads)
+            .enumerate()
+            .map(|(i, (b, r))| unpack(&bytes, 2 * i + *b as usize, nbits) ^ (u128::from(r) & mask))
+            .collect())
+    }
+
+    /// Receives the single-bit messages selected by `choices`.
+    pub fn receive_bits<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        choices: &[bool],
+        rng: &mut RNG,
+    ) -> Result<Vec<bool>, Error> {
+        let outputs = self.receive(channel, 1, choices, rng)?;
+        Ok(outputs.into_iter().map(|m| m == 1).collect())
+    }
+}
+
+impl<OT: RandomReceiver<Msg = Block>> std::fmt::Display for Receiver<OT> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Bit-OT Receiver")
+    }
+}
+
+impl<OT: RandomSender<Msg = Block> + SemiHonest> SemiHonest for Sender<OT> {}
+impl<OT: RandomReceiver<Msg = Block> + SemiHonest> SemiHonest for Receiver<OT> {}
+impl<OT: RandomSender<Msg = Block> + Malicious> Malicious for Sender<OT> {}
+impl<OT: RandomReceiver<Msg = Block> + Malicious> Malicious for Receiver<OT> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ot;
+    use scuttlebutt::{AesRng, Channel};
+    use std::{
+        io::{BufReader, BufWriter},
+        os::unix::net::UnixStream,
+    };
+
+    #[test]
+    fn test_pack() {
+        let mut rng = AesRng::new();
+        for nbits in &[1, 3, 8, 13, 64, 127, 128] {
+            let mask = mask(*nbits).unwrap();
+            let values = (0..37)
+                .map(|_| rng.gen::<u128>() & mask)
+                .collect::<Vec<u128>>();
+            let bytes = pack(values.iter().cloned(), values.len(), *nbits);
+            assert_eq!(bytes.len(), packed_len(values.len(), *nbits));
+            for (i, value) in values.iter().enumerate() {
+                assert_eq!(unpack(&bytes, i, *nbits), *value);
+            }
+        }
+        assert!(mask(0).is_err());
+        assert!(mask(129).is_err());
+    }
+
+    fn _test_bit_ot<
+        OTSender: RandomSender<Msg = Block> + Send + 'static,
+        OTReceiver: RandomReceiver<Msg = Block>,
+    >(
+        n: usize,
+        nbits: usize,
+    ) {
+        let mut rng = AesRng::new();
+        let mask = mask(nbits).unwrap();
+        let ms = (0..n)
+            .map(|_| (rng.gen::<u128>() & mask, rng.gen::<u128>() & mask))
+            .collect::<Vec<(u128, u128)>>();
+        let bs = (0..n).map(|_| rng.gen::<bool>()).collect::<Vec<bool>>();
+        let ms_ = ms.clone();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut ot = Sender::<OTSender>::init(&mut channel, &mut rng).unwrap();
+            ot.send(&mut channel, nbits, &ms_, &mut rng).unwrap();
+            let bits = ms_
+                .iter()
+                .map(|(m0, m1)| (m0 & 1 == 1, m1 & 1 == 1))
+                .collect::<Vec<(bool, bool)>>();
+            ot.send_bits(&mut channel, &bits, &mut rng).unwrap();
+        });
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut ot = Receiver::<OTReceiver>::init(&mut channel, &mut rng).unwrap();
+        let results = ot.receive(&mut channel, nbits, &bs, &mut rng).unwrap();
+        let bits = ot.receive_bits(&mut channel, &bs, &mut rng).unwrap();
+        handle.join().unwrap();
+        for (((m0, m1), b), (result, bit)) in ms.iter().zip(bs.iter()).zip(results.iter().zip(bits))
+        {
+            let m = if *b { m1 } else { m0 };
+            assert_eq!(result, m);
+            assert_eq!(bit, m & 1 == 1);
+        }
+    }
+
+    #[test]
+    fn test_bit_ot() {
+        for n in &[0, 1, 7, 1000] {
+            for nbits in &[1, 5, 64, 128] {
+                _test_bit_ot::<ot::AlszSender, ot::AlszReceiver>(*n, *nbits);
+            }
+        }
+        _test_bit_ot::<ot::KosSender, ot::KosReceiver>(1001, 3);
+    }
+
+    #[test]
+    fn test_message_too_long() {
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let _ = <ot::AlszReceiver as ot::Receiver>::init(&mut channel, &mut rng).unwrap();
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut ot = Sender::<ot::AlszSender>::init(&mut channel, &mut rng).unwrap();
+        assert!(ot.send(&mut channel, 2, &[(1, 4)], &mut rng).is_err());
+        handle.join().unwrap();
+    }
+}
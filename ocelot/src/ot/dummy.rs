@@ -6,6 +6,9 @@
 
 //! Implementation of an **entirely insecure** oblivious transfer protocol for
 //! testing purposes.
+//!
+//! This protocol deliberately implements neither `SemiHonest` nor `Malicious`,
+//! and so cannot be used as the base OT of any of the OT extension protocols.
 
 use crate::{
     errors::Error,
@@ -6,6 +6,24 @@
 
 //! Implementation of the Keller-Orsini-Scholl oblivious transfer extension
 //! protocol (cf. <https://eprint.iacr.org/2015/546>).
+//!
+//! The number of extra columns used by the correlation check is derived from
+//! the `SecurityParams` given to `init_with_params` (see `params`), which both
+//! parties must agree on.
+//!
+//! `init_with_params` starts with the sender sending its parameters to the
+//! receiver, which checks them against its own. `init` uses the defaults
+//! without sending them, so it keeps the wire format of KOS peers that predate
+//! `SecurityParams`. Both parties must initialize the same way.
+//!
+//! The base OT must be maliciously secure, and using a semi-honest one fails to
+//! compile:
+//!
+//! ```compile_fail
+//! use ocelot::ot::{kos, NaorPinkasReceiver};
+//!
+//! fn extend(_: kos::Sender<NaorPinkasReceiver>) {}
+//! ```
 
 use crate::{
     errors::Error,
@@ -18,22 +36,24 @@ use crate::{
         Receiver as OtReceiver,
         Sender as OtSender,
     },
+    params::SecurityParams,
     utils,
 };
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use rand::{CryptoRng, Rng, RngCore, SeedableRng};
 use scuttlebutt::{cointoss, AbstractChannel, AesRng, Block, Malicious, SemiHonest};
 use std::{convert::TryInto, io::ErrorKind};
 
-// The statistical security parameter.
-const SSP: usize = 40;
-
 /// Oblivious transfer extension sender.
 pub struct Sender<OT: OtReceiver<Msg = Block> + Malicious> {
     ot: AlszSender<OT>,
+    params: SecurityParams,
 }
 /// Oblivious transfer extension receiver.
 pub struct Receiver<OT: OtSender<Msg = Block> + Malicious> {
     ot: AlszReceiver<OT>,
+    params: SecurityParams,
 }
 
 impl<OT: OtReceiver<Msg = Block> + Malicious> Sender<OT> {
@@ -44,7 +64,7 @@ impl<OT: OtReceiver<Msg = Block> + Malicious> Sender<OT> {
         rng: &mut RNG,
     ) -> Result<Vec<u8>, Error> {
         let m = if m % 8 != 0 { m + (8 - m % 8) } else { m };
-        let ncols = m + 128 + SSP;
+        let ncols = m + self.params.kos_extra_columns();
         let qs = self.ot.send_setup(channel, ncols)?;
         // Check correlation
         let mut seed = Block::default();
@@ -84,7 +104,18 @@ impl<OT: OtReceiver<Msg = Block> + Malicious> OtSender for Sender<OT> {
         rng: &mut RNG,
     ) -> Result<Self, Error> {
         let ot = AlszSender::<OT>::init(channel, rng)?;
-        Ok(Self { ot })
+        let params = SecurityParams::default();
+        Ok(Self { ot, params })
+    }
+
+    fn init_with_params<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        params: SecurityParams,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        params.send(channel)?;
+        let ot = AlszSender::<OT>::init(channel, rng)?;
+        Ok(Self { ot, params })
     }
 
     fn send<C: AbstractChannel, RNG: CryptoRng + Rng>(
@@ -96,15 +127,21 @@ impl<OT: OtReceiver<Msg = Block> + Malicious> OtSender for Sender<OT> {
         let m = inputs.len();
         let qs = self.send_setup(channel, m, rng)?;
         // Output result
-        for (j, input) in inputs.iter().enumerate() {
-            let q = &qs[j * 16..(j + 1) * 16];
-            let q: [u8; 16] = q.try_into().unwrap();
-            let q = Block::from(q);
-            let y0 = self.ot.hash.tccr_hash(Block::from(j as u128), q) ^ input.0;
-            let q = q ^ self.ot.s_;
-            let y1 = self.ot.hash.tccr_hash(Block::from(j as u128), q) ^ input.1;
-            channel.write_block(&y0)?;
-            channel.write_block(&y1)?;
+        let (hash, s_) = (&self.ot.hash, self.ot.s_);
+        let ys = utils::range(m)
+            .map(|j| {
+                let q = &qs[j * 16..(j + 1) * 16];
+                let q: [u8; 16] = q.try_into().unwrap();
+                let q = Block::from(q);
+                let y0 = hash.tccr_hash(Block::from(j as u128), q) ^ inputs[j].0;
+                let q = q ^ s_;
+                let y1 = hash.tccr_hash(Block::from(j as u128), q) ^ inputs[j].1;
+                (y0, y1)
+            })
+            .collect::<Vec<(Block, Block)>>();
+        for (y0, y1) in ys.iter() {
+            channel.write_block(y0)?;
+            channel.write_block(y1)?;
         }
         channel.flush()?;
         Ok(())
@@ -120,17 +157,23 @@ impl<OT: OtReceiver<Msg = Block> + Malicious> CorrelatedSender for Sender<OT> {
     ) -> Result<Vec<(Self::Msg, Self::Msg)>, Error> {
         let m = deltas.len();
         let qs = self.send_setup(channel, m, rng)?;
+        let (hash, s_) = (&self.ot.hash, self.ot.s_);
+        let xys = utils::range(m)
+            .map(|j| {
+                let q = &qs[j * 16..(j + 1) * 16];
+                let q: [u8; 16] = q.try_into().unwrap();
+                let q = Block::from(q);
+                let x0 = hash.tccr_hash(Block::from(j as u128), q);
+                let x1 = x0 ^ deltas[j];
+                let q = q ^ s_;
+                let y = hash.tccr_hash(Block::from(j as u128), q) ^ x1;
+                ((x0, x1), y)
+            })
+            .collect::<Vec<((Block, Block), Block)>>();
         let mut out = Vec::with_capacity(m);
-        for (j, delta) in deltas.iter().enumerate() {
-            let q = &qs[j * 16..(j + 1) * 16];
-            let q: [u8; 16] = q.try_into().unwrap();
-            let q = Block::from(q);
-            let x0 = self.ot.hash.tccr_hash(Block::from(j as u128), q);
-            let x1 = x0 ^ *delta;
-            let q = q ^ self.ot.s_;
-            let y = self.ot.hash.tccr_hash(Block::from(j as u128), q) ^ x1;
+        for (x, y) in xys.into_iter() {
             channel.write_block(&y)?;
-            out.push((x0, x1));
+            out.push(x);
         }
         channel.flush()?;
         Ok(out)
@@ -145,16 +188,18 @@ impl<OT: OtReceiver<Msg = Block> + Malicious> RandomSender for Sender<OT> {
         rng: &mut RNG,
     ) -> Result<Vec<(Self::Msg, Self::Msg)>, Error> {
         let qs = self.send_setup(channel, m, rng)?;
-        let mut out = Vec::with_capacity(m);
-        for j in 0..m {
-            let q = &qs[j * 16..(j + 1) * 16];
-            let q: [u8; 16] = q.try_into().unwrap();
-            let q = Block::from(q);
-            let x0 = self.ot.hash.tccr_hash(Block::from(j as u128), q);
-            let q = q ^ self.ot.s_;
-            let x1 = self.ot.hash.tccr_hash(Block::from(j as u128), q);
-            out.push((x0, x1));
-        }
+        let (hash, s_) = (&self.ot.hash, self.ot.s_);
+        let out = utils::range(m)
+            .map(|j| {
+                let q = &qs[j * 16..(j + 1) * 16];
+                let q: [u8; 16] = q.try_into().unwrap();
+                let q = Block::from(q);
+                let x0 = hash.tccr_hash(Block::from(j as u128), q);
+                let q = q ^ s_;
+                let x1 = hash.tccr_hash(Block::from(j as u128), q);
+                (x0, x1)
+            })
+            .collect();
         Ok(out)
     }
 }
@@ -174,7 +219,7 @@ impl<OT: OtSender<Msg = Block> + Malicious> Receiver<OT> {
     ) -> Result<Vec<u8>, Error> {
         let m = inputs.len();
         let m = if m % 8 != 0 { m + (8 - m % 8) } else { m };
-        let m_ = m + 128 + SSP;
+        let m_ = m + self.params.kos_extra_columns();
         let mut r = utils::boolvec_to_u8vec(inputs);
         r.extend((0..(m_ - m) / 8).map(|_| rand::random::<u8>()));
         let ts = self.ot.receive_setup(channel, &r, m_)?;
@@ -212,7 +257,18 @@ impl<OT: OtSender<Msg = Block> + Malicious> OtReceiver for Receiver<OT> {
         rng: &mut RNG,
     ) -> Result<Self, Error> {
         let ot = AlszReceiver::<OT>::init(channel, rng)?;
-        Ok(Self { ot })
+        let params = SecurityParams::default();
+        Ok(Self { ot, params })
+    }
+
+    fn init_with_params<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        params: SecurityParams,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        params.receive(channel)?;
+        let ot = AlszReceiver::<OT>::init(channel, rng)?;
+        Ok(Self { ot, params })
     }
 
     fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
@@ -223,19 +279,22 @@ impl<OT: OtSender<Msg = Block> + Malicious> OtReceiver for Receiver<OT> {
     ) -> Result<Vec<Block>, Error> {
         let ts = self.receive_setup(channel, inputs, rng)?;
         // Output result
-        let mut out = Vec::with_capacity(inputs.len());
-        for (j, b) in inputs.iter().enumerate() {
-            let t = &ts[j * 16..(j + 1) * 16];
-            let t: [u8; 16] = t.try_into().unwrap();
-            let y0 = channel.read_block()?;
-            let y1 = channel.read_block()?;
-            let y = if *b { y1 } else { y0 };
-            let y = y ^ self
-                .ot
-                .hash
-                .tccr_hash(Block::from(j as u128), Block::from(t));
-            out.push(y);
-        }
+        let ys = inputs
+            .iter()
+            .map(|b| {
+                let y0 = channel.read_block()?;
+                let y1 = channel.read_block()?;
+                Ok(if *b { y1 } else { y0 })
+            })
+            .collect::<Result<Vec<Block>, Error>>()?;
+        let hash = &self.ot.hash;
+        let out = utils::range(inputs.len())
+            .map(|j| {
+                let t = &ts[j * 16..(j + 1) * 16];
+                let t: [u8; 16] = t.try_into().unwrap();
+                ys[j] ^ hash.tccr_hash(Block::from(j as u128), Block::from(t))
+            })
+            .collect();
         Ok(out)
     }
 }
@@ -248,18 +307,22 @@ impl<OT: OtSender<Msg = Block> + Malicious> CorrelatedReceiver for Receiver<OT>
         rng: &mut RNG,
     ) -> Result<Vec<Self::Msg>, Error> {
         let ts = self.receive_setup(channel, inputs, rng)?;
-        let mut out = Vec::with_capacity(inputs.len());
-        for (j, b) in inputs.iter().enumerate() {
-            let t = &ts[j * 16..(j + 1) * 16];
-            let t: [u8; 16] = t.try_into().unwrap();
-            let y = channel.read_block()?;
-            let y = if *b { y } else { Block::default() };
-            let h = self
-                .ot
-                .hash
-                .tccr_hash(Block::from(j as u128), Block::from(t));
-            out.push(y ^ h);
-        }
+        let ys = inputs
+            .iter()
+            .map(|b| {
+                let y = channel.read_block()?;
+                Ok(if *b { y } else { Block::default() })
+            })
+            .collect::<Result<Vec<Block>, Error>>()?;
+        let hash = &self.ot.hash;
+        let out = utils::range(inputs.len())
+            .map(|j| {
+                let t = &ts[j * 16..(j + 1) * 16];
+                let t: [u8; 16] = t.try_into().unwrap();
+                let h = hash.tccr_hash(Block::from(j as u128), Block::from(t));
+                ys[j] ^ h
+            })
+            .collect();
         Ok(out)
     }
 }
@@ -272,16 +335,14 @@ impl<OT: OtSender<Msg = Block> + Malicious> RandomReceiver for Receiver<OT> {
         rng: &mut RNG,
     ) -> Result<Vec<Self::Msg>, Error> {
         let ts = self.receive_setup(channel, inputs, rng)?;
-        let mut out = Vec::with_capacity(inputs.len());
-        for j in 0..inputs.len() {
-            let t = &ts[j * 16..(j + 1) * 16];
-            let t: [u8; 16] = t.try_into().unwrap();
-            let h = self
-                .ot
-                .hash
-                .tccr_hash(Block::from(j as u128), Block::from(t));
-            out.push(h);
-        }
+        let hash = &self.ot.hash;
+        let out = utils::range(inputs.len())
+            .map(|j| {
+                let t = &ts[j * 16..(j + 1) * 16];
+                let t: [u8; 16] = t.try_into().unwrap();
+                hash.tccr_hash(Block::from(j as u128), Block::from(t))
+            })
+            .collect();
         Ok(out)
     }
 }
@@ -0,0 +1,170 @@
+// -*- mode: rust; -*-
+//
+// This file is part of ocelot.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Implementation of the Masny-Rindal "endemic" oblivious transfer protocol
+//! (cf. <https://eprint.iacr.org/2019/706>), instantiated with Diffie-Hellman
+//! key agreement.
+//!
+//! For each OT, the receiver with choice bit `c` picks a random scalar `b`,
+//! sets `B = b · G`, picks a uniformly random point `r_{1-c}`, and sends `(r_0,
+//! r_1)`, where `r_c = B - H(r_{1-c})` and `H` hashes onto the curve. The
+//! sender, holding `A = a · G`, recovers the two candidate public keys `B_i =
+//! r_i + H(r_{1-i})` and encrypts `m_i` under a key derived from `a · B_i`; the
+//! receiver can only derive the key for `B_c = B`. Since `H` is a random
+//! oracle, the receiver cannot know the discrete logarithm of both `B_0` and
+//! `B_1`, which gives security against a malicious receiver, and `(r_0, r_1)`
+//! is uniformly distributed regardless of `c`, which gives security against a
+//! malicious sender.
+//!
+//! This implementation uses the Ristretto prime order elliptic curve group from
+//! the `curve25519-dalek` library, hashing onto the curve with SHA-512 and
+//! Elligator, and works over blocks rather than arbitrary length messages. As
+//! in `chou_orlandi`, we hash a counter into both `H` and the key derivation
+//! so that different OTs are independent.
+
+use crate::{
+    errors::Error,
+    ot::{Receiver as OtReceiver, Sender as OtSender},
+};
+use curve25519_dalek::{
+    constants::RISTRETTO_BASEPOINT_TABLE,
+    ristretto::RistrettoPoint,
+    scalar::Scalar,
+};
+use rand::{CryptoRng, Rng};
+use scuttlebutt::{AbstractChannel, Block, Malicious, SemiHonest};
+use sha2::{Digest, Sha512};
+
+/// Hashes point `pt` onto the curve, using `tweak` for domain separation.
+#[inline]
+fn hash_to_curve(tweak: u128, pt: &RistrettoPoint) -> RistrettoPoint {
+    let h = Sha512::new()
+        .chain(tweak.to_le_bytes())
+        .chain(pt.compress().as_bytes());
+    RistrettoPoint::from_hash(h)
+}
+
+/// Oblivious transfer sender.
+pub struct Sender {
+    counter: u128,
+}
+
+impl OtSender for Sender {
+    type Msg = Block;
+
+    fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        _: &mut C,
+        _: &mut RNG,
+    ) -> Result<Self, Error> {
+        Ok(Self { counter: 0 })
+    }
+
+    fn send<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        inputs: &[(Block, Block)],
+        mut rng: &mut RNG,
+    ) -> Result<(), Error> {
+        let a = Scalar::random(&mut rng);
+        let pk = &a * &RISTRETTO_BASEPOINT_TABLE;
+        channel.write_pt(&pk)?;
+        channel.flush()?;
+        let ks = (0..inputs.len())
+            .map(|i| {
+                let tweak = self.counter + i as u128;
+                let r0 = channel.read_pt()?;
+                let r1 = channel.read_pt()?;
+                let b0 = r0 + hash_to_curve(tweak, &r1);
+                let b1 = r1 + hash_to_curve(tweak, &r0);
+                let k0 = Block::hash_pt(tweak, &(a * b0));
+                let k1 = Block::hash_pt(tweak, &(a * b1));
+                Ok((k0, k1))
+            })
+            .collect::<Result<Vec<(Block, Block)>, Error>>()?;
+        self.counter += inputs.len() as u128;
+        for (input, k) in inputs.iter().zip(ks) {
+            let c0 = k.0 ^ input.0;
+            let c1 = k.1 ^ input.1;
+            channel.write_block(&c0)?;
+            channel.write_block(&c1)?;
+        }
+        channel.flush()?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Sender {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Masny-Rindal Sender")
+    }
+}
+
+/// Oblivious transfer receiver.
+pub struct Receiver {
+    counter: u128,
+}
+
+impl OtReceiver for Receiver {
+    type Msg = Block;
+
+    fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        _: &mut C,
+        _: &mut RNG,
+    ) -> Result<Self, Error> {
+        Ok(Self { counter: 0 })
+    }
+
+    fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        inputs: &[bool],
+        mut rng: &mut RNG,
+    ) -> Result<Vec<Block>, Error> {
+        let bs = inputs
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                let tweak = self.counter + i as u128;
+                let b = Scalar::random(&mut rng);
+                let pk = &b * &RISTRETTO_BASEPOINT_TABLE;
+                let r = RistrettoPoint::random(&mut rng);
+                let r_ = pk - hash_to_curve(tweak, &r);
+                let (r0, r1) = if *c { (r, r_) } else { (r_, r) };
+                channel.write_pt(&r0)?;
+                channel.write_pt(&r1)?;
+                Ok(b)
+            })
+            .collect::<Result<Vec<Scalar>, Error>>()?;
+        channel.flush()?;
+        let pk = channel.read_pt()?;
+        let ks = bs
+            .into_iter()
+            .enumerate()
+            .map(|(i, b)| Block::hash_pt(self.counter + i as u128, &(b * pk)))
+            .collect::<Vec<Block>>();
+        self.counter += inputs.len() as u128;
+        inputs
+            .iter()
+            .zip(ks)
+            .map(|(c, k)| {
+                let c0 = channel.read_block()?;
+                let c1 = channel.read_block()?;
+                Ok(k ^ if *c { c1 } else { c0 })
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for Receiver {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Masny-Rindal Receiver")
+    }
+}
+
+impl SemiHonest for Sender {}
+impl Malicious for Sender {}
+impl SemiHonest for Receiver {}
+impl Malicious for Receiver {}
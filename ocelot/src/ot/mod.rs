@@ -12,17 +12,27 @@
 //! * `dummy`: a dummy and completely insecure OT for testing purposes.
 //! * `naor_pinkas`: Naor-Pinkas semi-honest OT.
 //! * `chou_orlandi`: Chou-Orlandi malicious OT.
+//! * `masny_rindal`: Masny-Rindal malicious (endemic) OT.
 //! * `alsz`: Asharov-Lindell-Schneider-Zohner semi-honest OT extension (+ correlated and random OT).
 //! * `kos`: Keller-Orsini-Scholl malicious OT extension (+ correlated and random OT).
+//! * `bit`: OT of short messages, packed into bytes, from random OT.
+//!
+//! The protocols that only implement chosen-message OT (the dummy and base
+//! OTs) can be given correlated and random OT by wrapping them in
+//! `adapter::FromChosen`, and any random OT can be turned back into
+//! chosen-message and correlated OT with `adapter::FromRandom`.
 //!
 
+pub mod adapter;
 pub mod alsz;
+pub mod bit;
 pub mod chou_orlandi;
 pub mod dummy;
 pub mod kos;
+pub mod masny_rindal;
 pub mod naor_pinkas;
 
-use crate::errors::Error;
+use crate::{errors::Error, params::SecurityParams};
 use rand::{CryptoRng, Rng};
 use scuttlebutt::AbstractChannel;
 
@@ -34,6 +44,10 @@ pub type ChouOrlandiReceiver = chou_orlandi::Receiver;
 pub type DummySender = dummy::Sender;
 /// Instantiation of the dummy OT receiver.
 pub type DummyReceiver = dummy::Receiver;
+/// Instantiation of the Masny-Rindal OT sender.
+pub type MasnyRindalSender = masny_rindal::Sender;
+/// Instantiation of the Masny-Rindal OT receiver.
+pub type MasnyRindalReceiver = masny_rindal::Receiver;
 /// Instantiation of the Naor-Pinkas OT sender.
 pub type NaorPinkasSender = naor_pinkas::Sender;
 /// Instantiation of the Naor-Pinkas OT receiver.
@@ -42,10 +56,31 @@ pub type NaorPinkasReceiver = naor_pinkas::Receiver;
 pub type AlszSender = alsz::Sender<ChouOrlandiReceiver>;
 /// Instantiation of the ALSZ OT extension receiver, using Chou-Orlandi as the base OT.
 pub type AlszReceiver = alsz::Receiver<ChouOrlandiSender>;
+/// Instantiation of the ALSZ OT extension sender, using Naor-Pinkas as the base OT.
+pub type AlszNaorPinkasSender = alsz::Sender<NaorPinkasReceiver>;
+/// Instantiation of the ALSZ OT extension receiver, using Naor-Pinkas as the base OT.
+pub type AlszNaorPinkasReceiver = alsz::Receiver<NaorPinkasSender>;
+/// Instantiation of the ALSZ OT extension sender, using Masny-Rindal as the base OT.
+pub type AlszMasnyRindalSender = alsz::Sender<MasnyRindalReceiver>;
+/// Instantiation of the ALSZ OT extension receiver, using Masny-Rindal as the base OT.
+pub type AlszMasnyRindalReceiver = alsz::Receiver<MasnyRindalSender>;
 /// Instantiation of the KOS OT extension sender, using Chou-Orlandi as the base OT.
 pub type KosSender = kos::Sender<ChouOrlandiReceiver>;
 /// Instantiation of the KOS OT extension receiver, using Chou-Orlandi as the base OT.
 pub type KosReceiver = kos::Receiver<ChouOrlandiSender>;
+/// Instantiation of the KOS OT extension sender, using Masny-Rindal as the base OT.
+pub type KosMasnyRindalSender = kos::Sender<MasnyRindalReceiver>;
+/// Instantiation of the KOS OT extension receiver, using Masny-Rindal as the base OT.
+pub type KosMasnyRindalReceiver = kos::Receiver<MasnyRindalSender>;
+
+/// Instantiation of the bit-OT sender, using ALSZ random OT.
+pub type AlszBitSender = bit::Sender<AlszSender>;
+/// Instantiation of the bit-OT receiver, using ALSZ random OT.
+pub type AlszBitReceiver = bit::Receiver<AlszReceiver>;
+/// Instantiation of the bit-OT sender, using KOS random OT.
+pub type KosBitSender = bit::Sender<KosSender>;
+/// Instantiation of the bit-OT receiver, using KOS random OT.
+pub type KosBitReceiver = bit::Receiver<KosReceiver>;
 
 /// Trait for one-out-of-two oblivious transfer from the sender's point-of-view.
 pub trait Sender
@@ -61,6 +96,16 @@ where
         channel: &mut C,
         rng: &mut RNG,
     ) -> Result<Self, Error>;
+    /// Runs any one-time initialization, using the security parameters
+    /// `params`. Protocols whose security does not depend on `params` just
+    /// call `init`.
+    fn init_with_params<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        _: SecurityParams,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        Self::init(channel, rng)
+    }
     /// Sends messages.
     fn send<C: AbstractChannel, RNG: CryptoRng + Rng>(
         &mut self,
@@ -85,6 +130,16 @@ where
         channel: &mut C,
         rng: &mut RNG,
     ) -> Result<Self, Error>;
+    /// Runs any one-time initialization, using the security parameters
+    /// `params`. Protocols whose security does not depend on `params` just
+    /// call `init`.
+    fn init_with_params<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        _: SecurityParams,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        Self::init(channel, rng)
+    }
     /// Receives messages.
     fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
         &mut self,
@@ -161,7 +216,7 @@ mod tests {
     #[cfg(feature = "nightly")]
     extern crate test;
     use super::*;
-    use scuttlebutt::{AesRng, Block, Channel};
+    use scuttlebutt::{AesRng, Block, Channel, Malicious, SemiHonest};
     use std::{
         fmt::Display,
         io::{BufReader, BufWriter},
@@ -315,6 +370,32 @@ mod tests {
         }
     }
 
+    fn test_all<
+        OTSender: CorrelatedSender<Msg = Block> + RandomSender<Msg = Block>,
+        OTReceiver: CorrelatedReceiver<Msg = Block> + RandomReceiver<Msg = Block> + Display,
+    >(
+        ninputs: usize,
+    ) {
+        test_otext::<OTSender, OTReceiver>(ninputs);
+        test_cotext::<OTSender, OTReceiver>(ninputs);
+        test_rotext::<OTSender, OTReceiver>(ninputs);
+    }
+
+    #[test]
+    fn test_adapters() {
+        use adapter::{FromChosen, FromRandom};
+        test_all::<FromChosen<DummySender>, FromChosen<DummyReceiver>>(129);
+        test_all::<FromChosen<NaorPinkasSender>, FromChosen<NaorPinkasReceiver>>(129);
+        test_all::<FromChosen<ChouOrlandiSender>, FromChosen<ChouOrlandiReceiver>>(129);
+        test_all::<FromChosen<MasnyRindalSender>, FromChosen<MasnyRindalReceiver>>(129);
+        test_all::<FromRandom<AlszSender>, FromRandom<AlszReceiver>>((1 << 10) + 1);
+        test_all::<FromRandom<KosSender>, FromRandom<KosReceiver>>((1 << 10) + 1);
+        test_all::<
+            FromRandom<FromChosen<ChouOrlandiSender>>,
+            FromRandom<FromChosen<ChouOrlandiReceiver>>,
+        >(129);
+    }
+
     #[test]
     fn test_dummy() {
         test_ot::<DummySender, DummyReceiver>();
@@ -330,6 +411,11 @@ mod tests {
         test_ot::<ChouOrlandiSender, ChouOrlandiReceiver>();
     }
 
+    #[test]
+    fn test_masny_rindal() {
+        test_ot::<MasnyRindalSender, MasnyRindalReceiver>();
+    }
+
     #[test]
     fn test_alsz() {
         let ninputs = 1 << 10;
@@ -353,4 +439,144 @@ mod tests {
         test_cotext::<KosSender, KosReceiver>(ninputs);
         test_rotext::<KosSender, KosReceiver>(ninputs);
     }
+
+    // Zero inputs, and a number of inputs that is not a multiple of 8. Run
+    // with `--features parallel` to cover the parallel path.
+    #[test]
+    fn test_input_lengths() {
+        for &ninputs in &[0, (1 << 14) + 3] {
+            test_all::<AlszSender, AlszReceiver>(ninputs);
+            test_all::<KosSender, KosReceiver>(ninputs);
+        }
+    }
+
+    #[test]
+    fn test_security_markers() {
+        fn semi_honest<T: SemiHonest>() {}
+        fn malicious<T: Malicious>() {}
+        semi_honest::<NaorPinkasSender>();
+        semi_honest::<NaorPinkasReceiver>();
+        semi_honest::<AlszSender>();
+        semi_honest::<AlszReceiver>();
+        semi_honest::<AlszNaorPinkasSender>();
+        semi_honest::<AlszNaorPinkasReceiver>();
+        semi_honest::<AlszMasnyRindalSender>();
+        semi_honest::<AlszMasnyRindalReceiver>();
+        malicious::<ChouOrlandiSender>();
+        malicious::<ChouOrlandiReceiver>();
+        malicious::<MasnyRindalSender>();
+        malicious::<MasnyRindalReceiver>();
+        malicious::<KosSender>();
+        malicious::<KosReceiver>();
+        malicious::<KosMasnyRindalSender>();
+        malicious::<KosMasnyRindalReceiver>();
+    }
+
+    #[test]
+    fn test_alsz_base_ots() {
+        test_all::<AlszNaorPinkasSender, AlszNaorPinkasReceiver>(129);
+        test_all::<AlszMasnyRindalSender, AlszMasnyRindalReceiver>(129);
+    }
+
+    #[test]
+    fn test_generic_constructors() {
+        let ms = rand_block_vec(129)
+            .into_iter()
+            .zip(rand_block_vec(129))
+            .collect::<Vec<(Block, Block)>>();
+        let bs = rand_bool_vec(129);
+        let ms_ = ms.clone();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut ot =
+                crate::ot_extension_sender::<MasnyRindalReceiver, _, _>(&mut channel, &mut rng)
+                    .unwrap();
+            ot.send(&mut channel, &ms_, &mut rng).unwrap();
+            let mut ot = crate::malicious_ot_extension_sender::<MasnyRindalReceiver, _, _>(
+                &mut channel,
+                &mut rng,
+            )
+            .unwrap();
+            ot.send(&mut channel, &ms_, &mut rng).unwrap();
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut ot =
+            crate::ot_extension_receiver::<MasnyRindalSender, _, _>(&mut channel, &mut rng)
+                .unwrap();
+        let results0 = ot.receive(&mut channel, &bs, &mut rng).unwrap();
+        let mut ot = crate::malicious_ot_extension_receiver::<MasnyRindalSender, _, _>(
+            &mut channel,
+            &mut rng,
+        )
+        .unwrap();
+        let results1 = ot.receive(&mut channel, &bs, &mut rng).unwrap();
+        handle.join().unwrap();
+        for (j, (m0, m1)) in ms.into_iter().enumerate() {
+            let m = if bs[j] { m1 } else { m0 };
+            assert_eq!(results0[j], m);
+            assert_eq!(results1[j], m);
+        }
+    }
+
+    #[test]
+    fn test_kos_masny_rindal() {
+        let ninputs = (1 << 10) + 1;
+        test_otext::<KosMasnyRindalSender, KosMasnyRindalReceiver>(ninputs);
+        test_cotext::<KosMasnyRindalSender, KosMasnyRindalReceiver>(ninputs);
+        test_rotext::<KosMasnyRindalSender, KosMasnyRindalReceiver>(ninputs);
+    }
+
+    fn test_kos_with_params(sender_params: SecurityParams, receiver_params: SecurityParams) {
+        let ninputs = 1001;
+        let ms = rand_block_vec(ninputs)
+            .into_iter()
+            .zip(rand_block_vec(ninputs))
+            .collect::<Vec<(Block, Block)>>();
+        let bs = rand_bool_vec(ninputs);
+        let ms_ = ms.clone();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            if let Ok(mut otext) =
+                KosSender::init_with_params(&mut channel, sender_params, &mut rng)
+            {
+                otext.send(&mut channel, &ms_, &mut rng).unwrap();
+            }
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let otext = KosReceiver::init_with_params(&mut channel, receiver_params, &mut rng);
+        if sender_params != receiver_params {
+            assert!(otext.is_err());
+            drop(channel);
+            handle.join().unwrap();
+            return;
+        }
+        let results = otext.unwrap().receive(&mut channel, &bs, &mut rng).unwrap();
+        handle.join().unwrap();
+        for ((result, b), m) in results.into_iter().zip(bs).zip(ms) {
+            assert_eq!(result, if b { m.1 } else { m.0 });
+        }
+    }
+
+    #[test]
+    fn test_kos_params() {
+        let params = SecurityParams::new(112, 41).unwrap();
+        test_kos_with_params(params, params);
+        let params = SecurityParams::new(128, 128).unwrap();
+        test_kos_with_params(params, params);
+        test_kos_with_params(SecurityParams::default(), params);
+    }
 }
@@ -0,0 +1,208 @@
+// -*- mode: rust; -*-
+//
+// This file is part of ocelot.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Security parameters for OT extension and the oblivious PRFs.
+//!
+//! A `SecurityParams` fixes the computational security level `κ` and the
+//! statistical security level `σ`, both in bits. The protocols derive their
+//! sizes from these as follows:
+//!
+//! * Base OTs (`alsz`, `kos`): `128`. Every extension matrix column is a
+//!   `Block`, so this cannot be lowered, and it is at least `κ` since `κ ≤
+//!   128`.
+//! * KOS extra columns: `κ + σ`, rounded up to a multiple of 8. The receiver
+//!   appends this many random choice bits so that the correlation check leaks
+//!   nothing about its real choices, and a cheating receiver passes the check
+//!   with probability at most `2^-σ` (cf. Section 3 of the KOS paper).
+//! * OOS consistency checks: `σ`, each of which a cheating receiver passes
+//!   with probability at most one half. The check challenges are 64-bit
+//!   words, so OOS requires `σ ≤ 64`.
+//! * KKRT pseudorandom code width: `512` bits. The protocol is secure as long
+//!   as no two inputs have codewords at Hamming distance less than `κ`, and
+//!   the width is fixed, so instead the number of inputs `n` is bounded: we
+//!   require `n² · Pr[Bin(512, 1/2) < κ] ≤ 2^-σ`, a union bound over all
+//!   pairs of inputs. For `κ = 128` and `σ = 40` this allows `n ≈ 2^31`.
+//! * KMPRT hashing: the bin sizes are taken from the paper, which chooses them
+//!   for a failure probability of `2^-40`, so KMPRT requires `σ ≤ 40`. The
+//!   number of attempts at building the sender's tables (`N_TABLE_LOOPS`)
+//!   only affects how often the sender gives up, not security.
+//!
+//! Beyond that, `SecurityParams::new` requires `1 ≤ σ ≤ κ ≤ 128`, since all
+//! the primitives underneath (AES-128, the Ristretto base OTs, and the
+//! 128-bit extension matrices) top out at 128 bits of security.
+//!
+//! The protocols that take a `SecurityParams` send it to the other party in
+//! `init_with_params`, and the other party fails if it was configured with
+//! different parameters. Plain `init` uses `SecurityParams::default()` without
+//! sending it, so that KOS, KKRT and OOS keep their original wire format. As a
+//! result, both parties must initialize with the same method.
+
+use crate::errors::Error;
+use scuttlebutt::AbstractChannel;
+
+/// Computational and statistical security levels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SecurityParams {
+    computational: usize,
+    statistical: usize,
+}
+
+impl SecurityParams {
+    /// The maximum supported computational security level.
+    pub const MAX_COMPUTATIONAL: usize = 128;
+
+    /// Makes a new set of security parameters, with computational security
+    /// level `computational` and statistical security level `statistical`.
+    pub fn new(computational: usize, statistical: usize) -> Result<Self, Error> {
+        if computational == 0 || computational > Self::MAX_COMPUTATIONAL {
+            return Err(Error::Other(format!(
+                "computational security must be between 1 and {}, got {}",
+                Self::MAX_COMPUTATIONAL,
+                computational
+            )));
+        }
+        if statistical == 0 || statistical > computational {
+            return Err(Error::Other(format!(
+                "statistical security must be between 1 and the computational security ({}), got {}",
+                computational, statistical
+            )));
+        }
+        Ok(Self {
+            computational,
+            statistical,
+        })
+    }
+
+    /// Returns the computational security level `κ`.
+    pub fn computational(&self) -> usize {
+        self.computational
+    }
+
+    /// Returns the statistical security level `σ`.
+    pub fn statistical(&self) -> usize {
+        self.statistical
+    }
+
+    /// Returns the number of base OTs used by OT extension.
+    pub fn base_ots(&self) -> usize {
+        128
+    }
+
+    /// Returns the number of extra columns used by the KOS correlation check.
+    pub fn kos_extra_columns(&self) -> usize {
+        let ncols = self.computational + self.statistical;
+        if ncols % 8 != 0 {
+            ncols + (8 - ncols % 8)
+        } else {
+            ncols
+        }
+    }
+
+    /// Returns the number of consistency checks run by the OOS OPRF.
+    pub fn oos_checks(&self) -> usize {
+        self.statistical
+    }
+
+    /// Returns the width of the KKRT pseudorandom code, in bits.
+    pub fn kkrt_code_width(&self) -> usize {
+        512
+    }
+
+    /// Returns the maximum number of inputs a KKRT receiver may evaluate the
+    /// OPRF on. This is zero if the parameters are too strong for KKRT.
+    pub fn kkrt_max_inputs(&self) -> u64 {
+        let k = self.kkrt_code_width();
+        // Compute `Pr[Bin(k, 1/2) < κ]` term by term, starting from `2^-k`.
+        let mut term = 2f64.powi(-(k as i32));
+        let mut tail = 0f64;
+        for i in 0..self.computational {
+            tail += term;
+            term *= (k - i) as f64 / (i + 1) as f64;
+        }
+        let log2n = (-(self.statistical as f64) - tail.log2()) / 2.0;
+        if log2n < 0.0 {
+            0
+        } else if log2n >= 64.0 {
+            u64::MAX
+        } else {
+            log2n.exp2().floor() as u64
+        }
+    }
+
+    // Sends the parameters to the other party, who checks them with
+    // `receive`.
+    pub(crate) fn send<C: AbstractChannel>(&self, channel: &mut C) -> Result<(), Error> {
+        channel.write_usize(self.computational)?;
+        channel.write_usize(self.statistical)?;
+        channel.flush()?;
+        Ok(())
+    }
+
+    // Receives the other party's parameters, failing if they differ from ours.
+    pub(crate) fn receive<C: AbstractChannel>(&self, channel: &mut C) -> Result<(), Error> {
+        let computational = channel.read_usize()?;
+        let statistical = channel.read_usize()?;
+        if computational != self.computational || statistical != self.statistical {
+            return Err(Error::Other(format!(
+                "security parameter mismatch: expected {}, got κ = {}, σ = {}",
+                self, computational, statistical
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Default for SecurityParams {
+    fn default() -> Self {
+        Self {
+            computational: 128,
+            statistical: 40,
+        }
+    }
+}
+
+impl std::fmt::Display for SecurityParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "κ = {}, σ = {}", self.computational, self.statistical)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validation() {
+        assert!(SecurityParams::new(128, 40).is_ok());
+        assert!(SecurityParams::new(80, 80).is_ok());
+        assert!(SecurityParams::new(0, 0).is_err());
+        assert!(SecurityParams::new(129, 40).is_err());
+        assert!(SecurityParams::new(128, 0).is_err());
+        assert!(SecurityParams::new(64, 80).is_err());
+        assert_eq!(
+            SecurityParams::new(128, 40).unwrap(),
+            SecurityParams::default()
+        );
+    }
+
+    #[test]
+    fn test_derived() {
+        let params = SecurityParams::default();
+        assert_eq!(params.base_ots(), 128);
+        assert_eq!(params.kos_extra_columns(), 168);
+        assert_eq!(params.oos_checks(), 40);
+        assert_eq!(params.kkrt_code_width(), 512);
+        let n = params.kkrt_max_inputs();
+        assert!(n > 1 << 31 && n < 1 << 32);
+        let params = SecurityParams::new(112, 41).unwrap();
+        assert_eq!(params.kos_extra_columns(), 160);
+        assert!(params.kkrt_max_inputs() > n);
+        // `Pr[Bin(512, 1/2) < 128] ≈ 2^-102.3`, so `σ = 102` allows a single
+        // input and `σ = 103` none at all.
+        assert_eq!(SecurityParams::new(128, 102).unwrap().kkrt_max_inputs(), 1);
+        assert_eq!(SecurityParams::new(128, 103).unwrap().kkrt_max_inputs(), 0);
+    }
+}
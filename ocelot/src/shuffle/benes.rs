@@ -0,0 +1,197 @@
+// -*- mode: rust; -*-
+//
+// This file is part of ocelot.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Beneš permutation networks on `2^k` wires, and the (Opferman-Tsao-Wu)
+//! looping algorithm for routing a permutation through them.
+//!
+//! A network on `n ≥ 4` wires consists of a layer of `n / 2` switches, two
+//! networks on `n / 2` wires (the "top" one, fed by the first output of every
+//! switch, and the "bottom" one, fed by the second), and another layer of `n
+//! / 2` switches, combining the `i`th outputs of the two subnetworks. A
+//! network on two wires is a single switch.
+
+/// A two-input, two-output switch: when its control bit is set it swaps its
+/// inputs, and otherwise passes them straight through.
+#[derive(Clone, Copy, Debug)]
+pub struct Switch {
+    /// The input wires.
+    pub inputs: (usize, usize),
+    /// The output wires.
+    pub outputs: (usize, usize),
+}
+
+/// A Beneš network. The input wires are `0..n`, and every switch creates two
+/// fresh wires.
+pub struct Network {
+    /// The switches, in topological order.
+    pub switches: Vec<Switch>,
+    /// The output wires, in order.
+    pub outputs: Vec<usize>,
+    /// The total number of wires.
+    pub nwires: usize,
+}
+
+impl Network {
+    /// Builds the network on `n` wires, where `n` must be a power of two.
+    pub fn new(n: usize) -> Self {
+        debug_assert!(n.is_power_of_two());
+        let mut switches = Vec::with_capacity(n * log2(n));
+        let mut nwires = n;
+        let inputs = (0..n).collect::<Vec<usize>>();
+        let outputs = build(&inputs, &mut nwires, &mut switches);
+        Self {
+            switches,
+            outputs,
+            nwires,
+        }
+    }
+}
+
+#[inline]
+fn log2(n: usize) -> usize {
+    n.trailing_zeros() as usize
+}
+
+fn switch(
+    inputs: (usize, usize),
+    nwires: &mut usize,
+    switches: &mut Vec<Switch>,
+) -> (usize, usize) {
+    let outputs = (*nwires, *nwires + 1);
+    *nwires += 2;
+    switches.push(Switch { inputs, outputs });
+    outputs
+}
+
+fn build(inputs: &[usize], nwires: &mut usize, switches: &mut Vec<Switch>) -> Vec<usize> {
+    let n = inputs.len();
+    if n == 1 {
+        return inputs.to_vec();
+    }
+    if n == 2 {
+        let (o0, o1) = switch((inputs[0], inputs[1]), nwires, switches);
+        return vec![o0, o1];
+    }
+    let (top, bottom): (Vec<usize>, Vec<usize>) = inputs
+        .chunks(2)
+        .map(|pair| switch((pair[0], pair[1]), nwires, switches))
+        .unzip();
+    let top = build(&top, nwires, switches);
+    let bottom = build(&bottom, nwires, switches);
+    let mut outputs = Vec::with_capacity(n);
+    for (t, b) in top.into_iter().zip(bottom) {
+        let (o0, o1) = switch((t, b), nwires, switches);
+        outputs.push(o0);
+        outputs.push(o1);
+    }
+    outputs
+}
+
+/// Computes the control bits, in the order of `Network::switches`, that
+/// route input `perm[i]` to output `i`. The length of `perm` must be a power
+/// of two.
+pub fn route(perm: &[usize]) -> Vec<bool> {
+    let n = perm.len();
+    debug_assert!(n.is_power_of_two());
+    let mut bits = Vec::with_capacity(n * log2(n));
+    _route(perm, &mut bits);
+    bits
+}
+
+fn _route(perm: &[usize], bits: &mut Vec<bool>) {
+    let n = perm.len();
+    if n == 1 {
+        return;
+    }
+    if n == 2 {
+        bits.push(perm[0] == 1);
+        return;
+    }
+    let mut inv = vec![0; n];
+    for (y, x) in perm.iter().enumerate() {
+        inv[*x] = y;
+    }
+    // Assign each output to the top or bottom subnetwork, such that the two
+    // outputs of each last-layer switch, and the two inputs of each
+    // first-layer switch, use different subnetworks.
+    let mut top: Vec<Option<bool>> = vec![None; n];
+    for start in (0..n).step_by(2) {
+        if top[start].is_some() {
+            continue;
+        }
+        let mut y = start;
+        loop {
+            // Route output `y` through the top, so its partner goes through
+            // the bottom, as does the partner of its input.
+            top[y] = Some(true);
+            top[y ^ 1] = Some(false);
+            let y_ = inv[perm[y] ^ 1];
+            // Output `y_` goes through the bottom, so its partner goes
+            // through the top.
+            top[y_] = Some(false);
+            y = y_ ^ 1;
+            if top[y].is_some() {
+                break;
+            }
+        }
+    }
+    let top = top.into_iter().map(Option::unwrap).collect::<Vec<bool>>();
+    let mut top_perm = vec![0; n / 2];
+    let mut bottom_perm = vec![0; n / 2];
+    let mut first = vec![false; n / 2];
+    for (y, x) in perm.iter().enumerate() {
+        if top[y] {
+            top_perm[y / 2] = x / 2;
+            // The input goes up, which is straight through if it is the first
+            // input of its switch.
+            first[x / 2] = x % 2 == 1;
+        } else {
+            bottom_perm[y / 2] = x / 2;
+        }
+    }
+    bits.extend(first);
+    _route(&top_perm, bits);
+    _route(&bottom_perm, bits);
+    // Output `2j` comes from the top straight through.
+    bits.extend((0..n / 2).map(|j| !top[2 * j]));
+}
+
+/// Evaluates the network in the clear on `values`, using control bits `bits`.
+#[cfg(test)]
+pub fn evaluate<T: Copy + Default>(network: &Network, bits: &[bool], values: &[T]) -> Vec<T> {
+    let mut wires = vec![T::default(); network.nwires];
+    wires[..values.len()].copy_from_slice(values);
+    for (s, b) in network.switches.iter().zip(bits.iter()) {
+        let (v0, v1) = (wires[s.inputs.0], wires[s.inputs.1]);
+        let (o0, o1) = if *b { (v1, v0) } else { (v0, v1) };
+        wires[s.outputs.0] = o0;
+        wires[s.outputs.1] = o1;
+    }
+    network.outputs.iter().map(|w| wires[*w]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::seq::SliceRandom;
+
+    #[test]
+    fn test_route() {
+        let mut rng = rand::thread_rng();
+        for k in 0..10 {
+            let n = 1 << k;
+            let network = Network::new(n);
+            for _ in 0..10 {
+                let mut perm = (0..n).collect::<Vec<usize>>();
+                perm.shuffle(&mut rng);
+                let bits = route(&perm);
+                assert_eq!(bits.len(), network.switches.len());
+                let values = (0..n).collect::<Vec<usize>>();
+                assert_eq!(evaluate(&network, &bits, &values), perm);
+            }
+        }
+    }
+}
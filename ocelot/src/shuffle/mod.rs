@@ -0,0 +1,380 @@
+// -*- mode: rust; -*-
+//
+// This file is part of ocelot.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Two-party oblivious permutation and shuffling of secret-shared vectors,
+//! using an oblivious switching network built from random OT (cf.
+//! <https://eprint.iacr.org/2019/518>, §5, and
+//! <https://eprint.iacr.org/2013/137>).
+//!
+//! The parties hold XOR shares of a vector `x`. In `permute`, the `Permuter`
+//! additionally holds a permutation `π`, and the parties end up with fresh
+//! XOR shares of `y`, where `y[i] = x[π[i]]`. The other party, the `Peer`,
+//! learns nothing about `π`, and neither party learns anything about `x` or
+//! `y`. To permute a vector held in the clear by the peer, the permuter
+//! simply uses all-zero shares (and vice versa).
+//!
+//! The permuter permutes its own shares locally. For the peer's shares, the
+//! peer masks every wire of a Beneš network with a random value and sends
+//! its masked shares to the permuter. For each switch, the peer offers, in a
+//! random OT, the two possible corrections turning the masks on the inputs of
+//! the switch into the masks on its outputs, one for each setting of the
+//! switch. The permuter picks the corrections for the switch settings that
+//! route `π`, so it ends up with the permuted shares masked by the peer's
+//! masks on the output wires, which become the peer's new shares.
+//!
+//! A `Shuffler` runs two such permutations, one with each party as the
+//! permuter, so that neither party learns the overall permutation.
+//!
+//! These protocols are semi-honest secure.
+
+mod benes;
+
+use crate::{
+    errors::Error,
+    ot::{self, RandomReceiver, RandomSender},
+};
+use rand::{seq::SliceRandom, CryptoRng, Rng, SeedableRng};
+use scuttlebutt::{AbstractChannel, AesRng, Block, Block512, SemiHonest};
+use std::ops::BitXor;
+
+/// Permuter using ALSZ random OT.
+pub type AlszPermuter = Permuter<ot::AlszReceiver>;
+/// Peer using ALSZ random OT.
+pub type AlszPeer = Peer<ot::AlszSender>;
+/// Shuffler using ALSZ random OT.
+pub type AlszShuffler = Shuffler<ot::AlszSender, ot::AlszReceiver>;
+
+/// Trait for the types of the shares that can be permuted.
+pub trait Share: Copy + Default + Send + Sync + BitXor<Output = Self> {
+    /// Samples a uniformly random share.
+    fn random<RNG: Rng>(rng: &mut RNG) -> Self;
+    /// Writes the share to `channel`.
+    fn write<C: AbstractChannel>(&self, channel: &mut C) -> std::io::Result<()>;
+    /// Reads a share from `channel`.
+    fn read<C: AbstractChannel>(channel: &mut C) -> std::io::Result<Self>;
+}
+
+impl Share for Block {
+    #[inline]
+    fn random<RNG: Rng>(rng: &mut RNG) -> Self {
+        rng.gen()
+    }
+    #[inline]
+    fn write<C: AbstractChannel>(&self, channel: &mut C) -> std::io::Result<()> {
+        channel.write_block(self)
+    }
+    #[inline]
+    fn read<C: AbstractChannel>(channel: &mut C) -> std::io::Result<Self> {
+        channel.read_block()
+    }
+}
+
+impl Share for Block512 {
+    #[inline]
+    fn random<RNG: Rng>(rng: &mut RNG) -> Self {
+        rng.gen()
+    }
+    #[inline]
+    fn write<C: AbstractChannel>(&self, channel: &mut C) -> std::io::Result<()> {
+        channel.write_block512(self)
+    }
+    #[inline]
+    fn read<C: AbstractChannel>(channel: &mut C) -> std::io::Result<Self> {
+        channel.read_block512()
+    }
+}
+
+// Expand a random OT message into a pad for the two corrections of a switch.
+#[inline]
+fn pad<T: Share>(key: Block) -> (T, T) {
+    let mut rng = AesRng::from_seed(key);
+    (T::random(&mut rng), T::random(&mut rng))
+}
+
+/// The party holding the permutation.
+pub struct Permuter<OT: RandomReceiver<Msg = Block> + SemiHonest> {
+    ot: OT,
+}
+
+impl<OT: RandomReceiver<Msg = Block> + SemiHonest> Permuter<OT> {
+    /// Runs any one-time initialization.
+    pub fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let ot = OT::init(channel, rng)?;
+        Ok(Self { ot })
+    }
+
+    /// Permutes the vector shared as `shares` (ours) and the peer's shares by
+    /// `perm`, returning our shares of the vector whose `i`th entry is entry
+    /// `perm[i]` of the input. `perm` must be a permutation of `0..n`, where
+    /// `n` is the number of shares held by each party.
+    pub fn permute<C: AbstractChannel, RNG: CryptoRng + Rng, T: Share>(
+        &mut self,
+        channel: &mut C,
+        perm: &[usize],
+        shares: &[T],
+        rng: &mut RNG,
+    ) -> Result<Vec<T>, Error> {
+        let n = shares.len();
+        if perm.len() != n {
+            return Err(Error::InvalidInputLength);
+        }
+        let mut seen = vec![false; n];
+        for i in perm.iter() {
+            if *i >= n || seen[*i] {
+                return Err(Error::Other(format!("{:?} is not a permutation", perm)));
+            }
+            seen[*i] = true;
+        }
+        let n_ = channel.read_u64()? as usize;
+        if n_ != n {
+            return Err(Error::Other(format!(
+                "permutation length mismatch: expected {}, got {}",
+                n, n_
+            )));
+        }
+        // Pad the permutation with fixed points up to a power of two.
+        let size = n.next_power_of_two();
+        let perm_ = perm.iter().cloned().chain(n..size).collect::<Vec<usize>>();
+        let network = benes::Network::new(size);
+        let bits = benes::route(&perm_);
+        let mut wires = vec![T::default(); network.nwires];
+        for wire in wires[..size].iter_mut() {
+            *wire = T::read(channel)?;
+        }
+        let keys = self.ot.receive_random(channel, &bits, rng)?;
+        for ((s, b), key) in network.switches.iter().zip(bits.iter()).zip(keys) {
+            let (p0, p1) = pad::<T>(key);
+            let (m00, m01) = (T::read(channel)?, T::read(channel)?);
+            let (m10, m11) = (T::read(channel)?, T::read(channel)?);
+            let (c0, c1) = if *b {
+                (m10 ^ p0, m11 ^ p1)
+            } else {
+                (m00 ^ p0, m01 ^ p1)
+            };
+            let (v0, v1) = (wires[s.inputs.0], wires[s.inputs.1]);
+            let (v0, v1) = if *b { (v1, v0) } else { (v0, v1) };
+            wires[s.outputs.0] = v0 ^ c0;
+            wires[s.outputs.1] = v1 ^ c1;
+        }
+        Ok(network.outputs[..n]
+            .iter()
+            .zip(perm.iter())
+            .map(|(w, i)| wires[*w] ^ shares[*i])
+            .collect())
+    }
+}
+
+/// The party not holding the permutation.
+pub struct Peer<OT: RandomSender<Msg = Block> + SemiHonest> {
+    ot: OT,
+}
+
+impl<OT: RandomSender<Msg = Block> + SemiHonest> Peer<OT> {
+    /// Runs any one-time initialization.
+    pub fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let ot = OT::init(channel, rng)?;
+        Ok(Self { ot })
+    }
+
+    /// Permutes the vector shared as `shares` (ours) and the permuter's
+    /// shares by the permuter's permutation, returning our shares of the
+    /// result.
+    pub fn permute<C: AbstractChannel, RNG: CryptoRng + Rng, T: Share>(
+        &mut self,
+        channel: &mut C,
+        shares: &[T],
+        rng: &mut RNG,
+    ) -> Result<Vec<T>, Error> {
+        let n = shares.len();
+        channel.write_u64(n as u64)?;
+        let size = n.next_power_of_two();
+        let network = benes::Network::new(size);
+        let masks = (0..network.nwires)
+            .map(|_| T::random(rng))
+            .collect::<Vec<T>>();
+        for (i, mask) in masks[..size].iter().enumerate() {
+            let x = if i < n { shares[i] } else { T::default() };
+            (x ^ *mask).write(channel)?;
+        }
+        channel.flush()?;
+        let keys = self.ot.send_random(channel, network.switches.len(), rng)?;
+        for (s, (k0, k1)) in network.switches.iter().zip(keys) {
+            let (a0, a1) = (masks[s.inputs.0], masks[s.inputs.1]);
+            let (c0, c1) = (masks[s.outputs.0], masks[s.outputs.1]);
+            let (p00, p01) = pad::<T>(k0);
+            let (p10, p11) = pad::<T>(k1);
+            (a0 ^ c0 ^ p00).write(channel)?;
+            (a1 ^ c1 ^ p01).write(channel)?;
+            (a1 ^ c0 ^ p10).write(channel)?;
+            (a0 ^ c1 ^ p11).write(channel)?;
+        }
+        channel.flush()?;
+        Ok(network.outputs[..n].iter().map(|w| masks[*w]).collect())
+    }
+}
+
+/// One party of a two-party oblivious shuffle, in which each party applies a
+/// random permutation of its own.
+pub struct Shuffler<
+    OTS: RandomSender<Msg = Block> + SemiHonest,
+    OTR: RandomReceiver<Msg = Block> + SemiHonest,
+> {
+    permuter: Permuter<OTR>,
+    peer: Peer<OTS>,
+    first: bool,
+}
+
+impl<
+        OTS: RandomSender<Msg = Block> + SemiHonest,
+        OTR: RandomReceiver<Msg = Block> + SemiHonest,
+    > Shuffler<OTS, OTR>
+{
+    /// Runs any one-time initialization. Exactly one of the two parties must
+    /// set `first`; that party permutes first.
+    pub fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        first: bool,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let (permuter, peer) = if first {
+            let permuter = Permuter::init(channel, rng)?;
+            let peer = Peer::init(channel, rng)?;
+            (permuter, peer)
+        } else {
+            let peer = Peer::init(channel, rng)?;
+            let permuter = Permuter::init(channel, rng)?;
+            (permuter, peer)
+        };
+        Ok(Self {
+            permuter,
+            peer,
+            first,
+        })
+    }
+
+    /// Shuffles the vector shared as `shares` (ours) and the other party's
+    /// shares, returning our shares of the result.
+    pub fn shuffle<C: AbstractChannel, RNG: CryptoRng + Rng, T: Share>(
+        &mut self,
+        channel: &mut C,
+        shares: &[T],
+        rng: &mut RNG,
+    ) -> Result<Vec<T>, Error> {
+        let mut perm = (0..shares.len()).collect::<Vec<usize>>();
+        perm.shuffle(rng);
+        if self.first {
+            let shares = self.permuter.permute(channel, &perm, shares, rng)?;
+            self.peer.permute(channel, &shares, rng)
+        } else {
+            let shares = self.peer.permute(channel, shares, rng)?;
+            self.permuter.permute(channel, &perm, &shares, rng)
+        }
+    }
+}
+
+impl<OT: RandomReceiver<Msg = Block> + SemiHonest> SemiHonest for Permuter<OT> {}
+impl<OT: RandomSender<Msg = Block> + SemiHonest> SemiHonest for Peer<OT> {}
+impl<
+        OTS: RandomSender<Msg = Block> + SemiHonest,
+        OTR: RandomReceiver<Msg = Block> + SemiHonest,
+    > SemiHonest for Shuffler<OTS, OTR>
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scuttlebutt::Channel;
+    use std::{
+        io::{BufReader, BufWriter},
+        os::unix::net::UnixStream,
+    };
+
+    fn rand_vec<T: Share>(n: usize) -> Vec<T> {
+        let mut rng = AesRng::new();
+        (0..n).map(|_| T::random(&mut rng)).collect()
+    }
+
+    fn _test_permute<T: Share + PartialEq + std::fmt::Debug + 'static>(n: usize) {
+        let mut rng = AesRng::new();
+        let (xs0, xs1) = (rand_vec::<T>(n), rand_vec::<T>(n));
+        let mut perm = (0..n).collect::<Vec<usize>>();
+        perm.shuffle(&mut rng);
+        let xs1_ = xs1.clone();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut peer = AlszPeer::init(&mut channel, &mut rng).unwrap();
+            peer.permute(&mut channel, &xs1_, &mut rng).unwrap()
+        });
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut permuter = AlszPermuter::init(&mut channel, &mut rng).unwrap();
+        let ys0 = permuter
+            .permute(&mut channel, &perm, &xs0, &mut rng)
+            .unwrap();
+        let ys1 = handle.join().unwrap();
+        assert_eq!(ys0.len(), n);
+        assert_eq!(ys1.len(), n);
+        for (i, j) in perm.iter().enumerate() {
+            assert_eq!(ys0[i] ^ ys1[i], xs0[*j] ^ xs1[*j]);
+        }
+    }
+
+    #[test]
+    fn test_permute() {
+        for n in [0, 1, 2, 3, 5, 16, 100].iter() {
+            _test_permute::<Block>(*n);
+            _test_permute::<Block512>(*n);
+        }
+    }
+
+    #[test]
+    fn test_shuffle() {
+        let n = 100;
+        let (xs0, xs1) = (rand_vec::<Block>(n), rand_vec::<Block>(n));
+        let xs1_ = xs1.clone();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut shuffler = AlszShuffler::init(&mut channel, false, &mut rng).unwrap();
+            shuffler.shuffle(&mut channel, &xs1_, &mut rng).unwrap()
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut shuffler = AlszShuffler::init(&mut channel, true, &mut rng).unwrap();
+        let ys0 = shuffler.shuffle(&mut channel, &xs0, &mut rng).unwrap();
+        let ys1 = handle.join().unwrap();
+        let mut xs = xs0
+            .iter()
+            .zip(xs1.iter())
+            .map(|(x0, x1)| u128::from(*x0 ^ *x1))
+            .collect::<Vec<u128>>();
+        let mut ys = ys0
+            .iter()
+            .zip(ys1.iter())
+            .map(|(y0, y1)| u128::from(*y0 ^ *y1))
+            .collect::<Vec<u128>>();
+        xs.sort();
+        ys.sort();
+        assert_eq!(xs, ys);
+    }
+}
@@ -0,0 +1,253 @@
+// -*- mode: rust; -*-
+//
+// This file is part of ocelot.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Boolean AND triples from random OT (cf.
+//! <https://eprint.iacr.org/2013/552>, §5.1).
+//!
+//! A random OT with messages `(m_0, m_1)` and choice bit `y` gives the sender
+//! a random bit `x = lsb(m_0) ⊕ lsb(m_1)` and the parties shares `lsb(m_0)`
+//! and `lsb(m_y)` of `x ∧ y`. Running two such OTs, with the receiver
+//! choosing `b_1` and `a_1`, gives shares of `a_0 ∧ b_1` and `b_0 ∧ a_1`,
+//! from which the parties compute shares of `c = (a_0 ⊕ a_1) ∧ (b_0 ⊕ b_1)`.
+
+use crate::{
+    errors::Error,
+    ot::{RandomReceiver, RandomSender},
+    triple::{cointoss_rng, compare, Triple, TripleGenerator},
+};
+use rand::{CryptoRng, Rng};
+use scuttlebutt::{AbstractChannel, Block, SemiHonest};
+
+/// Boolean triple generator for the first party.
+pub struct Sender<OT: RandomSender<Msg = Block> + SemiHonest> {
+    ot: OT,
+    check: bool,
+}
+
+impl<OT: RandomSender<Msg = Block> + SemiHonest> Sender<OT> {
+    /// Runs the one-time initialization, enabling the consistency check if
+    /// `check` is set. The other party must make the same choice.
+    pub fn init_with_check<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        check: bool,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        channel.write_bool(check)?;
+        channel.flush()?;
+        let ot = OT::init(channel, rng)?;
+        Ok(Self { ot, check })
+    }
+}
+
+impl<OT: RandomSender<Msg = Block> + SemiHonest> TripleGenerator for Sender<OT> {
+    type Item = bool;
+
+    fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        Self::init_with_check(channel, false, rng)
+    }
+
+    fn generate<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        n: usize,
+        rng: &mut RNG,
+    ) -> Result<Vec<Triple<bool>>, Error> {
+        let m = if self.check { 2 * n } else { n };
+        let ms = self.ot.send_random(channel, 2 * m, rng)?;
+        let triples = ms[..m]
+            .iter()
+            .zip(ms[m..].iter())
+            .map(|((m0, m1), (n0, n1))| {
+                let a = m0.lsb() ^ m1.lsb();
+                let b = n0.lsb() ^ n1.lsb();
+                Triple {
+                    a,
+                    b,
+                    c: (a & b) ^ m0.lsb() ^ n0.lsb(),
+                }
+            })
+            .collect::<Vec<Triple<bool>>>();
+        if self.check {
+            sacrifice(channel, true, triples, rng)
+        } else {
+            Ok(triples)
+        }
+    }
+}
+
+/// Boolean triple generator for the second party.
+pub struct Receiver<OT: RandomReceiver<Msg = Block> + SemiHonest> {
+    ot: OT,
+    check: bool,
+}
+
+impl<OT: RandomReceiver<Msg = Block> + SemiHonest> Receiver<OT> {
+    /// Runs the one-time initialization, enabling the consistency check if
+    /// `check` is set. Fails if the other party made a different choice.
+    pub fn init_with_check<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        check: bool,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let check_ = channel.read_bool()?;
+        if check_ != check {
+            return Err(Error::Other(format!(
+                "triple consistency check mismatch: expected {}",
+                check
+            )));
+        }
+        let ot = OT::init(channel, rng)?;
+        Ok(Self { ot, check })
+    }
+}
+
+impl<OT: RandomReceiver<Msg = Block> + SemiHonest> TripleGenerator for Receiver<OT> {
+    type Item = bool;
+
+    fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        Self::init_with_check(channel, false, rng)
+    }
+
+    fn generate<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        n: usize,
+        rng: &mut RNG,
+    ) -> Result<Vec<Triple<bool>>, Error> {
+        let m = if self.check { 2 * n } else { n };
+        // Choose our `b`s then our `a`s.
+        let choices = (0..2 * m).map(|_| rng.gen::<bool>()).collect::<Vec<bool>>();
+        let ts = self.ot.receive_random(channel, &choices, rng)?;
+        let triples = (0..m)
+            .map(|i| {
+                let a = choices[m + i];
+                let b = choices[i];
+                Triple {
+                    a,
+                    b,
+                    c: (a & b) ^ ts[i].lsb() ^ ts[m + i].lsb(),
+                }
+            })
+            .collect::<Vec<Triple<bool>>>();
+        if self.check {
+            sacrifice(channel, false, triples, rng)
+        } else {
+            Ok(triples)
+        }
+    }
+}
+
+// The boolean version of `triple::sacrifice`, with all keys and values being
+// bits.
+fn sacrifice<C: AbstractChannel, RNG: CryptoRng + Rng>(
+    channel: &mut C,
+    first: bool,
+    mut triples: Vec<Triple<bool>>,
+    rng: &mut RNG,
+) -> Result<Vec<Triple<bool>>, Error> {
+    let n = triples.len() / 2;
+    let checks = triples.split_off(n);
+    let mut rng_ = cointoss_rng(channel, first, rng)?;
+    let rs = (0..n).map(|_| rng_.gen::<bool>()).collect::<Vec<bool>>();
+    let mut rhos_sigmas = Vec::with_capacity(2 * n);
+    for ((t, t_), r) in triples.iter().zip(checks.iter()).zip(rs.iter()) {
+        rhos_sigmas.push((r & t.a) ^ t_.a);
+        rhos_sigmas.push(t.b ^ t_.b);
+    }
+    if first {
+        write_bools(channel, &rhos_sigmas)?;
+    }
+    let rhos_sigmas_ = read_bools(channel, rhos_sigmas.len())?;
+    if !first {
+        write_bools(channel, &rhos_sigmas)?;
+    }
+    let es = triples
+        .iter()
+        .zip(checks.iter())
+        .zip(rs.iter())
+        .zip(rhos_sigmas.chunks(2).zip(rhos_sigmas_.chunks(2)))
+        .map(|(((t, t_), r), (rs, rs_))| {
+            let (rho, sigma) = (rs[0] ^ rs_[0], rs[1] ^ rs_[1]);
+            ((r & t.c) ^ t_.c ^ (rho & t.b) ^ (sigma & t_.a)) as u8
+        })
+        .collect::<Vec<u8>>();
+    compare(channel, first, &es, rng)?;
+    Ok(triples)
+}
+
+fn write_bools<C: AbstractChannel>(channel: &mut C, bs: &[bool]) -> Result<(), Error> {
+    for b in bs.iter() {
+        channel.write_bool(*b)?;
+    }
+    channel.flush()?;
+    Ok(())
+}
+
+fn read_bools<C: AbstractChannel>(channel: &mut C, n: usize) -> Result<Vec<bool>, Error> {
+    (0..n)
+        .map(|_| channel.read_bool().map_err(Error::from))
+        .collect()
+}
+
+impl<OT: RandomSender<Msg = Block> + SemiHonest> SemiHonest for Sender<OT> {}
+impl<OT: RandomReceiver<Msg = Block> + SemiHonest> SemiHonest for Receiver<OT> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ot;
+    use scuttlebutt::{AesRng, Channel};
+    use std::{
+        io::{BufReader, BufWriter},
+        os::unix::net::UnixStream,
+    };
+
+    fn _test_triples(ns: &[usize], check: bool) {
+        let ns_ = ns.to_vec();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut gen =
+                Sender::<ot::AlszSender>::init_with_check(&mut channel, check, &mut rng).unwrap();
+            ns_.iter()
+                .map(|n| gen.generate(&mut channel, *n, &mut rng).unwrap())
+                .collect::<Vec<Vec<Triple<bool>>>>()
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut gen =
+            Receiver::<ot::AlszReceiver>::init_with_check(&mut channel, check, &mut rng).unwrap();
+        let tss1 = ns
+            .iter()
+            .map(|n| gen.generate(&mut channel, *n, &mut rng).unwrap())
+            .collect::<Vec<Vec<Triple<bool>>>>();
+        let tss0 = handle.join().unwrap();
+        for ((ts0, ts1), n) in tss0.iter().zip(tss1.iter()).zip(ns.iter()) {
+            assert_eq!(ts0.len(), *n);
+            assert_eq!(ts1.len(), *n);
+            for (t0, t1) in ts0.iter().zip(ts1.iter()) {
+                assert_eq!((t0.a ^ t1.a) & (t0.b ^ t1.b), t0.c ^ t1.c);
+            }
+        }
+    }
+
+    #[test]
+    fn test_triples() {
+        _test_triples(&[1, 10, 0, 1000], false);
+        _test_triples(&[1, 10, 0, 1000], true);
+    }
+}
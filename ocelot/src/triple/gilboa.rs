@@ -0,0 +1,238 @@
+// -*- mode: rust; -*-
+//
+// This file is part of ocelot.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Multiplication triples over a ring, built on Gilboa's oblivious linear
+//! evaluation (OLE) protocol from correlated OT (see `vole::gilboa`).
+//!
+//! To generate a triple, each party picks random shares `a_i` and `b_i`, and
+//! the parties run two OLEs, on `(a_0, b_1)` and on `(b_0, a_1)`. Adding in
+//! the local products `a_i · b_i` gives shares of `c = (a_0 + a_1) · (b_0 +
+//! b_1)`. In both OLEs the first party acts as the OLE sender, so a single
+//! OLE instance is enough.
+
+use crate::{
+    errors::Error,
+    ot::{CorrelatedReceiver, CorrelatedSender},
+    triple::{sacrifice, Triple, TripleGenerator},
+    vole::{
+        gilboa::{OleReceiver, OleSender},
+        Ring,
+    },
+};
+use rand::{CryptoRng, Rng};
+use scuttlebutt::{AbstractChannel, Block, SemiHonest};
+
+/// Gilboa triple generator for the first party.
+pub struct Sender<R: Ring, OT: CorrelatedSender<Msg = Block> + SemiHonest> {
+    ole: OleSender<R, OT>,
+    check: bool,
+}
+
+impl<R: Ring, OT: CorrelatedSender<Msg = Block> + SemiHonest> Sender<R, OT> {
+    /// Runs the one-time initialization, enabling the consistency check if
+    /// `check` is set. The other party must make the same choice.
+    pub fn init_with_check<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        check: bool,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        channel.write_bool(check)?;
+        channel.flush()?;
+        let ole = OleSender::init(channel, rng)?;
+        Ok(Self { ole, check })
+    }
+}
+
+impl<R: Ring, OT: CorrelatedSender<Msg = Block> + SemiHonest> TripleGenerator for Sender<R, OT> {
+    type Item = R;
+
+    fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        Self::init_with_check(channel, false, rng)
+    }
+
+    fn generate<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        n: usize,
+        rng: &mut RNG,
+    ) -> Result<Vec<Triple<R>>, Error> {
+        let m = if self.check { 2 * n } else { n };
+        let mut xs = (0..2 * m).map(|_| R::random(rng)).collect::<Vec<R>>();
+        let shares = self.ole.multiply(channel, &xs, rng)?;
+        let bs = xs.split_off(m);
+        let triples = xs
+            .into_iter()
+            .zip(bs)
+            .zip(shares[..m].iter().zip(shares[m..].iter()))
+            .map(|((a, b), (ab, ba))| Triple {
+                a,
+                b,
+                c: a * b + *ab + *ba,
+            })
+            .collect::<Vec<Triple<R>>>();
+        if self.check {
+            sacrifice(channel, true, triples, rng)
+        } else {
+            Ok(triples)
+        }
+    }
+}
+
+/// Gilboa triple generator for the second party.
+pub struct Receiver<R: Ring, OT: CorrelatedReceiver<Msg = Block> + SemiHonest> {
+    ole: OleReceiver<R, OT>,
+    check: bool,
+}
+
+impl<R: Ring, OT: CorrelatedReceiver<Msg = Block> + SemiHonest> Receiver<R, OT> {
+    /// Runs the one-time initialization, enabling the consistency check if
+    /// `check` is set. Fails if the other party made a different choice.
+    pub fn init_with_check<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        check: bool,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let check_ = channel.read_bool()?;
+        if check_ != check {
+            return Err(Error::Other(format!(
+                "triple consistency check mismatch: expected {}",
+                check
+            )));
+        }
+        let ole = OleReceiver::init(channel, rng)?;
+        Ok(Self { ole, check })
+    }
+}
+
+impl<R: Ring, OT: CorrelatedReceiver<Msg = Block> + SemiHonest> TripleGenerator
+    for Receiver<R, OT>
+{
+    type Item = R;
+
+    fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        Self::init_with_check(channel, false, rng)
+    }
+
+    fn generate<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        n: usize,
+        rng: &mut RNG,
+    ) -> Result<Vec<Triple<R>>, Error> {
+        let m = if self.check { 2 * n } else { n };
+        let mut xs = (0..2 * m).map(|_| R::random(rng)).collect::<Vec<R>>();
+        let bs = xs.split_off(m);
+        // The first party's inputs are its `a`s then its `b`s, so ours are our
+        // `b`s then our `a`s.
+        let ys = bs.iter().chain(xs.iter()).cloned().collect::<Vec<R>>();
+        let shares = self.ole.multiply(channel, &ys, rng)?;
+        let triples = xs
+            .into_iter()
+            .zip(bs)
+            .zip(shares[..m].iter().zip(shares[m..].iter()))
+            .map(|((a, b), (ab, ba))| Triple {
+                a,
+                b,
+                c: a * b + *ab + *ba,
+            })
+            .collect::<Vec<Triple<R>>>();
+        if self.check {
+            sacrifice(channel, false, triples, rng)
+        } else {
+            Ok(triples)
+        }
+    }
+}
+
+impl<R: Ring, OT: CorrelatedSender<Msg = Block> + SemiHonest> SemiHonest for Sender<R, OT> {}
+impl<R: Ring, OT: CorrelatedReceiver<Msg = Block> + SemiHonest> SemiHonest for Receiver<R, OT> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ot,
+        vole::{Fp, Gf128, Z2k},
+    };
+    use scuttlebutt::{AesRng, Channel};
+    use std::{
+        io::{BufReader, BufWriter},
+        os::unix::net::UnixStream,
+    };
+
+    fn _test_triples<R: Ring + 'static>(ns: &[usize], check: bool) {
+        let ns_ = ns.to_vec();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut gen =
+                Sender::<R, ot::AlszSender>::init_with_check(&mut channel, check, &mut rng)
+                    .unwrap();
+            ns_.iter()
+                .map(|n| gen.generate(&mut channel, *n, &mut rng).unwrap())
+                .collect::<Vec<Vec<Triple<R>>>>()
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut gen =
+            Receiver::<R, ot::AlszReceiver>::init_with_check(&mut channel, check, &mut rng)
+                .unwrap();
+        let tss1 = ns
+            .iter()
+            .map(|n| gen.generate(&mut channel, *n, &mut rng).unwrap())
+            .collect::<Vec<Vec<Triple<R>>>>();
+        let tss0 = handle.join().unwrap();
+        for ((ts0, ts1), n) in tss0.iter().zip(tss1.iter()).zip(ns.iter()) {
+            assert_eq!(ts0.len(), *n);
+            assert_eq!(ts1.len(), *n);
+            for (t0, t1) in ts0.iter().zip(ts1.iter()) {
+                assert_eq!((t0.a + t1.a) * (t0.b + t1.b), t0.c + t1.c);
+            }
+        }
+    }
+
+    #[test]
+    fn test_triples() {
+        _test_triples::<Z2k<u32>>(&[1, 10, 0, 100], false);
+        _test_triples::<Z2k<u64>>(&[1, 10, 0, 100], false);
+        _test_triples::<Z2k<u64>>(&[1, 10, 0, 100], true);
+        _test_triples::<Fp>(&[1, 10, 0, 100], false);
+        _test_triples::<Fp>(&[1, 10, 0, 100], true);
+        _test_triples::<Gf128>(&[10], true);
+    }
+
+    #[test]
+    fn test_check_mismatch() {
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let _ = Sender::<Fp, ot::AlszSender>::init_with_check(&mut channel, true, &mut rng);
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let result =
+            Receiver::<Fp, ot::AlszReceiver>::init_with_check(&mut channel, false, &mut rng);
+        assert!(result.is_err());
+        drop(channel);
+        handle.join().unwrap();
+    }
+}
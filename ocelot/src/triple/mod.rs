@@ -0,0 +1,292 @@
+// -*- mode: rust; -*-
+//
+// This file is part of ocelot.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Multiplication triple traits + instantiations.
+//!
+//! A (Beaver) multiplication triple is an additive secret sharing of random
+//! `a` and `b` together with `c = a · b`. Each party ends up with a `Triple`
+//! holding its shares, such that `(a_0 + a_1) · (b_0 + b_1) = c_0 + c_1`.
+//!
+//! This module provides the following triple generators:
+//!
+//! * `gilboa`: triples over any `vole::Ring` (e.g., `Z_{2^k}` or a prime
+//!   field), built on Gilboa's oblivious linear evaluation (OLE) protocol
+//!   from `vole::gilboa`, which uses correlated OT.
+//! * `boolean`: boolean AND triples, built from random OT (cf.
+//!   <https://eprint.iacr.org/2013/552>, §5.1).
+//!
+//! Both are semi-honest secure. Optionally, each triple can be checked by
+//! "sacrificing" a second triple acting as a one-time MAC on it under a
+//! jointly random key `r`. The check catches a party that deviates during
+//! generation (e.g., by sending inconsistent OLE corrections), except with
+//! probability at most `1 / |R|` per bad triple over a field `R`, and at most
+//! `1/2` per bad triple over `Z_{2^k}` or for boolean triples. It doubles the
+//! cost of generation and does not make the protocols maliciously secure.
+
+pub mod boolean;
+pub mod gilboa;
+
+use crate::{
+    errors::Error,
+    ot,
+    vole::{Fp, Ring, Z2k},
+};
+use rand::{CryptoRng, Rng, SeedableRng};
+use scuttlebutt::{
+    cointoss,
+    commitment::{Commitment, ShaCommitment},
+    AbstractChannel,
+    AesRng,
+    Block,
+    Channel,
+};
+use sha2::{Digest, Sha256};
+use std::io::ErrorKind;
+
+/// Gilboa triple generator over `Z_{2^64}` (first party), using ALSZ
+/// correlated OT.
+pub type Z64TripleSender = gilboa::Sender<Z2k<u64>, ot::AlszSender>;
+/// Gilboa triple generator over `Z_{2^64}` (second party), using ALSZ
+/// correlated OT.
+pub type Z64TripleReceiver = gilboa::Receiver<Z2k<u64>, ot::AlszReceiver>;
+/// Gilboa triple generator over `Fp` (first party), using ALSZ correlated
+/// OT.
+pub type FpTripleSender = gilboa::Sender<Fp, ot::AlszSender>;
+/// Gilboa triple generator over `Fp` (second party), using ALSZ correlated
+/// OT.
+pub type FpTripleReceiver = gilboa::Receiver<Fp, ot::AlszReceiver>;
+/// Boolean AND triple generator (first party), using ALSZ random OT.
+pub type AndTripleSender = boolean::Sender<ot::AlszSender>;
+/// Boolean AND triple generator (second party), using ALSZ random OT.
+pub type AndTripleReceiver = boolean::Receiver<ot::AlszReceiver>;
+
+/// One party's shares of a multiplication triple.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Triple<T> {
+    /// The share of `a`.
+    pub a: T,
+    /// The share of `b`.
+    pub b: T,
+    /// The share of `c = a · b`.
+    pub c: T,
+}
+
+/// Trait for one party of a two-party triple generation protocol.
+pub trait TripleGenerator
+where
+    Self: Sized,
+{
+    /// The type of the shares.
+    type Item;
+    /// Runs any one-time initialization.
+    fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Self, Error>;
+    /// Generates a batch of `n` triples, returning this party's shares.
+    fn generate<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        n: usize,
+        rng: &mut RNG,
+    ) -> Result<Vec<Triple<Self::Item>>, Error>;
+}
+
+#[inline]
+fn check_failed() -> Error {
+    Error::from(std::io::Error::new(
+        ErrorKind::InvalidData,
+        "Consistency check failed",
+    ))
+}
+
+// Agree on a seed for the check's random keys.
+fn cointoss_rng<C: AbstractChannel, RNG: CryptoRng + Rng>(
+    channel: &mut C,
+    first: bool,
+    rng: &mut RNG,
+) -> Result<AesRng, Error> {
+    let seed = rng.gen::<Block>();
+    let seed = if first {
+        cointoss::send(channel, &[seed])?
+    } else {
+        cointoss::receive(channel, &[seed])?
+    };
+    Ok(AesRng::from_seed(seed[0]))
+}
+
+// Check that both parties hold the same `bytes`, without either party being
+// able to choose its `bytes` after seeing the other's: the first party
+// commits to its bytes, the second party sends a hash of its bytes, and the
+// first party then opens its commitment.
+fn compare<C: AbstractChannel, RNG: CryptoRng + Rng>(
+    channel: &mut C,
+    first: bool,
+    bytes: &[u8],
+    rng: &mut RNG,
+) -> Result<(), Error> {
+    if first {
+        let seed = rng.gen::<[u8; 32]>();
+        let mut commitment = ShaCommitment::new(seed);
+        commitment.input(bytes);
+        channel.write_bytes(&commitment.finish())?;
+        channel.flush()?;
+        let mut hash = [0u8; 32];
+        channel.read_bytes(&mut hash)?;
+        if hash[..] != Sha256::digest(bytes)[..] {
+            return Err(check_failed());
+        }
+        channel.write_bytes(&seed)?;
+        channel.flush()?;
+    } else {
+        let mut commitment = [0u8; 32];
+        channel.read_bytes(&mut commitment)?;
+        channel.write_bytes(&Sha256::digest(bytes))?;
+        channel.flush()?;
+        let mut seed = [0u8; 32];
+        channel.read_bytes(&mut seed)?;
+        let mut commitment_ = ShaCommitment::new(seed);
+        commitment_.input(bytes);
+        if !ShaCommitment::check(&commitment, &commitment_.finish()) {
+            return Err(check_failed());
+        }
+    }
+    Ok(())
+}
+
+// Open the shared values `xs`, with the first party sending its shares first.
+fn open<R: Ring, C: AbstractChannel>(
+    channel: &mut C,
+    first: bool,
+    xs: &[R],
+) -> Result<Vec<R>, Error> {
+    let write = |channel: &mut C| -> Result<(), Error> {
+        for x in xs.iter() {
+            x.write(channel)?;
+        }
+        channel.flush()?;
+        Ok(())
+    };
+    if first {
+        write(channel)?;
+    }
+    let mut out = Vec::with_capacity(xs.len());
+    for x in xs.iter() {
+        out.push(*x + R::read(channel)?);
+    }
+    if !first {
+        write(channel)?;
+    }
+    Ok(out)
+}
+
+// Check the first half of `triples` by sacrificing the second half, returning
+// the first half. For each triple `(a, b, c)` with check triple `(x, y, z)`
+// and random key `r`, the parties open `ρ = r · a - x` and `σ = b - y`, and
+// check that `r · c - z - ρ · b - σ · x = 0`.
+fn sacrifice<R: Ring, C: AbstractChannel, RNG: CryptoRng + Rng>(
+    channel: &mut C,
+    first: bool,
+    mut triples: Vec<Triple<R>>,
+    rng: &mut RNG,
+) -> Result<Vec<Triple<R>>, Error> {
+    let n = triples.len() / 2;
+    let checks = triples.split_off(n);
+    let mut rng_ = cointoss_rng(channel, first, rng)?;
+    let rs = (0..n).map(|_| R::random(&mut rng_)).collect::<Vec<R>>();
+    let mut rhos_sigmas = Vec::with_capacity(2 * n);
+    for ((t, t_), r) in triples.iter().zip(checks.iter()).zip(rs.iter()) {
+        rhos_sigmas.push(*r * t.a - t_.a);
+        rhos_sigmas.push(t.b - t_.b);
+    }
+    let rhos_sigmas = open(channel, first, &rhos_sigmas)?;
+    // The second party negates its shares, so that both parties should end
+    // up with the same values.
+    let mut es = Channel::new(std::io::empty(), Vec::new());
+    for (((t, t_), r), rs) in triples
+        .iter()
+        .zip(checks.iter())
+        .zip(rs.iter())
+        .zip(rhos_sigmas.chunks(2))
+    {
+        let e = *r * t.c - t_.c - rs[0] * t.b - rs[1] * t_.a;
+        let e = if first { e } else { -e };
+        e.write(&mut es)?;
+    }
+    let es = es.writer();
+    let es = es.borrow();
+    compare(channel, first, &es, rng)?;
+    Ok(triples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufReader, BufWriter},
+        os::unix::net::UnixStream,
+    };
+
+    // Share `n` triples over `R` locally, adding `error` to the first `c`.
+    fn share<R: Ring>(n: usize, error: R) -> (Vec<Triple<R>>, Vec<Triple<R>>) {
+        let mut rng = AesRng::new();
+        let mut ts0 = Vec::with_capacity(n);
+        let mut ts1 = Vec::with_capacity(n);
+        for i in 0..n {
+            let (a, b) = (R::random(&mut rng), R::random(&mut rng));
+            let (a0, b0, c0) = (
+                R::random(&mut rng),
+                R::random(&mut rng),
+                R::random(&mut rng),
+            );
+            let c = if i == 0 { a * b + error } else { a * b };
+            ts0.push(Triple {
+                a: a0,
+                b: b0,
+                c: c0,
+            });
+            ts1.push(Triple {
+                a: a - a0,
+                b: b - b0,
+                c: c - c0,
+            });
+        }
+        (ts0, ts1)
+    }
+
+    fn _test_sacrifice<R: Ring + 'static>(n: usize, error: R) -> bool {
+        let (ts0, ts1) = share(n, error);
+        let ts0_ = ts0.clone();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            sacrifice(&mut channel, true, ts0_, &mut rng).is_ok()
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let result = sacrifice(&mut channel, false, ts1.clone(), &mut rng);
+        let result_ = handle.join().unwrap();
+        assert_eq!(result.is_ok(), result_);
+        if let Ok(ts1_) = result {
+            assert_eq!(&ts1_[..], &ts1[..n / 2]);
+        }
+        result_
+    }
+
+    #[test]
+    fn test_sacrifice() {
+        assert!(_test_sacrifice::<Fp>(0, Fp::default()));
+        assert!(_test_sacrifice::<Fp>(200, Fp::default()));
+        assert!(!_test_sacrifice::<Fp>(200, Fp::new(1)));
+        assert!(_test_sacrifice::<Z2k<u64>>(200, Z2k(0)));
+        assert!(!_test_sacrifice::<Z2k<u64>>(200, Z2k(1)));
+    }
+}
@@ -0,0 +1,457 @@
+// -*- mode: rust; -*-
+//
+// This file is part of ocelot.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Bit-matrix transposition.
+//!
+//! A matrix with `nrows` rows and `ncols` columns is stored row-by-row, with
+//! each row padded to a whole number of bytes, and with column `j` of a row
+//! stored in bit `j % 8` of byte `j / 8`. The output uses the same layout.
+//!
+//! The bulk of the matrix is handled by an SSE2 or AVX2 kernel, chosen at
+//! runtime, which transposes blocks of 16 rows by 128 (resp. 256) columns. The
+//! leftover rows and columns are handled by a portable kernel that works on
+//! 8×8 blocks, falling back to single bits along the edges.
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+// The number of bytes taken up by a row of `n` bits.
+#[inline(always)]
+fn nbytes(n: usize) -> usize {
+    n.div_ceil(8)
+}
+
+/// Transpose the `nrows × ncols` bit-matrix `m`.
+#[inline]
+pub fn transpose(m: &[u8], nrows: usize, ncols: usize) -> Vec<u8> {
+    assert_eq!(m.len(), nrows * nbytes(ncols));
+    #[cfg(feature = "parallel")]
+    {
+        if ncols >= 2 * TRANSPOSE_CHUNK {
+            return transpose_by_columns(m, nrows, ncols);
+        }
+        if nrows >= 2 * TRANSPOSE_CHUNK {
+            return transpose_by_rows(m, nrows, ncols);
+        }
+    }
+    transpose_serial(m, nrows, ncols)
+}
+
+#[inline]
+fn transpose_serial(m: &[u8], nrows: usize, ncols: usize) -> Vec<u8> {
+    let mut out = vec![0u8; ncols * nbytes(nrows)];
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            let (r, c) = unsafe { transpose_avx2(&mut out, m, nrows, ncols) };
+            transpose_edges(&mut out, m, nrows, ncols, r, c);
+            return out;
+        }
+        if is_x86_feature_detected!("sse2") {
+            let (r, c) = unsafe { transpose_sse2(&mut out, m, nrows, ncols) };
+            transpose_edges(&mut out, m, nrows, ncols, r, c);
+            return out;
+        }
+    }
+    transpose_portable(&mut out, m, nrows, ncols, 0..nrows, 0..ncols);
+    out
+}
+
+// Transpose everything outside of the `nrows_ × ncols_` upper-left block, which
+// has already been handled by one of the SIMD kernels.
+#[inline]
+fn transpose_edges(
+    out: &mut [u8],
+    m: &[u8],
+    nrows: usize,
+    ncols: usize,
+    nrows_: usize,
+    ncols_: usize,
+) {
+    transpose_portable(out, m, nrows, ncols, nrows_..nrows, 0..ncols);
+    transpose_portable(out, m, nrows, ncols, 0..nrows_, ncols_..ncols);
+}
+
+// Transpose the bits of `m` lying in `rows × cols` into `out`, which is assumed
+// to be zero in the corresponding positions. Both `rows.start` and
+// `cols.start` must be multiples of eight.
+fn transpose_portable(
+    out: &mut [u8],
+    m: &[u8],
+    nrows: usize,
+    ncols: usize,
+    rows: std::ops::Range<usize>,
+    cols: std::ops::Range<usize>,
+) {
+    debug_assert!(rows.start.is_multiple_of(8) && cols.start.is_multiple_of(8));
+    let (istride, ostride) = (nbytes(ncols), nbytes(nrows));
+    let mut r = rows.start;
+    while r < rows.end {
+        let height = std::cmp::min(8, rows.end - r);
+        let mut c = cols.start;
+        while c < cols.end {
+            let width = std::cmp::min(8, cols.end - c);
+            if height == 8 && width == 8 {
+                // Load the 8×8 block with row `k` in byte `k`, transpose it,
+                // and store column `k` from byte `k`.
+                let mut x = 0u64;
+                for k in 0..8 {
+                    x |= u64::from(m[(r + k) * istride + c / 8]) << (8 * k);
+                }
+                let x = transpose8x8(x);
+                for k in 0..8 {
+                    out[(c + k) * ostride + r / 8] = (x >> (8 * k)) as u8;
+                }
+            } else {
+                for i in r..r + height {
+                    let byte = m[i * istride + c / 8];
+                    for j in 0..width {
+                        let bit = (byte >> j) & 1;
+                        out[(c + j) * ostride + i / 8] |= bit << (i % 8);
+                    }
+                }
+            }
+            c += 8;
+        }
+        r += 8;
+    }
+}
+
+// Transpose the 8×8 bit-matrix stored in `x` (cf. Hacker's Delight, §7-3).
+#[inline(always)]
+fn transpose8x8(mut x: u64) -> u64 {
+    let t = (x ^ (x >> 7)) & 0x00AA_00AA_00AA_00AA;
+    x ^= t ^ (t << 7);
+    let t = (x ^ (x >> 14)) & 0x0000_CCCC_0000_CCCC;
+    x ^= t ^ (t << 14);
+    let t = (x ^ (x >> 28)) & 0x0000_0000_F0F0_F0F0;
+    x ^ t ^ (t << 28)
+}
+
+// Both SIMD kernels below work the same way: load 16 bytes from each of 16
+// rows, transpose that 16×16 byte-matrix with four rounds of unpack
+// instructions, and then extract each column of bits from the resulting
+// vectors with `movemask`, which collects the top bit of each byte. The AVX2
+// kernel runs two such byte-matrices side-by-side, one per 128-bit lane.
+//
+// Each kernel returns the number of rows and columns it handled.
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse2")]
+unsafe fn transpose_sse2(out: &mut [u8], m: &[u8], nrows: usize, ncols: usize) -> (usize, usize) {
+    let (istride, ostride) = (nbytes(ncols), nbytes(nrows));
+    let nrows_ = nrows - nrows % 16;
+    let ncols_ = ncols - ncols % 128;
+    let inp = m.as_ptr();
+    let outp = out.as_mut_ptr();
+    let mut v = [_mm_setzero_si128(); 16];
+    for r in (0..nrows_).step_by(16) {
+        for c in (0..ncols_).step_by(128) {
+            for (k, v) in v.iter_mut().enumerate() {
+                *v = _mm_loadu_si128(inp.add((r + k) * istride + c / 8) as *const __m128i);
+            }
+            let v = transpose16x16_sse2(&v);
+            for (k, v) in v.iter().enumerate() {
+                let mut v = *v;
+                for i in (0..8).rev() {
+                    let h = _mm_movemask_epi8(v) as u16;
+                    let o = outp.add((c + 8 * k + i) * ostride + r / 8);
+                    std::ptr::copy_nonoverlapping(h.to_le_bytes().as_ptr(), o, 2);
+                    v = _mm_slli_epi64(v, 1);
+                }
+            }
+        }
+    }
+    (nrows_, ncols_)
+}
+
+// Transpose the 16×16 byte-matrix with rows `x`. The result is such that byte
+// `i` of vector `j` is byte `j` of `x[i]`.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse2")]
+unsafe fn transpose16x16_sse2(x: &[__m128i; 16]) -> [__m128i; 16] {
+    let mut a = [_mm_setzero_si128(); 16];
+    let mut b = [_mm_setzero_si128(); 16];
+    // Bytes → pairs of rows (`a[8h + i]` holds rows `2i, 2i+1`).
+    for i in 0..8 {
+        a[i] = _mm_unpacklo_epi8(x[2 * i], x[2 * i + 1]);
+        a[8 + i] = _mm_unpackhi_epi8(x[2 * i], x[2 * i + 1]);
+    }
+    // Pairs → quads of rows (`b[4g + i]` holds rows `4i..4i+3`).
+    for h in 0..2 {
+        for i in 0..4 {
+            b[8 * h + i] = _mm_unpacklo_epi16(a[8 * h + 2 * i], a[8 * h + 2 * i + 1]);
+            b[8 * h + 4 + i] = _mm_unpackhi_epi16(a[8 * h + 2 * i], a[8 * h + 2 * i + 1]);
+        }
+    }
+    // Quads → octets of rows (`a[2p + i]` holds rows `8i..8i+7`).
+    for g in 0..4 {
+        for i in 0..2 {
+            a[4 * g + i] = _mm_unpacklo_epi32(b[4 * g + 2 * i], b[4 * g + 2 * i + 1]);
+            a[4 * g + 2 + i] = _mm_unpackhi_epi32(b[4 * g + 2 * i], b[4 * g + 2 * i + 1]);
+        }
+    }
+    // Octets → all sixteen rows.
+    for p in 0..8 {
+        b[2 * p] = _mm_unpacklo_epi64(a[2 * p], a[2 * p + 1]);
+        b[2 * p + 1] = _mm_unpackhi_epi64(a[2 * p], a[2 * p + 1]);
+    }
+    b
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn transpose_avx2(out: &mut [u8], m: &[u8], nrows: usize, ncols: usize) -> (usize, usize) {
+    let (istride, ostride) = (nbytes(ncols), nbytes(nrows));
+    let nrows_ = nrows - nrows % 16;
+    let ncols_ = ncols - ncols % 256;
+    let inp = m.as_ptr();
+    let outp = out.as_mut_ptr();
+    let mut v = [_mm256_setzero_si256(); 16];
+    for r in (0..nrows_).step_by(16) {
+        for c in (0..ncols_).step_by(256) {
+            for (k, v) in v.iter_mut().enumerate() {
+                *v = _mm256_loadu_si256(inp.add((r + k) * istride + c / 8) as *const __m256i);
+            }
+            let v = transpose16x16_avx2(&v);
+            for (k, v) in v.iter().enumerate() {
+                let mut v = *v;
+                for i in (0..8).rev() {
+                    let h = (_mm256_movemask_epi8(v) as u32).to_le_bytes();
+                    // The low lane holds bytes `0..16` of the rows, and the
+                    // high lane holds bytes `16..32`.
+                    let o = outp.add((c + 8 * k + i) * ostride + r / 8);
+                    std::ptr::copy_nonoverlapping(h.as_ptr(), o, 2);
+                    let o = outp.add((c + 128 + 8 * k + i) * ostride + r / 8);
+                    std::ptr::copy_nonoverlapping(h.as_ptr().add(2), o, 2);
+                    v = _mm256_slli_epi64(v, 1);
+                }
+            }
+        }
+    }
+    (nrows_, ncols_)
+}
+
+// The same as `transpose16x16_sse2`, but on each 128-bit lane independently.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn transpose16x16_avx2(x: &[__m256i; 16]) -> [__m256i; 16] {
+    let mut a = [_mm256_setzero_si256(); 16];
+    let mut b = [_mm256_setzero_si256(); 16];
+    for i in 0..8 {
+        a[i] = _mm256_unpacklo_epi8(x[2 * i], x[2 * i + 1]);
+        a[8 + i] = _mm256_unpackhi_epi8(x[2 * i], x[2 * i + 1]);
+    }
+    for h in 0..2 {
+        for i in 0..4 {
+            b[8 * h + i] = _mm256_unpacklo_epi16(a[8 * h + 2 * i], a[8 * h + 2 * i + 1]);
+            b[8 * h + 4 + i] = _mm256_unpackhi_epi16(a[8 * h + 2 * i], a[8 * h + 2 * i + 1]);
+        }
+    }
+    for g in 0..4 {
+        for i in 0..2 {
+            a[4 * g + i] = _mm256_unpacklo_epi32(b[4 * g + 2 * i], b[4 * g + 2 * i + 1]);
+            a[4 * g + 2 + i] = _mm256_unpackhi_epi32(b[4 * g + 2 * i], b[4 * g + 2 * i + 1]);
+        }
+    }
+    for p in 0..8 {
+        b[2 * p] = _mm256_unpacklo_epi64(a[2 * p], a[2 * p + 1]);
+        b[2 * p + 1] = _mm256_unpackhi_epi64(a[2 * p], a[2 * p + 1]);
+    }
+    b
+}
+
+// The number of rows or columns handled by a single task when transposing in
+// parallel. Must be a multiple of 256, so that every task but the last runs
+// entirely in the SIMD kernels.
+#[cfg(feature = "parallel")]
+const TRANSPOSE_CHUNK: usize = 1 << 12;
+
+// Split `[0..n)` into consecutive chunks of `size` elements.
+#[cfg(feature = "parallel")]
+fn chunk_bounds(n: usize, size: usize) -> Vec<(usize, usize)> {
+    (0..n)
+        .step_by(size)
+        .map(|start| (start, std::cmp::min(start + size, n)))
+        .collect()
+}
+
+// Transpose by splitting the columns of `m` into chunks. Each chunk of columns
+// becomes a contiguous chunk of rows in the output, so the tasks write to
+// disjoint parts of the output directly.
+#[cfg(feature = "parallel")]
+fn transpose_by_columns(m: &[u8], nrows: usize, ncols: usize) -> Vec<u8> {
+    let mut m_ = vec![0u8; ncols * nbytes(nrows)];
+    let bounds = chunk_bounds(ncols, TRANSPOSE_CHUNK);
+    let mut outs = Vec::with_capacity(bounds.len());
+    let mut rest = m_.as_mut_slice();
+    for (start, end) in bounds.iter() {
+        let (out, rest_) = rest.split_at_mut((end - start) * nbytes(nrows));
+        outs.push(out);
+        rest = rest_;
+    }
+    bounds
+        .into_par_iter()
+        .zip(outs.into_par_iter())
+        .for_each(|((start, end), out)| {
+            let width = end - start;
+            let mut sub = Vec::with_capacity(nrows * nbytes(width));
+            for row in m.chunks(nbytes(ncols)) {
+                sub.extend_from_slice(&row[start / 8..start / 8 + nbytes(width)]);
+            }
+            if width % 8 != 0 {
+                // Clear the bits of the last byte belonging to other columns.
+                let mask = (1u8 << (width % 8)) - 1;
+                for row in sub.chunks_mut(nbytes(width)) {
+                    row[nbytes(width) - 1] &= mask;
+                }
+            }
+            out.copy_from_slice(&transpose_serial(&sub, nrows, width));
+        });
+    m_
+}
+
+// Transpose by splitting the rows of `m` into chunks. Each chunk of rows
+// becomes a chunk of columns in the output, which we scatter into place once
+// all the tasks are done.
+#[cfg(feature = "parallel")]
+fn transpose_by_rows(m: &[u8], nrows: usize, ncols: usize) -> Vec<u8> {
+    let mut m_ = vec![0u8; ncols * nbytes(nrows)];
+    let bounds = chunk_bounds(nrows, TRANSPOSE_CHUNK);
+    let subs = bounds
+        .par_iter()
+        .map(|(start, end)| {
+            let rows = &m[start * nbytes(ncols)..end * nbytes(ncols)];
+            transpose_serial(rows, end - start, ncols)
+        })
+        .collect::<Vec<Vec<u8>>>();
+    for ((start, end), sub) in bounds.into_iter().zip(subs.into_iter()) {
+        let height = nbytes(end - start);
+        for (row, sub_row) in m_.chunks_mut(nbytes(nrows)).zip(sub.chunks(height)) {
+            row[start / 8..start / 8 + height].copy_from_slice(sub_row);
+        }
+    }
+    m_
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rand_matrix(nrows: usize, ncols: usize) -> Vec<u8> {
+        let mut m = (0..nrows * nbytes(ncols))
+            .map(|_| rand::random::<u8>())
+            .collect::<Vec<u8>>();
+        // Zero out the padding bits at the end of each row.
+        if !ncols.is_multiple_of(8) {
+            let mask = (1u8 << (ncols % 8)) - 1;
+            for row in m.chunks_mut(nbytes(ncols)) {
+                row[nbytes(ncols) - 1] &= mask;
+            }
+        }
+        m
+    }
+
+    fn naive_transpose(m: &[u8], nrows: usize, ncols: usize) -> Vec<u8> {
+        let mut out = vec![0u8; ncols * nbytes(nrows)];
+        for i in 0..nrows {
+            for j in 0..ncols {
+                let bit = (m[i * nbytes(ncols) + j / 8] >> (j % 8)) & 1;
+                out[j * nbytes(nrows) + i / 8] |= bit << (i % 8);
+            }
+        }
+        out
+    }
+
+    fn _transpose(nrows: usize, ncols: usize) {
+        let m = rand_matrix(nrows, ncols);
+        let m_ = transpose(&m, nrows, ncols);
+        assert_eq!(m_, naive_transpose(&m, nrows, ncols));
+        let m_ = transpose(&m_, ncols, nrows);
+        assert_eq!(m, m_);
+    }
+
+    #[test]
+    fn test_transpose() {
+        _transpose(16, 16);
+        _transpose(24, 16);
+        _transpose(32, 16);
+        _transpose(40, 16);
+        _transpose(128, 16);
+        _transpose(128, 24);
+        _transpose(128, 128);
+        _transpose(128, 1 << 16);
+        _transpose(128, 1 << 18);
+        _transpose(32, 32);
+        _transpose(64, 32);
+        _transpose(128, (1 << 14) + 24);
+        _transpose((1 << 14) + 8, 512);
+    }
+
+    #[test]
+    fn test_transpose_odd_dimensions() {
+        _transpose(1, 1);
+        _transpose(3, 5);
+        _transpose(8, 9);
+        _transpose(17, 129);
+        _transpose(130, 257);
+        _transpose(131, 1000);
+        _transpose(1000, 131);
+        _transpose(128, (1 << 14) + 3);
+        _transpose((1 << 14) + 5, 512);
+    }
+
+    #[test]
+    fn test_transpose_portable() {
+        for &(nrows, ncols) in [(16, 128), (24, 40), (33, 300), (256, 1024)].iter() {
+            let m = rand_matrix(nrows, ncols);
+            let mut out = vec![0u8; ncols * nbytes(nrows)];
+            transpose_portable(&mut out, &m, nrows, ncols, 0..nrows, 0..ncols);
+            assert_eq!(out, naive_transpose(&m, nrows, ncols));
+        }
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    #[test]
+    fn test_transpose_simd() {
+        for &(nrows, ncols) in [(16, 128), (24, 300), (48, 1000), (128, 4096)].iter() {
+            let m = rand_matrix(nrows, ncols);
+            let expected = naive_transpose(&m, nrows, ncols);
+            if is_x86_feature_detected!("sse2") {
+                let mut out = vec![0u8; ncols * nbytes(nrows)];
+                let (r, c) = unsafe { transpose_sse2(&mut out, &m, nrows, ncols) };
+                transpose_edges(&mut out, &m, nrows, ncols, r, c);
+                assert_eq!(out, expected);
+            }
+            if is_x86_feature_detected!("avx2") {
+                let mut out = vec![0u8; ncols * nbytes(nrows)];
+                let (r, c) = unsafe { transpose_avx2(&mut out, &m, nrows, ncols) };
+                transpose_edges(&mut out, &m, nrows, ncols, r, c);
+                assert_eq!(out, expected);
+            }
+        }
+    }
+}
+
+#[cfg(all(feature = "nightly", test))]
+mod benchmarks {
+    extern crate test;
+    use super::*;
+    use test::Bencher;
+
+    #[bench]
+    fn bench_transpose(b: &mut Bencher) {
+        let (nrows, ncols) = (128, 1 << 18);
+        let m = (0..nrows * ncols / 8)
+            .map(|_| rand::random::<u8>())
+            .collect::<Vec<u8>>();
+        b.iter(|| transpose(&m, nrows, ncols));
+    }
+}
@@ -0,0 +1,236 @@
+// -*- mode: rust; -*-
+//
+// This file is part of ocelot.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Finite fields for VOLE.
+
+use crate::vole::Ring;
+use scuttlebutt::{AbstractChannel, Block};
+use std::ops::{Add, AddAssign, Mul, Neg, Sub};
+
+/// Marker trait for rings that are fields.
+pub trait Field: Ring {}
+
+/// The prime field of integers modulo the Mersenne prime `2^61 - 1`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Fp(u64);
+
+impl Fp {
+    /// The modulus `2^61 - 1`.
+    pub const MODULUS: u64 = (1 << 61) - 1;
+
+    /// Creates the element `x mod p`.
+    #[inline]
+    pub fn new(x: u64) -> Self {
+        Fp(Self::reduce(u128::from(x)))
+    }
+
+    /// Returns the canonical representative of the element, in `[0, p)`.
+    #[inline]
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+
+    // Reduce `x < 2^122` modulo `2^61 - 1`, using `2^61 ≡ 1`.
+    #[inline]
+    fn reduce(x: u128) -> u64 {
+        let x = (x & u128::from(Self::MODULUS)) + (x >> 61);
+        let x = (x & u128::from(Self::MODULUS)) + (x >> 61);
+        let x = x as u64;
+        if x >= Self::MODULUS {
+            x - Self::MODULUS
+        } else {
+            x
+        }
+    }
+}
+
+impl Add for Fp {
+    type Output = Self;
+    #[inline]
+    fn add(self, rhs: Self) -> Self {
+        let x = self.0 + rhs.0;
+        Fp(if x >= Self::MODULUS {
+            x - Self::MODULUS
+        } else {
+            x
+        })
+    }
+}
+
+impl AddAssign for Fp {
+    #[inline]
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl Neg for Fp {
+    type Output = Self;
+    #[inline]
+    fn neg(self) -> Self {
+        Fp(if self.0 == 0 {
+            0
+        } else {
+            Self::MODULUS - self.0
+        })
+    }
+}
+
+impl Sub for Fp {
+    type Output = Self;
+    #[inline]
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn sub(self, rhs: Self) -> Self {
+        self + (-rhs)
+    }
+}
+
+impl Mul for Fp {
+    type Output = Self;
+    #[inline]
+    fn mul(self, rhs: Self) -> Self {
+        Fp(Self::reduce(u128::from(self.0) * u128::from(rhs.0)))
+    }
+}
+
+impl Ring for Fp {
+    const BITS: usize = 61;
+
+    #[inline]
+    fn one() -> Self {
+        Fp(1)
+    }
+    #[inline]
+    fn basis(i: usize) -> Self {
+        Fp(1 << i)
+    }
+    #[inline]
+    fn bit(&self, i: usize) -> bool {
+        (self.0 >> i) & 1 == 1
+    }
+    #[inline]
+    fn from_block(b: Block) -> Self {
+        // The bias from reducing 128 bits is about `2^-67`.
+        Fp((u128::from(b) % u128::from(Self::MODULUS)) as u64)
+    }
+    #[inline]
+    fn write<C: AbstractChannel>(&self, channel: &mut C) -> std::io::Result<()> {
+        channel.write_u64(self.0)
+    }
+    #[inline]
+    fn read<C: AbstractChannel>(channel: &mut C) -> std::io::Result<Self> {
+        let x = channel.read_u64()?;
+        if x >= Self::MODULUS {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "field element out of range",
+            ));
+        }
+        Ok(Fp(x))
+    }
+}
+
+impl Field for Fp {}
+
+/// The binary field `GF(2^128)`, with the reduction polynomial `x^128 + x^7 +
+/// x^2 + x + 1`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Gf128(pub Block);
+
+impl Add for Gf128 {
+    type Output = Self;
+    #[inline]
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn add(self, rhs: Self) -> Self {
+        Gf128(self.0 ^ rhs.0)
+    }
+}
+
+impl AddAssign for Gf128 {
+    #[inline]
+    #[allow(clippy::suspicious_op_assign_impl)]
+    fn add_assign(&mut self, rhs: Self) {
+        self.0 ^= rhs.0;
+    }
+}
+
+impl Neg for Gf128 {
+    type Output = Self;
+    #[inline]
+    fn neg(self) -> Self {
+        self
+    }
+}
+
+impl Sub for Gf128 {
+    type Output = Self;
+    #[inline]
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn sub(self, rhs: Self) -> Self {
+        Gf128(self.0 ^ rhs.0)
+    }
+}
+
+impl Mul for Gf128 {
+    type Output = Self;
+    #[inline]
+    fn mul(self, rhs: Self) -> Self {
+        Gf128(self.0.gfmul(rhs.0))
+    }
+}
+
+impl Ring for Gf128 {
+    const BITS: usize = 128;
+
+    #[inline]
+    fn one() -> Self {
+        Gf128(Block::from(1u128))
+    }
+    #[inline]
+    fn basis(i: usize) -> Self {
+        Gf128(Block::from(1u128 << i))
+    }
+    #[inline]
+    fn bit(&self, i: usize) -> bool {
+        (u128::from(self.0) >> i) & 1 == 1
+    }
+    #[inline]
+    fn from_block(b: Block) -> Self {
+        Gf128(b)
+    }
+    #[inline]
+    fn write<C: AbstractChannel>(&self, channel: &mut C) -> std::io::Result<()> {
+        channel.write_block(&self.0)
+    }
+    #[inline]
+    fn read<C: AbstractChannel>(channel: &mut C) -> std::io::Result<Self> {
+        Ok(Gf128(channel.read_block()?))
+    }
+}
+
+impl Field for Gf128 {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vole::ring::tests::_test_ring;
+
+    #[test]
+    fn test_fp() {
+        _test_ring::<Fp>();
+        let p = Fp::MODULUS;
+        assert_eq!(Fp::new(p), Fp::default());
+        assert_eq!(Fp::new(p - 1) * Fp::new(p - 1), Fp::one());
+        let (a, b) = (rand::random::<u64>() % p, rand::random::<u64>() % p);
+        let ab = (u128::from(a) * u128::from(b) % u128::from(p)) as u64;
+        assert_eq!((Fp::new(a) * Fp::new(b)).value(), ab);
+    }
+
+    #[test]
+    fn test_gf128() {
+        _test_ring::<Gf128>();
+    }
+}
@@ -0,0 +1,307 @@
+// -*- mode: rust; -*-
+//
+// This file is part of ocelot.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Implementation of Gilboa's semi-honest oblivious linear evaluation (OLE)
+//! and VOLE protocols (cf. <https://doi.org/10.1007/3-540-48405-1_8>), built
+//! from correlated OT.
+//!
+//! In an OLE over a ring `R`, the sender inputs `x`, the receiver inputs `y`,
+//! and they end up with additive shares of `x · y`. Write `y = ∑_i y_i · g_i`
+//! in terms of its bits `y_i` and the basis `g_i` of the ring. For each bit
+//! `i`, the parties run one correlated OT with the receiver choosing `y_i`.
+//! Each OT yields a pair of blocks, which both parties map to ring elements
+//! `(t_0, t_1)`; the sender then sends `c = t_1 - t_0 - x · g_i`, which the
+//! receiver uses to turn `t_1` into `t_0 + x · g_i` when `y_i = 1`. Either
+//! way, the receiver ends up with `t_0 + y_i · x · g_i`, and summing over all
+//! bits gives it `T + x · y`, where `-T = -∑_i t_0` is the sender's share.
+//! Ring addition is not XOR, so the sender cannot use `x · g_i` itself as the
+//! OT offset. Instead it picks the offsets at random, which makes the block
+//! the receiver does not learn uniformly random from its point of view, so
+//! that `c` hides `x`.
+//!
+//! VOLE is OLE in which the receiver always inputs `y = Δ`: the sender's
+//! share is `w` and the receiver's share is `-v`, so that `w = u · Δ + v`.
+
+use crate::{
+    errors::Error,
+    ot::{CorrelatedReceiver, CorrelatedSender},
+    vole::{Field, Ring, VoleReceiver, VoleSender},
+};
+use rand::{CryptoRng, Rng};
+use scuttlebutt::{AbstractChannel, Block, SemiHonest};
+use std::marker::PhantomData;
+
+/// Gilboa OLE sender.
+pub struct OleSender<R: Ring, OT: CorrelatedSender<Msg = Block> + SemiHonest> {
+    _ring: PhantomData<R>,
+    ot: OT,
+}
+
+impl<R: Ring, OT: CorrelatedSender<Msg = Block> + SemiHonest> OleSender<R, OT> {
+    /// Runs any one-time initialization.
+    pub fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let ot = OT::init(channel, rng)?;
+        Ok(Self {
+            _ring: PhantomData::<R>,
+            ot,
+        })
+    }
+
+    /// Runs OLE on inputs `xs`, returning shares of `x_i · y_i`, where `y_i`
+    /// belongs to the receiver.
+    pub fn multiply<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        xs: &[R],
+        rng: &mut RNG,
+    ) -> Result<Vec<R>, Error> {
+        let deltas = (0..xs.len() * R::BITS)
+            .map(|_| rng.gen::<Block>())
+            .collect::<Vec<Block>>();
+        let ts = self.ot.send_correlated(channel, &deltas, rng)?;
+        let mut shares = Vec::with_capacity(xs.len());
+        for (x, ts) in xs.iter().zip(ts.chunks(R::BITS)) {
+            let mut share = R::default();
+            for (i, (t0, t1)) in ts.iter().enumerate() {
+                let t0 = R::from_block(*t0);
+                let t1 = R::from_block(*t1);
+                let c = t1 - t0 - *x * R::basis(i);
+                c.write(channel)?;
+                share += t0;
+            }
+            shares.push(-share);
+        }
+        channel.flush()?;
+        Ok(shares)
+    }
+}
+
+/// Gilboa OLE receiver.
+pub struct OleReceiver<R: Ring, OT: CorrelatedReceiver<Msg = Block> + SemiHonest> {
+    _ring: PhantomData<R>,
+    ot: OT,
+}
+
+impl<R: Ring, OT: CorrelatedReceiver<Msg = Block> + SemiHonest> OleReceiver<R, OT> {
+    /// Runs any one-time initialization.
+    pub fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let ot = OT::init(channel, rng)?;
+        Ok(Self {
+            _ring: PhantomData::<R>,
+            ot,
+        })
+    }
+
+    /// Runs OLE on inputs `ys`, returning shares of `x_i · y_i`, where `x_i`
+    /// belongs to the sender.
+    pub fn multiply<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        ys: &[R],
+        rng: &mut RNG,
+    ) -> Result<Vec<R>, Error> {
+        let choices = ys
+            .iter()
+            .flat_map(|y| (0..R::BITS).map(move |i| y.bit(i)))
+            .collect::<Vec<bool>>();
+        let ts = self.ot.receive_correlated(channel, &choices, rng)?;
+        let mut shares = Vec::with_capacity(ys.len());
+        for (ts, bs) in ts.chunks(R::BITS).zip(choices.chunks(R::BITS)) {
+            let mut share = R::default();
+            for (t, b) in ts.iter().zip(bs.iter()) {
+                let c = R::read(channel)?;
+                let t = R::from_block(*t);
+                share += if *b { t - c } else { t };
+            }
+            shares.push(share);
+        }
+        Ok(shares)
+    }
+}
+
+/// Gilboa VOLE sender.
+pub struct Sender<F: Field, OT: CorrelatedSender<Msg = Block> + SemiHonest> {
+    ole: OleSender<F, OT>,
+}
+
+impl<F: Field, OT: CorrelatedSender<Msg = Block> + SemiHonest> VoleSender for Sender<F, OT> {
+    type Field = F;
+
+    fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let ole = OleSender::init(channel, rng)?;
+        Ok(Self { ole })
+    }
+
+    fn send<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        us: &[F],
+        rng: &mut RNG,
+    ) -> Result<Vec<F>, Error> {
+        self.ole.multiply(channel, us, rng)
+    }
+}
+
+/// Gilboa VOLE receiver.
+pub struct Receiver<F: Field, OT: CorrelatedReceiver<Msg = Block> + SemiHonest> {
+    ole: OleReceiver<F, OT>,
+    delta: F,
+}
+
+impl<F: Field, OT: CorrelatedReceiver<Msg = Block> + SemiHonest> Receiver<F, OT> {
+    /// Runs the one-time initialization with `delta` as `Δ`.
+    pub fn init_with_delta<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        delta: F,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let ole = OleReceiver::init(channel, rng)?;
+        Ok(Self { ole, delta })
+    }
+}
+
+impl<F: Field, OT: CorrelatedReceiver<Msg = Block> + SemiHonest> VoleReceiver for Receiver<F, OT> {
+    type Field = F;
+
+    fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let delta = F::random(rng);
+        Self::init_with_delta(channel, delta, rng)
+    }
+
+    fn delta(&self) -> F {
+        self.delta
+    }
+
+    fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        m: usize,
+        rng: &mut RNG,
+    ) -> Result<Vec<F>, Error> {
+        let deltas = vec![self.delta; m];
+        let shares = self.ole.multiply(channel, &deltas, rng)?;
+        Ok(shares.into_iter().map(|share| -share).collect())
+    }
+}
+
+impl<R: Ring, OT: CorrelatedSender<Msg = Block> + SemiHonest> SemiHonest for OleSender<R, OT> {}
+impl<R: Ring, OT: CorrelatedReceiver<Msg = Block> + SemiHonest> SemiHonest for OleReceiver<R, OT> {}
+impl<F: Field, OT: CorrelatedSender<Msg = Block> + SemiHonest> SemiHonest for Sender<F, OT> {}
+impl<F: Field, OT: CorrelatedReceiver<Msg = Block> + SemiHonest> SemiHonest for Receiver<F, OT> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ot,
+        vole::{self, Fp, Gf128, Z2k},
+    };
+    use scuttlebutt::{AesRng, Channel};
+    use std::{
+        io::{BufReader, BufWriter},
+        os::unix::net::UnixStream,
+    };
+
+    fn _test_vole<
+        F: Field + 'static,
+        S: VoleSender<Field = F> + Send + 'static,
+        R: VoleReceiver<Field = F>,
+    >(
+        ms: &[usize],
+    ) {
+        let mut rng = AesRng::new();
+        let uss = ms
+            .iter()
+            .map(|m| (0..*m).map(|_| F::random(&mut rng)).collect::<Vec<F>>())
+            .collect::<Vec<Vec<F>>>();
+        let uss_ = uss.clone();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut vole = S::init(&mut channel, &mut rng).unwrap();
+            uss_.iter()
+                .map(|us| vole.send(&mut channel, us, &mut rng).unwrap())
+                .collect::<Vec<Vec<F>>>()
+        });
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut vole = R::init(&mut channel, &mut rng).unwrap();
+        let delta = vole.delta();
+        let vss = ms
+            .iter()
+            .map(|m| vole.receive(&mut channel, *m, &mut rng).unwrap())
+            .collect::<Vec<Vec<F>>>();
+        let wss = handle.join().unwrap();
+        for ((us, vs), ws) in uss.iter().zip(vss.iter()).zip(wss.iter()) {
+            assert_eq!(us.len(), vs.len());
+            assert_eq!(us.len(), ws.len());
+            for ((u, v), w) in us.iter().zip(vs.iter()).zip(ws.iter()) {
+                assert_eq!(*w, *u * delta + *v);
+            }
+        }
+    }
+
+    fn _test_ole<R: Ring + 'static>(n: usize) {
+        let mut rng = AesRng::new();
+        let xs = (0..n).map(|_| R::random(&mut rng)).collect::<Vec<R>>();
+        let ys = (0..n).map(|_| R::random(&mut rng)).collect::<Vec<R>>();
+        let xs_ = xs.clone();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut ole = OleSender::<R, ot::AlszSender>::init(&mut channel, &mut rng).unwrap();
+            ole.multiply(&mut channel, &xs_, &mut rng).unwrap()
+        });
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut ole = OleReceiver::<R, ot::AlszReceiver>::init(&mut channel, &mut rng).unwrap();
+        let shares1 = ole.multiply(&mut channel, &ys, &mut rng).unwrap();
+        let shares0 = handle.join().unwrap();
+        assert_eq!(shares0.len(), n);
+        assert_eq!(shares1.len(), n);
+        for (((x, y), s0), s1) in xs.iter().zip(ys.iter()).zip(shares0).zip(shares1) {
+            assert_eq!(*x * *y, s0 + s1);
+        }
+    }
+
+    #[test]
+    fn test_ole() {
+        _test_ole::<Z2k<u32>>(10);
+        _test_ole::<Z2k<u64>>(0);
+        _test_ole::<Z2k<u64>>(100);
+        _test_ole::<Fp>(100);
+    }
+
+    #[test]
+    fn test_vole_fp() {
+        _test_vole::<Fp, vole::GilboaFpSender, vole::GilboaFpReceiver>(&[1, 10, 0, 100]);
+    }
+
+    #[test]
+    fn test_vole_gf128() {
+        _test_vole::<Gf128, vole::GilboaGf128Sender, vole::GilboaGf128Receiver>(&[1, 10, 0, 100]);
+    }
+}
@@ -0,0 +1,85 @@
+// -*- mode: rust; -*-
+//
+// This file is part of ocelot.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Vector oblivious linear evaluation (VOLE) traits + instantiations.
+//!
+//! In VOLE, the receiver holds a field element `Δ` and the sender holds a
+//! vector `u`. At the end of the protocol the sender learns `w` and the
+//! receiver learns `v` such that `w = u · Δ + v`, with `v` uniformly random.
+//!
+//! This module provides the following VOLE protocols:
+//!
+//! * `gilboa`: Gilboa's semi-honest VOLE, built from correlated OT. The module
+//!   also exposes the underlying OLE protocol over any `Ring`.
+//!
+//! It supports the fields in `field`: a prime field and `GF(2^128)`. The
+//! more general rings in `ring`, which include `Z_{2^k}`, are used for OLE
+//! (see `triple`).
+
+pub mod field;
+pub mod gilboa;
+pub mod ring;
+
+use crate::{errors::Error, ot};
+use rand::{CryptoRng, Rng};
+use scuttlebutt::AbstractChannel;
+
+pub use field::{Field, Fp, Gf128};
+pub use ring::{Ring, Z2k};
+
+/// Gilboa VOLE sender over `Fp`, using ALSZ correlated OT.
+pub type GilboaFpSender = gilboa::Sender<Fp, ot::AlszSender>;
+/// Gilboa VOLE receiver over `Fp`, using ALSZ correlated OT.
+pub type GilboaFpReceiver = gilboa::Receiver<Fp, ot::AlszReceiver>;
+/// Gilboa VOLE sender over `GF(2^128)`, using ALSZ correlated OT.
+pub type GilboaGf128Sender = gilboa::Sender<Gf128, ot::AlszSender>;
+/// Gilboa VOLE receiver over `GF(2^128)`, using ALSZ correlated OT.
+pub type GilboaGf128Receiver = gilboa::Receiver<Gf128, ot::AlszReceiver>;
+
+/// Trait for a VOLE sender.
+pub trait VoleSender
+where
+    Self: Sized,
+{
+    /// The field the VOLE is over.
+    type Field: Field;
+    /// Runs any one-time initialization.
+    fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Self, Error>;
+    /// Runs VOLE on inputs `us`, returning the `w`s such that `w_i = u_i · Δ +
+    /// v_i`, where `Δ` and `v_i` belong to the receiver.
+    fn send<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        us: &[Self::Field],
+        rng: &mut RNG,
+    ) -> Result<Vec<Self::Field>, Error>;
+}
+
+/// Trait for a VOLE receiver.
+pub trait VoleReceiver
+where
+    Self: Sized,
+{
+    /// The field the VOLE is over.
+    type Field: Field;
+    /// Runs any one-time initialization, choosing `Δ` at random.
+    fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Self, Error>;
+    /// Returns `Δ`, which is fixed for the lifetime of the receiver.
+    fn delta(&self) -> Self::Field;
+    /// Runs `m` VOLE instances, returning the `v`s.
+    fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        m: usize,
+        rng: &mut RNG,
+    ) -> Result<Vec<Self::Field>, Error>;
+}
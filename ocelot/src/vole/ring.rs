@@ -0,0 +1,171 @@
+// -*- mode: rust; -*-
+//
+// This file is part of ocelot.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Finite rings for VOLE and OLE.
+
+use rand::Rng;
+use scuttlebutt::{AbstractChannel, Block};
+use std::ops::{Add, AddAssign, Mul, Neg, Sub};
+
+/// Trait for the finite (commutative) rings that VOLE and OLE can be run
+/// over.
+///
+/// Every element `x` must be expressible as `∑_i bit(x, i) · basis(i)` for `i
+/// < BITS`, which is what lets Gilboa's protocol work bit by bit.
+pub trait Ring:
+    Copy
+    + Clone
+    + Default
+    + PartialEq
+    + Eq
+    + std::fmt::Debug
+    + Send
+    + Sync
+    + Add<Output = Self>
+    + AddAssign
+    + Sub<Output = Self>
+    + Neg<Output = Self>
+    + Mul<Output = Self>
+{
+    /// The number of bits needed to represent an element.
+    const BITS: usize;
+    /// The multiplicative identity.
+    fn one() -> Self;
+    /// The `i`th basis element.
+    fn basis(i: usize) -> Self;
+    /// The `i`th bit of the element.
+    fn bit(&self, i: usize) -> bool;
+    /// Maps a uniformly random block to a (statistically close to) uniformly
+    /// random element.
+    fn from_block(b: Block) -> Self;
+    /// Samples a uniformly random element.
+    #[inline]
+    fn random<RNG: Rng>(rng: &mut RNG) -> Self {
+        Self::from_block(rng.gen())
+    }
+    /// Writes the element to `channel`.
+    fn write<C: AbstractChannel>(&self, channel: &mut C) -> std::io::Result<()>;
+    /// Reads an element from `channel`.
+    fn read<C: AbstractChannel>(channel: &mut C) -> std::io::Result<Self>;
+}
+
+/// The ring of integers modulo `2^k`, where `k` is the width of the unsigned
+/// integer type `T`: `Z2k<u32>`, `Z2k<u64>` and `Z2k<u128>` are supported.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Z2k<T>(pub T);
+
+macro_rules! impl_z2k {
+    ($t:ty) => {
+        impl Add for Z2k<$t> {
+            type Output = Self;
+            #[inline]
+            fn add(self, rhs: Self) -> Self {
+                Z2k(self.0.wrapping_add(rhs.0))
+            }
+        }
+
+        impl AddAssign for Z2k<$t> {
+            #[inline]
+            fn add_assign(&mut self, rhs: Self) {
+                self.0 = self.0.wrapping_add(rhs.0);
+            }
+        }
+
+        impl Neg for Z2k<$t> {
+            type Output = Self;
+            #[inline]
+            fn neg(self) -> Self {
+                Z2k(self.0.wrapping_neg())
+            }
+        }
+
+        impl Sub for Z2k<$t> {
+            type Output = Self;
+            #[inline]
+            fn sub(self, rhs: Self) -> Self {
+                Z2k(self.0.wrapping_sub(rhs.0))
+            }
+        }
+
+        impl Mul for Z2k<$t> {
+            type Output = Self;
+            #[inline]
+            fn mul(self, rhs: Self) -> Self {
+                Z2k(self.0.wrapping_mul(rhs.0))
+            }
+        }
+
+        impl Ring for Z2k<$t> {
+            const BITS: usize = 8 * std::mem::size_of::<$t>();
+
+            #[inline]
+            fn one() -> Self {
+                Z2k(1)
+            }
+            #[inline]
+            fn basis(i: usize) -> Self {
+                Z2k(1 << i)
+            }
+            #[inline]
+            fn bit(&self, i: usize) -> bool {
+                (self.0 >> i) & 1 == 1
+            }
+            #[inline]
+            fn from_block(b: Block) -> Self {
+                Z2k(u128::from(b) as $t)
+            }
+            #[inline]
+            fn write<C: AbstractChannel>(&self, channel: &mut C) -> std::io::Result<()> {
+                channel.write_bytes(&self.0.to_le_bytes())
+            }
+            #[inline]
+            fn read<C: AbstractChannel>(channel: &mut C) -> std::io::Result<Self> {
+                let mut bytes = [0u8; std::mem::size_of::<$t>()];
+                channel.read_bytes(&mut bytes)?;
+                Ok(Z2k(<$t>::from_le_bytes(bytes)))
+            }
+        }
+    };
+}
+
+impl_z2k!(u32);
+impl_z2k!(u64);
+impl_z2k!(u128);
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use scuttlebutt::AesRng;
+
+    #[allow(clippy::eq_op)]
+    pub(crate) fn _test_ring<R: Ring>() {
+        let mut rng = AesRng::new();
+        let (a, b, c) = (
+            R::random(&mut rng),
+            R::random(&mut rng),
+            R::random(&mut rng),
+        );
+        assert_eq!(a + b, b + a);
+        assert_eq!(a * b, b * a);
+        assert_eq!((a + b) * c, a * c + b * c);
+        assert_eq!((a * b) * c, a * (b * c));
+        assert_eq!(a - a, R::default());
+        assert_eq!(a + (-a), R::default());
+        assert_eq!(a * R::one(), a);
+        let a_ = (0..R::BITS)
+            .filter(|i| a.bit(*i))
+            .fold(R::default(), |acc, i| acc + R::basis(i));
+        assert_eq!(a, a_);
+    }
+
+    #[test]
+    fn test_z2k() {
+        _test_ring::<Z2k<u32>>();
+        _test_ring::<Z2k<u64>>();
+        _test_ring::<Z2k<u128>>();
+        assert_eq!(-Z2k(1u64), Z2k(u64::MAX));
+    }
+}
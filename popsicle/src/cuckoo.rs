@@ -27,7 +27,7 @@ pub(crate) struct CuckooHash {
 /// The number of times to loop when trying to place an entry in a bin.
 const NITERS: usize = 1000;
 
-fn compute_nbins(n: usize, nhashes: usize) -> Result<usize, Error> {
+pub(crate) fn compute_nbins(n: usize, nhashes: usize) -> Result<usize, Error> {
     // Numbers taken from <https://thomaschneider.de/papers/PSZ18.pdf>, §3.2.2.
     if nhashes == 3 {
         if n < 1 << 27 {
@@ -71,7 +71,16 @@ impl CuckooHash {
     /// bits will be used to store the hash index.
     pub fn new(inputs: &[Block], nhashes: usize) -> Result<CuckooHash, Error> {
         let nbins = compute_nbins(inputs.len(), nhashes)?;
+        CuckooHash::with_nbins(inputs, nhashes, nbins)
+    }
 
+    /// Build a new cuckoo hash table with `nbins` bins, hashing `inputs` in,
+    /// as in `new`.
+    pub(crate) fn with_nbins(
+        inputs: &[Block],
+        nhashes: usize,
+        nbins: usize,
+    ) -> Result<CuckooHash, Error> {
         let mut tbl = CuckooHash {
             items: vec![None; nbins],
             nbins,
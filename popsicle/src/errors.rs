@@ -28,6 +28,8 @@ pub enum Error {
     PsiProtocolError(String),
     /// Not enough payloads.
     InvalidPayloadsLength,
+    /// The other party deviated from the protocol.
+    CheatingDetected(String),
     /// SSL Error
     #[cfg(feature = "psty")]
     SSLError(openssl::error::ErrorStack),
@@ -90,6 +92,7 @@ impl std::fmt::Display for Error {
             ),
             Error::PsiProtocolError(s) => write!(f, "PSI protocol error: {}", s),
             Error::InvalidPayloadsLength => write!(f, "Invalid length of payloads!"),
+            Error::CheatingDetected(s) => write!(f, "cheating detected: {}", s),
             #[cfg(feature = "psty")]
             Error::SSLError(e) => write!(f, "SSL Error: {}", e),
             #[cfg(feature = "psty")]
@@ -13,6 +13,7 @@
 
 mod cuckoo;
 mod errors;
+pub mod network;
 mod psi;
 mod utils;
 
@@ -0,0 +1,336 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `popsicle`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! A full mesh of TCP connections between the parties of a multi-party
+//! protocol, such as the ones in `kmprt`.
+//!
+//! Each party listens at the address given for it in the configuration,
+//! accepts a connection from every party with a smaller id, and connects to
+//! every party with a larger id. Connections are retried until the other
+//! party listens, so the parties can be started in any order, and the whole
+//! mesh must be up before a timeout. Incoming connections that do not come
+//! from an expected party are dropped.
+
+use crate::{psi::kmprt::PartyId, Error};
+use itertools::Itertools;
+use scuttlebutt::TrackChannel;
+#[cfg(feature = "serde1")]
+use serde::Deserialize;
+use std::{
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+
+/// The channel to another party in a `Network`.
+pub type NetworkChannel = TrackChannel<BufReader<TcpStream>, BufWriter<TcpStream>>;
+
+/// How long to wait before retrying to connect to, or accept a connection
+/// from, another party.
+const RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long to wait for an incoming connection to say which party it is from.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The address a party listens at.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde1", derive(Deserialize))]
+pub struct PartyConfig {
+    /// The host name or IP address of the party.
+    pub address: String,
+    /// The port of the party.
+    pub port: u16,
+}
+
+/// Communication statistics of the link to another party.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LinkStats {
+    /// The other party.
+    pub party: PartyId,
+    /// The number of kilobits sent to the other party.
+    pub kilobits_written: f64,
+    /// The number of kilobits received from the other party.
+    pub kilobits_read: f64,
+}
+
+/// A party's connections to every other party.
+pub struct Network {
+    me: PartyId,
+    channels: Vec<(PartyId, NetworkChannel)>,
+}
+
+impl Network {
+    /// Connect party `me` to every other party, where party `i` listens at
+    /// `parties[i]`. Fails if not every connection is up after `timeout`.
+    pub fn connect(me: PartyId, parties: &[PartyConfig], timeout: Duration) -> Result<Self, Error> {
+        if me >= parties.len() {
+            return Err(Error::IoError(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "party {} is not in a configuration of {} parties",
+                    me,
+                    parties.len()
+                ),
+            )));
+        }
+        let deadline = Instant::now() + timeout;
+        let listener = TcpListener::bind((parties[me].address.as_str(), parties[me].port))?;
+        listener.set_nonblocking(true)?;
+
+        let mut streams = (0..parties.len()).map(|_| None).collect_vec();
+        // Connecting before accepting cannot deadlock, since a connection to
+        // a listening party completes before the party accepts it.
+        for (them, party) in parties.iter().enumerate().skip(me + 1) {
+            let mut stream = connect_to(party, deadline)?;
+            stream.write_all(&(me as u64).to_le_bytes())?;
+            stream.write_all(&(them as u64).to_le_bytes())?;
+            streams[them] = Some(stream);
+        }
+        let mut naccepted = 0;
+        while naccepted < me {
+            let mut stream = match listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    wait(deadline, "accept a connection from every party")?;
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            match read_handshake(&mut stream, deadline) {
+                Ok((them, target)) if target == me && them < me && streams[them].is_none() => {
+                    stream.set_read_timeout(None)?;
+                    streams[them] = Some(stream);
+                    naccepted += 1;
+                }
+                // Whoever connected is not a party we are waiting for, so
+                // drop the connection and keep accepting until the deadline.
+                _ => continue,
+            }
+        }
+
+        let channels = streams
+            .into_iter()
+            .enumerate()
+            .filter_map(|(them, stream)| stream.map(|stream| (them, stream)))
+            .map(|(them, stream)| {
+                stream.set_nodelay(true)?;
+                let reader = BufReader::new(stream.try_clone()?);
+                let writer = BufWriter::new(stream);
+                Ok((them, TrackChannel::new(reader, writer)))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(Self { me, channels })
+    }
+
+    /// Return the id of this party.
+    pub fn id(&self) -> PartyId {
+        self.me
+    }
+
+    /// Return the channels to every other party, in order of their ids, in
+    /// the form taken by the multi-party protocols.
+    pub fn channels(&mut self) -> &mut [(PartyId, NetworkChannel)] {
+        &mut self.channels
+    }
+
+    /// Return the communication statistics of the link to every other party,
+    /// in order of their ids.
+    pub fn stats(&self) -> Vec<LinkStats> {
+        self.channels
+            .iter()
+            .map(|(party, channel)| LinkStats {
+                party: *party,
+                kilobits_written: channel.kilobits_written(),
+                kilobits_read: channel.kilobits_read(),
+            })
+            .collect()
+    }
+
+    /// Reset the communication statistics of every link.
+    pub fn clear_stats(&mut self) {
+        for (_, channel) in self.channels.iter_mut() {
+            channel.clear();
+        }
+    }
+}
+
+// Connect to `party`, retrying until `deadline`. No single attempt outlives
+// the deadline, even if the other host never answers.
+fn connect_to(party: &PartyConfig, deadline: Instant) -> Result<TcpStream, Error> {
+    let addresses = (party.address.as_str(), party.port)
+        .to_socket_addrs()?
+        .collect_vec();
+    loop {
+        for address in addresses.iter() {
+            if let Ok(stream) = TcpStream::connect_timeout(address, remaining(deadline)) {
+                return Ok(stream);
+            }
+        }
+        wait(
+            deadline,
+            &format!("connect to {}:{}", party.address, party.port),
+        )?;
+    }
+}
+
+// Sleep before the next retry, or fail if `deadline` has passed.
+fn wait(deadline: Instant, what: &str) -> Result<(), Error> {
+    if Instant::now() >= deadline {
+        return Err(Error::IoError(std::io::Error::new(
+            ErrorKind::TimedOut,
+            format!("could not {} in time", what),
+        )));
+    }
+    std::thread::sleep(RETRY_INTERVAL);
+    Ok(())
+}
+
+// Return the time left until `deadline`, which is never zero, since a zero
+// read timeout is an error.
+fn remaining(deadline: Instant) -> Duration {
+    std::cmp::max(
+        deadline.saturating_duration_since(Instant::now()),
+        Duration::from_millis(1),
+    )
+}
+
+// Read the ids of the connecting party and of the party it wants to connect
+// to from `stream`, waiting at most `HANDSHAKE_TIMEOUT` and never past
+// `deadline`.
+fn read_handshake(stream: &mut TcpStream, deadline: Instant) -> Result<(usize, usize), Error> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(std::cmp::min(remaining(deadline), HANDSHAKE_TIMEOUT)))?;
+    let them = read_u64(stream)? as usize;
+    let target = read_u64(stream)? as usize;
+    Ok((them, target))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64, Error> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::psi::kmprt::{Receiver, Sender};
+    use rand::Rng;
+    use scuttlebutt::{AesRng, Block};
+
+    // Returns a configuration of `nparties` parties on localhost, at ports
+    // that were free a moment ago.
+    fn localhost(nparties: usize) -> Vec<PartyConfig> {
+        (0..nparties)
+            .map(|_| {
+                let listener = TcpListener::bind("localhost:0").unwrap();
+                PartyConfig {
+                    address: "localhost".to_string(),
+                    port: listener.local_addr().unwrap().port(),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_network() {
+        let mut rng = AesRng::new();
+        let nparties = 3;
+        let set_size = 1 << 6;
+        let intersection = (0..set_size / 2).map(|_| rng.gen()).collect_vec();
+        let sets = (0..nparties)
+            .map(|_| {
+                let mut set = intersection.clone();
+                set.extend((set_size / 2..set_size).map(|_| rng.gen::<Block>()));
+                set
+            })
+            .collect_vec();
+        let config = localhost(nparties);
+
+        // Start the parties in reverse order, so that some of them have to
+        // retry connecting.
+        let handles = sets
+            .into_iter()
+            .enumerate()
+            .rev()
+            .map(|(me, set)| {
+                let config = config.clone();
+                std::thread::spawn(move || {
+                    let mut rng = AesRng::new();
+                    let mut network =
+                        Network::connect(me, &config, Duration::from_secs(10)).unwrap();
+                    assert_eq!(network.id(), me);
+                    let ids = network.channels().iter().map(|(id, _)| *id).collect_vec();
+                    assert_eq!(ids, (0..nparties).filter(|&id| id != me).collect_vec());
+                    let result = if me == 0 {
+                        let mut receiver = Receiver::init(network.channels(), &mut rng).unwrap();
+                        Some(
+                            receiver
+                                .receive(&set, network.channels(), &mut rng)
+                                .unwrap(),
+                        )
+                    } else {
+                        let mut sender = Sender::init(me, network.channels(), &mut rng).unwrap();
+                        sender.send(&set, network.channels(), &mut rng).unwrap();
+                        None
+                    };
+                    let stats = network.stats();
+                    for link in stats.iter() {
+                        assert!(link.kilobits_written > 0.0);
+                        assert!(link.kilobits_read > 0.0);
+                    }
+                    network.clear_stats();
+                    assert!(network.stats().iter().all(|link| link.kilobits_read == 0.0));
+                    result
+                })
+            })
+            .collect_vec();
+        let results = handles
+            .into_iter()
+            .filter_map(|handle| handle.join().unwrap())
+            .collect_vec();
+        assert_eq!(results, vec![intersection]);
+    }
+
+    #[test]
+    fn test_unexpected_connections() {
+        let config = localhost(2);
+        let config_ = config.clone();
+        let handle = std::thread::spawn(move || {
+            let network = Network::connect(1, &config_, Duration::from_secs(10)).unwrap();
+            network.channels.iter().map(|(id, _)| *id).collect_vec()
+        });
+        // Connections claiming the wrong ids, or hanging up halfway through
+        // the handshake, are dropped, and party 1 keeps waiting for party 0.
+        let deadline = Instant::now() + Duration::from_secs(10);
+        for handshake in &[&[0u8; 16][..], &[0u8; 4][..], &[1u8; 16][..]] {
+            let mut stream = connect_to(&config[1], deadline).unwrap();
+            stream.write_all(handshake).unwrap();
+        }
+        let network = Network::connect(0, &config, Duration::from_secs(10)).unwrap();
+        assert_eq!(network.channels.len(), 1);
+        assert_eq!(handle.join().unwrap(), vec![0]);
+    }
+
+    #[test]
+    fn test_timeout() {
+        // Party 1 never shows up.
+        let config = localhost(2);
+        match Network::connect(0, &config, Duration::from_millis(100)) {
+            Err(Error::IoError(e)) => assert_eq!(e.kind(), ErrorKind::TimedOut),
+            _ => panic!("missing party not detected"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_id() {
+        let config = localhost(2);
+        match Network::connect(2, &config, Duration::from_millis(100)) {
+            Err(Error::IoError(e)) => assert_eq!(e.kind(), ErrorKind::InvalidInput),
+            _ => panic!("invalid party id not detected"),
+        }
+    }
+}
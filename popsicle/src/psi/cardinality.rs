@@ -0,0 +1,425 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `popsicle`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Implementation of two-party private set intersection cardinality (PSI-CA)
+//! and PSI-sum from commutative Diffie-Hellman blinding (cf.
+//! <https://eprint.iacr.org/2011/141>, §5, and
+//! <https://eprint.iacr.org/2019/723>, §3.1).
+//!
+//! The receiver picks a key `a` and sends `a · H(x)` for each `x` in its set.
+//! The sender picks a key `b` and sends back `b · a · H(x)` for each of them,
+//! sorted by value, followed by `b · H(y)` for each `y` in its own set, also
+//! sorted. The receiver multiplies the latter by `a` and counts how many land
+//! among the former. Sorting pseudorandom values shuffles them, and the
+//! receiver cannot compute `b · a · H(x)` itself, so it has no way to tell
+//! which of its inputs a match corresponds to: it learns the size of the
+//! intersection and nothing else. The sender only learns the size of the
+//! receiver's set.
+//!
+//! In PSI-sum, the sender attaches `v + r` to each `b · H(y)`, where `v` is
+//! the value it associates with `y` and `r` is a fresh random mask. The
+//! receiver adds up the masked values of the matches, and learns the sum of
+//! their masks through one oblivious transfer per element of the sender's
+//! set, in which the sender offers `(m, m + r)` for random `m` summing to zero
+//! and the receiver picks the second message exactly for the matches. The
+//! receiver thus learns the sum of the values over the intersection, without
+//! any of the individual values. As in the protocol of Ion et al., it also
+//! learns the size of the intersection, which is the number of matches.
+//!
+//! Both parties learn the size of the other party's set. Duplicate inputs
+//! are counted once, and in PSI-sum their values are added together.
+
+use crate::{errors::Error, utils};
+use curve25519_dalek::{
+    ristretto::{CompressedRistretto, RistrettoPoint},
+    scalar::Scalar,
+};
+use itertools::Itertools;
+use ocelot::ot::{AlszReceiver as OtReceiver, AlszSender as OtSender, Receiver as _, Sender as _};
+use rand::{CryptoRng, Rng};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use scuttlebutt::{AbstractChannel, Block, SemiHonest};
+use sha2::{Digest, Sha512};
+use std::collections::{HashMap, HashSet};
+
+/// The type of values in the sender and receiver's sets.
+pub type Msg = Vec<u8>;
+
+/// PSI-cardinality and PSI-sum sender.
+#[derive(Default)]
+pub struct Sender {}
+
+/// PSI-cardinality and PSI-sum receiver.
+#[derive(Default)]
+pub struct Receiver {}
+
+// Hash an input to a point on the curve.
+#[inline]
+fn hash_to_curve(input: &Block) -> RistrettoPoint {
+    let mut hasher = Sha512::new();
+    hasher.input(b"popsicle psi-ca hash-to-curve");
+    hasher.input(input.as_ref());
+    RistrettoPoint::from_hash(hasher)
+}
+
+// Blind each of `inputs` with `key`, and sort the results.
+fn blind_sorted(inputs: &[RistrettoPoint], key: Scalar) -> Vec<[u8; 32]> {
+    let mut blinded = utils::iter(inputs)
+        .map(|pt| (key * pt).compress().to_bytes())
+        .collect::<Vec<[u8; 32]>>();
+    blinded.sort_unstable();
+    blinded
+}
+
+fn write_points<C: AbstractChannel>(channel: &mut C, pts: &[[u8; 32]]) -> Result<(), Error> {
+    channel.write_usize(pts.len())?;
+    for pt in pts.iter() {
+        channel.write_bytes(pt)?;
+    }
+    Ok(())
+}
+
+fn read_points<C: AbstractChannel>(channel: &mut C) -> Result<Vec<RistrettoPoint>, Error> {
+    let n = channel.read_usize()?;
+    (0..n).map(|_| read_point(channel)).collect()
+}
+
+fn read_point<C: AbstractChannel>(channel: &mut C) -> Result<RistrettoPoint, Error> {
+    let mut bytes = [0u8; 32];
+    channel.read_bytes(&mut bytes)?;
+    CompressedRistretto(bytes)
+        .decompress()
+        .ok_or_else(|| Error::PsiProtocolError("invalid group element".to_string()))
+}
+
+impl Sender {
+    /// Make a new PSI-cardinality and PSI-sum sender.
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Run the PSI-cardinality protocol over `inputs`, revealing only the size
+    /// of the intersection to the receiver.
+    pub fn send_cardinality<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        inputs: &[Msg],
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<(), Error> {
+        let inputs = inputs
+            .iter()
+            .map(|input| utils::compress_input(input))
+            .collect::<HashSet<Block>>()
+            .into_iter()
+            .collect_vec();
+        let key = self.blind_receiver_inputs(channel, rng)?;
+        let pts = utils::iter(&inputs)
+            .map(hash_to_curve)
+            .collect::<Vec<RistrettoPoint>>();
+        write_points(channel, &blind_sorted(&pts, key))?;
+        channel.flush()?;
+        Ok(())
+    }
+
+    /// Run the PSI-sum protocol over `inputs`, revealing to the receiver only
+    /// the sum, modulo `2^64`, of `values[i]` over all `inputs[i]` in the
+    /// intersection, and the size of the intersection.
+    pub fn send_sum<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        inputs: &[Msg],
+        values: &[u64],
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<(), Error> {
+        if values.len() != inputs.len() {
+            return Err(Error::InvalidPayloadsLength);
+        }
+        let mut sums = HashMap::with_capacity(inputs.len());
+        for (input, value) in inputs.iter().zip(values.iter()) {
+            let sum = sums.entry(utils::compress_input(input)).or_insert(0u64);
+            *sum = sum.wrapping_add(*value);
+        }
+        let (inputs, values): (Vec<Block>, Vec<u64>) = sums.into_iter().unzip();
+        let key = self.blind_receiver_inputs(channel, rng)?;
+        let mut entries = utils::iter(&inputs)
+            .map(|input| (key * hash_to_curve(input)).compress().to_bytes())
+            .zip(values)
+            .collect::<Vec<([u8; 32], u64)>>();
+        entries.sort_unstable();
+        channel.write_usize(entries.len())?;
+        let mut masks = Vec::with_capacity(entries.len());
+        for (pt, value) in entries.into_iter() {
+            let r = rng.gen::<u64>();
+            channel.write_bytes(&pt)?;
+            channel.write_u64(value.wrapping_add(r))?;
+            masks.push(r);
+        }
+        channel.flush()?;
+
+        // Obliviously reveal the sum of the masks of the values the receiver
+        // matched.
+        let mut ms = (1..masks.len()).map(|_| rng.gen::<u64>()).collect_vec();
+        if !masks.is_empty() {
+            let total = ms.iter().fold(0u64, |acc, m| acc.wrapping_add(*m));
+            ms.push(0u64.wrapping_sub(total));
+        }
+        let ot_inputs = ms
+            .into_iter()
+            .zip(masks)
+            .map(|(m, r)| {
+                (
+                    Block::from(u128::from(m)),
+                    Block::from(u128::from(m.wrapping_add(r))),
+                )
+            })
+            .collect_vec();
+        let mut ot = OtSender::init(channel, rng)?;
+        ot.send(channel, &ot_inputs, rng)?;
+        Ok(())
+    }
+
+    // Read the receiver's blinded inputs, and send them back blinded with a
+    // fresh key, in sorted order. Returns the key.
+    fn blind_receiver_inputs<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Scalar, Error> {
+        let key = Scalar::random(rng);
+        let pts = read_points(channel)?;
+        write_points(channel, &blind_sorted(&pts, key))?;
+        Ok(key)
+    }
+}
+
+impl Receiver {
+    /// Make a new PSI-cardinality and PSI-sum receiver.
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Run the PSI-cardinality protocol over `inputs`, returning the size of
+    /// the intersection.
+    pub fn receive_cardinality<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        inputs: &[Msg],
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<usize, Error> {
+        let (key, blinded) = self.blind_inputs(inputs, channel, rng)?;
+        let pts = read_points(channel)?;
+        let cardinality = utils::iter(&pts)
+            .filter(|pt| blinded.contains(&(key * *pt).compress().to_bytes()))
+            .count();
+        Ok(cardinality)
+    }
+
+    /// Run the PSI-sum protocol over `inputs`, returning the sum, modulo
+    /// `2^64`, of the sender's values over the intersection.
+    pub fn receive_sum<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        inputs: &[Msg],
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<u64, Error> {
+        let (key, blinded) = self.blind_inputs(inputs, channel, rng)?;
+        let n = channel.read_usize()?;
+        let mut pts = Vec::with_capacity(n);
+        let mut values = Vec::with_capacity(n);
+        for _ in 0..n {
+            pts.push(read_point(channel)?);
+            values.push(channel.read_u64()?);
+        }
+        let choices = utils::iter(&pts)
+            .map(|pt| blinded.contains(&(key * *pt).compress().to_bytes()))
+            .collect::<Vec<bool>>();
+        let sum = values
+            .into_iter()
+            .zip(choices.iter())
+            .filter(|(_, matched)| **matched)
+            .fold(0u64, |acc, (value, _)| acc.wrapping_add(value));
+
+        // Obliviously learn the sum of the masks of the matched values.
+        let mut ot = OtReceiver::init(channel, rng)?;
+        let masks = ot
+            .receive(channel, &choices, rng)?
+            .into_iter()
+            .fold(0u64, |acc, m| acc.wrapping_add(u128::from(m) as u64));
+        Ok(sum.wrapping_sub(masks))
+    }
+
+    // Send our inputs blinded with a fresh key, and read them back blinded
+    // with the sender's key as well. Returns our key and the doubly blinded
+    // inputs, which the sender has sorted.
+    fn blind_inputs<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        inputs: &[Msg],
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<(Scalar, HashSet<[u8; 32]>), Error> {
+        let inputs = inputs
+            .iter()
+            .map(|input| utils::compress_input(input))
+            .collect::<HashSet<Block>>()
+            .into_iter()
+            .collect_vec();
+        let key = Scalar::random(rng);
+        let pts = utils::iter(&inputs)
+            .map(hash_to_curve)
+            .collect::<Vec<RistrettoPoint>>();
+        write_points(channel, &blind_sorted(&pts, key))?;
+        channel.flush()?;
+        let blinded = read_points(channel)?
+            .into_iter()
+            .map(|pt| pt.compress().to_bytes())
+            .collect::<HashSet<[u8; 32]>>();
+        if blinded.len() != inputs.len() {
+            return Err(Error::PsiProtocolError(
+                "sender returned the wrong number of elements".to_string(),
+            ));
+        }
+        Ok((key, blinded))
+    }
+}
+
+impl SemiHonest for Sender {}
+impl SemiHonest for Receiver {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use scuttlebutt::{AesRng, Channel};
+    use std::{
+        io::{BufReader, BufWriter, Read},
+        os::unix::net::UnixStream,
+        sync::{Arc, Mutex},
+    };
+
+    const ITEM_SIZE: usize = 8;
+    const SET_SIZE: usize = 1 << 6;
+
+    // Records everything read from `inner`.
+    struct Recorder<R> {
+        inner: R,
+        log: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl<R: Read> Read for Recorder<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            self.log.lock().unwrap().extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+    }
+
+    // Runs PSI-cardinality (if `values` is `None`) or PSI-sum between the
+    // given sets, with fixed randomness on both sides. Returns the receiver's
+    // output and everything it read from the sender.
+    fn run(
+        sender_inputs: Vec<Msg>,
+        values: Option<Vec<u64>>,
+        receiver_inputs: &[Msg],
+    ) -> (u64, Vec<u8>) {
+        let sum = values.is_some();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::from_seed(Block::from(1u128));
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut psi = Sender::new();
+            match values {
+                Some(values) => psi.send_sum(&sender_inputs, &values, &mut channel, &mut rng),
+                None => psi.send_cardinality(&sender_inputs, &mut channel, &mut rng),
+            }
+            .unwrap();
+        });
+        let mut rng = AesRng::from_seed(Block::from(2u128));
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let reader = Recorder {
+            inner: receiver.try_clone().unwrap(),
+            log: log.clone(),
+        };
+        let reader = BufReader::new(reader);
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut psi = Receiver::new();
+        let output = if sum {
+            psi.receive_sum(receiver_inputs, &mut channel, &mut rng)
+        } else {
+            psi.receive_cardinality(receiver_inputs, &mut channel, &mut rng)
+                .map(|n| n as u64)
+        }
+        .unwrap();
+        handle.join().unwrap();
+        let log = log.lock().unwrap().clone();
+        (output, log)
+    }
+
+    // Returns the sender's and receiver's sets, which have `intersection_size`
+    // elements in common, followed by those elements.
+    fn sets(intersection_size: usize) -> (Vec<Msg>, Vec<Msg>) {
+        let mut rng = AesRng::new();
+        let intersection = utils::rand_vec_vec(intersection_size, ITEM_SIZE, &mut rng);
+        let mut sender_inputs =
+            utils::rand_vec_vec(SET_SIZE - intersection_size, ITEM_SIZE, &mut rng);
+        let mut receiver_inputs =
+            utils::rand_vec_vec(SET_SIZE - intersection_size, ITEM_SIZE, &mut rng);
+        sender_inputs.extend(intersection.clone());
+        receiver_inputs.extend(intersection);
+        (sender_inputs, receiver_inputs)
+    }
+
+    #[test]
+    fn test_cardinality() {
+        for &intersection_size in &[0, 1, SET_SIZE / 3, SET_SIZE] {
+            let (mut sender_inputs, receiver_inputs) = sets(intersection_size);
+            // Duplicates are only counted once.
+            sender_inputs.push(sender_inputs[SET_SIZE - 1].clone());
+            let (cardinality, _) = run(sender_inputs, None, &receiver_inputs);
+            assert_eq!(cardinality, intersection_size as u64);
+        }
+    }
+
+    #[test]
+    fn test_sum() {
+        let mut rng = AesRng::new();
+        for &intersection_size in &[0, 1, SET_SIZE / 3, SET_SIZE] {
+            let (mut sender_inputs, receiver_inputs) = sets(intersection_size);
+            let mut values = (0..SET_SIZE).map(|_| rng.gen::<u64>()).collect_vec();
+            // The values of duplicates are added together.
+            sender_inputs.push(sender_inputs[SET_SIZE - 1].clone());
+            values.push(rng.gen::<u64>());
+            let expected = values[SET_SIZE - intersection_size..]
+                .iter()
+                .fold(0u64, |acc, v| acc.wrapping_add(*v));
+            let expected = if intersection_size == 0 { 0 } else { expected };
+            let (sum, _) = run(sender_inputs, Some(values), &receiver_inputs);
+            assert_eq!(sum, expected);
+        }
+    }
+
+    // The receiver's view, i.e., everything it reads, only depends on its
+    // set and not on which of its inputs are in the intersection: moving the
+    // common elements around in its input leaves the view unchanged, so it
+    // cannot link any match to one of its inputs.
+    #[test]
+    fn test_receiver_view() {
+        let mut rng = AesRng::new();
+        let (sender_inputs, receiver_inputs) = sets(SET_SIZE / 4);
+        let values = (0..SET_SIZE).map(|_| rng.gen::<u64>()).collect_vec();
+        let mut reordered = receiver_inputs.clone();
+        reordered.reverse();
+        for values in &[None, Some(values)] {
+            let (output0, view0) = run(sender_inputs.clone(), values.clone(), &receiver_inputs);
+            let (output1, view1) = run(sender_inputs.clone(), values.clone(), &reordered);
+            assert_eq!(output0, output1);
+            assert_eq!(view0, view1);
+        }
+    }
+}
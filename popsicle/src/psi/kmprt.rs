@@ -6,12 +6,64 @@
 
 //! Implementation of the "Kolesnikov-Matania-Pinkas-Rosulek-Trieu" multi-party private
 //! set intersection protocol (cf. <https://eprint.iacr.org/2017/799.pdf>).
-
-use crate::Error;
+//!
+//! `ThresholdParty` extends the protocol to over-threshold PSI, in which the
+//! output consists of the elements held by at least `t` of the `n` parties, in
+//! the spirit of Mahdavi et al. (cf. <https://eprint.iacr.org/2019/1175>).
+//! Here the conditional secret sharing uses Shamir sharing in `GF(2^128)`
+//! instead of XOR sharing: for each party `r`, the other parties share a key
+//! that determines, for every element `x`, a random polynomial `f_{r,x}` of
+//! degree `t - 2` with `f_{r,x}(0) = 0`, and each party `j` programs
+//! `f_{r,x}(j)` at each of its elements `x` into an OPPRF towards `r`. Party
+//! `r` thus learns a point on `f_{r,x}` from each other party holding an
+//! element `x` of its own set, and a random value from each other party, so
+//! `x` is held by at least `t` parties if and only if at least `t - 1` of the
+//! values it receives lie on a polynomial of degree `t - 2` through the origin.
+//! Fewer than `t - 1` points on `f_{r,x}` are uniformly random, so party `r`
+//! learns nothing about elements below the threshold.
+//!
+//! Finding the consistent values takes trying every set of `t - 1` other
+//! parties, so the cost of the final step grows with `binomial(n - 1, t - 1)`.
+//! The set that succeeds also tells a party which other parties hold each of
+//! its over-threshold elements. On top of each party learning the
+//! over-threshold elements of its own set, every party can learn all of them,
+//! in which case party 0 also learns which party found which element.
+//!
+//! Learning just the number of over-threshold elements uses a different,
+//! circuit-style protocol, in which no party learns anything but that number,
+//! as long as parties 0 and 1 do not collude. Each party hashes its set into
+//! cuckoo bins, and for every other party `j` and bin `b`, the two run an OPPRF
+//! in which `j` programs a random mask for `b` at each of its elements that
+//! could land in `b`, followed by a private equality test between the OPPRF
+//! output and the mask. This leaves them with shares of whether `j` holds the
+//! element in `b`, which they turn into additive shares modulo `2^64`. Party
+//! `r` then counts, for the element in each of its bins, the number `c` of
+//! other parties holding it and the number `d` of those with a lower party
+//! number, in shared form: every party adds up its shares of `c + n · d` and
+//! splits the sum between parties 0 and 1. The element is over the threshold,
+//! and `r` is the first party holding it, if and only if `c + n · d` lies
+//! between `t - 1` and `n - 1`, which parties 0 and 1 test with one private
+//! equality test per possible value. They add up shares of the results and
+//! reveal only the total, which party 0 sends to everyone else.
+//!
+//! As in the plain protocol, all parties must have the same number of inputs.
+
+use crate::{
+    cuckoo::{compute_nbins, CuckooHash},
+    Error,
+};
 use itertools::Itertools;
-use ocelot::oprf::{KmprtReceiver, KmprtSender};
+use ocelot::{
+    compare::{KkrtCompareReceiver, KkrtCompareSender},
+    oprf::{KmprtReceiver, KmprtSender},
+    ot::{AlszReceiver, AlszSender, Receiver as OtReceiver, Sender as OtSender},
+};
 use rand::{CryptoRng, Rng, RngCore, SeedableRng};
-use scuttlebutt::{AbstractChannel, Block, Block512};
+use scuttlebutt::{AbstractChannel, Aes128, Block, Block512, SemiHonest};
+use std::collections::BTreeSet;
+
+// The number of hash functions used for the cuckoo bins when counting.
+const NHASHES: usize = 3;
 
 /// The party number for each party.
 pub type PartyId = usize;
@@ -29,6 +81,31 @@ pub struct Sender(Party);
 /// KMPRT receiver - there can only be one of these.
 pub struct Receiver(Party);
 
+/// KMPRT over-threshold party - every party runs one of these.
+pub struct ThresholdParty {
+    party: Party,
+    threshold: usize,
+    // `keys[p]` determines the polynomials of party `p`, and is known to every
+    // party but `p`.
+    keys: Vec<Option<Aes128>>,
+    // For each set of `threshold - 1` other parties, given by the indices of
+    // their channels, the weights of their values in the divided difference
+    // which is zero if and only if they lie on a polynomial of degree
+    // `threshold - 2` through the origin.
+    checks: Vec<(Vec<usize>, Vec<Block>)>,
+    // The equality tests and share conversions with each other party, in the
+    // order of `channels`.
+    links: Vec<Link>,
+}
+
+/// Private equality tests and conversions from XOR to additive shares with
+/// one other party, where the party with the lower party number plays the
+/// sender.
+enum Link {
+    Sender(KkrtCompareSender, AlszSender),
+    Receiver(KkrtCompareReceiver, AlszReceiver),
+}
+
 impl Sender {
     /// Initialize a PSI sender.
     pub fn init<C: AbstractChannel, RNG: RngCore + CryptoRng + SeedableRng>(
@@ -148,11 +225,39 @@ impl Party {
             })
             .collect_vec();
 
+        let outputs =
+            self.conditional_sharing(inputs, channels, rng, |i, other_id, _| s[i][other_id])?;
+        for bs in outputs.into_iter() {
+            for (i, b) in bs.into_iter().enumerate() {
+                s_hat[i] ^= b;
+            }
+        }
+
+        Ok(s_hat)
+    }
+
+    /// Share `share(i, other_id, rng)` with each other party using OPPRF,
+    /// conditioned on the other party holding `inputs[i]`, and return the
+    /// OPPRF outputs on `inputs` from each other party, in the order of
+    /// `channels`.
+    fn conditional_sharing<C, RNG, F>(
+        &mut self,
+        inputs: &[Block],
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+        mut share: F,
+    ) -> Result<Vec<Vec<Block512>>, Error>
+    where
+        C: AbstractChannel,
+        RNG: RngCore + CryptoRng + SeedableRng,
+        F: FnMut(usize, PartyId, &mut RNG) -> Block512,
+    {
+        let mut outputs = Vec::with_capacity(channels.len());
         for (channel_num, (other_id, channel)) in channels.iter_mut().enumerate() {
             let points = inputs
                 .iter()
                 .enumerate()
-                .map(|(i, x)| (*x, s[i][*other_id]))
+                .map(|(i, x)| (*x, share(i, *other_id, rng)))
                 .collect_vec();
 
             let bs;
@@ -163,14 +268,447 @@ impl Party {
                 bs = self.opprf_receivers[channel_num].receive(channel, inputs, rng)?;
                 self.opprf_senders[channel_num].send(channel, &points, inputs.len(), rng)?;
             }
+            outputs.push(bs);
+        }
+        Ok(outputs)
+    }
+}
 
-            for (i, b) in bs.into_iter().enumerate() {
-                s_hat[i] ^= b;
+impl ThresholdParty {
+    /// Initialize an over-threshold PSI party, which outputs the elements held
+    /// by at least `threshold` parties. All parties must use the same
+    /// threshold, which must be at least two and at most the number of
+    /// parties.
+    pub fn init<C: AbstractChannel, RNG: RngCore + CryptoRng + SeedableRng>(
+        me: PartyId,
+        threshold: usize,
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let nparties = channels.len() + 1;
+        if threshold < 2 || threshold > nparties {
+            return Err(Error::PsiProtocolError(format!(
+                "threshold {} is not between 2 and the number of parties {}",
+                threshold, nparties
+            )));
+        }
+        let party = Party::init(me, channels, rng)?;
+
+        // The key of each party is chosen by party 0, or by party 1 for party 0.
+        let dealer = |p: PartyId| if p == 0 { 1 } else { 0 };
+        let mut keys = (0..nparties).map(|_| None).collect_vec();
+        for (p, key) in keys.iter_mut().enumerate() {
+            if p != me && dealer(p) == me {
+                let k = rng.gen::<Block>();
+                for (other_id, channel) in channels.iter_mut() {
+                    if *other_id != p {
+                        channel.write_block(&k)?;
+                        channel.flush()?;
+                    }
+                }
+                *key = Some(Aes128::new(k));
+            }
+        }
+        for (p, key) in keys.iter_mut().enumerate() {
+            if p != me && dealer(p) != me {
+                let k = channel(channels, dealer(p)).read_block()?;
+                *key = Some(Aes128::new(k));
             }
         }
 
-        Ok(s_hat)
+        let checks = (0..channels.len())
+            .combinations(threshold - 1)
+            .map(|set| {
+                let zs = set.iter().map(|&c| point(channels[c].0)).collect_vec();
+                let weights = zs
+                    .iter()
+                    .map(|&z| {
+                        zs.iter()
+                            .filter(|&&z_| z_ != z)
+                            .fold(z, |acc, &z_| acc.gfmul(z ^ z_))
+                            .gfinv()
+                    })
+                    .collect_vec();
+                (set, weights)
+            })
+            .collect_vec();
+
+        let links = channels
+            .iter_mut()
+            .map(|(other_id, channel)| Link::init(me, *other_id, channel, rng))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            party,
+            threshold,
+            keys,
+            checks,
+            links,
+        })
     }
+
+    /// Run the over-threshold PSI protocol, returning the elements of `inputs`
+    /// held by at least `threshold` parties, including this one.
+    pub fn intersect<C: AbstractChannel, RNG: RngCore + CryptoRng + SeedableRng>(
+        &mut self,
+        inputs: &[Block],
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+    ) -> Result<Vec<Block>, Error> {
+        let me = self.party.id;
+        let threshold = self.threshold;
+        let keys = &self.keys;
+        let share = |i: usize, other_id: PartyId, rng: &mut RNG| {
+            let key = keys[other_id]
+                .as_ref()
+                .expect("we know every other party's key");
+            // Only the first block carries the share, so pad it with random
+            // blocks, as the outputs at points that were not programmed are.
+            let mut value = rng.gen::<Block512>();
+            <&mut [Block; 4]>::from(&mut value)[0] =
+                shamir_share(key, inputs[i], point(me), threshold);
+            value
+        };
+        let outputs = self
+            .party
+            .conditional_sharing(inputs, channels, rng, share)?;
+
+        let intersection = inputs
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| {
+                self.checks.iter().any(|(set, weights)| {
+                    let sum = set.iter().zip(weights.iter()).fold(
+                        Block::default(),
+                        |acc, (&c, weight)| {
+                            let value = <&[Block; 4]>::from(&outputs[c][*i])[0];
+                            acc ^ value.gfmul(*weight)
+                        },
+                    );
+                    sum == Block::default()
+                })
+            })
+            .map(|(_, x)| *x)
+            .collect_vec();
+        Ok(intersection)
+    }
+
+    /// Run the over-threshold PSI protocol, returning all elements held by at
+    /// least `threshold` parties, in sorted order, to every party.
+    pub fn intersect_all<C: AbstractChannel, RNG: RngCore + CryptoRng + SeedableRng>(
+        &mut self,
+        inputs: &[Block],
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+    ) -> Result<Vec<Block>, Error> {
+        let intersection = self.intersect(inputs, channels, rng)?;
+        if self.party.id == 0 {
+            // Collect what every party found, and send back the union.
+            let mut union = intersection.into_iter().collect::<BTreeSet<_>>();
+            for (_, channel) in channels.iter_mut() {
+                let n = channel.read_usize()?;
+                for _ in 0..n {
+                    union.insert(channel.read_block()?);
+                }
+            }
+            for (_, channel) in channels.iter_mut() {
+                channel.write_usize(union.len())?;
+                for x in union.iter() {
+                    channel.write_block(x)?;
+                }
+                channel.flush()?;
+            }
+            Ok(union.into_iter().collect())
+        } else {
+            let channel = channel(channels, 0);
+            channel.write_usize(intersection.len())?;
+            for x in intersection.iter() {
+                channel.write_block(x)?;
+            }
+            channel.flush()?;
+            let n = channel.read_usize()?;
+            (0..n)
+                .map(|_| channel.read_block().map_err(Error::from))
+                .collect()
+        }
+    }
+
+    /// Run the over-threshold PSI protocol, returning the number of elements
+    /// held by at least `threshold` parties to every party.
+    pub fn cardinality<C: AbstractChannel, RNG: RngCore + CryptoRng + SeedableRng>(
+        &mut self,
+        inputs: &[Block],
+        channels: &mut [(PartyId, C)],
+        rng: &mut RNG,
+    ) -> Result<usize, Error> {
+        let me = self.party.id;
+        let nparties = channels.len() + 1;
+
+        // Hash the inputs under a key chosen by party 0, so that they are
+        // spread evenly over the bins.
+        let key = if me == 0 {
+            let key = rng.gen::<Block>();
+            for (_, channel) in channels.iter_mut() {
+                channel.write_block(&key)?;
+                channel.flush()?;
+            }
+            key
+        } else {
+            channel(channels, 0).read_block()?
+        };
+        let aes = Aes128::new(key);
+        let inputs = inputs.iter().map(|x| aes.encrypt(*x)).collect_vec();
+        // The first hash function reads a word whose low byte is cleared, so
+        // with an even number of bins it only reaches the even ones, and
+        // small tables overflow far more often. An odd count avoids that.
+        let nbins = compute_nbins(inputs.len(), NHASHES)? | 1;
+        let cuckoo = CuckooHash::with_nbins(&inputs, NHASHES, nbins)?;
+        let queries = cuckoo
+            .items
+            .iter()
+            .map(|item| match item {
+                Some(item) => item.entry,
+                None => rng.gen(),
+            })
+            .collect_vec();
+
+        // `counts[r * nbins + b]` is our share of `c + n · d` for the element
+        // in bin `b` of party `r`.
+        let mut counts = vec![0u64; nparties * nbins];
+        for (channel_num, (other_id, channel)) in channels.iter_mut().enumerate() {
+            let masks = (0..nbins).map(|_| rng.gen::<u64>()).collect_vec();
+            let mask = Block::from(0xFFFF_FFFF_FFFF_FFFF_FFFF_FFFF_FFFF_FF00);
+            let points = inputs
+                .iter()
+                .flat_map(|&x| {
+                    (0..NHASHES).map(move |h| {
+                        let bin = CuckooHash::bin(x & mask, h, nbins);
+                        ((x & mask) ^ Block::from(h as u128), bin)
+                    })
+                })
+                .map(|(point, bin)| {
+                    let mut value = rng.gen::<Block512>();
+                    <&mut [Block; 4]>::from(&mut value)[0] = Block::from(masks[bin] as u128);
+                    (point, value)
+                })
+                .collect_vec();
+
+            let outputs;
+            if me < *other_id {
+                self.party.opprf_senders[channel_num].send(channel, &points, nbins, rng)?;
+                outputs =
+                    self.party.opprf_receivers[channel_num].receive(channel, &queries, rng)?;
+            } else {
+                outputs =
+                    self.party.opprf_receivers[channel_num].receive(channel, &queries, rng)?;
+                self.party.opprf_senders[channel_num].send(channel, &points, nbins, rng)?;
+            }
+            let outputs = outputs
+                .iter()
+                .map(|output| u128::from(<&[Block; 4]>::from(output)[0]) as u64)
+                .collect_vec();
+
+            // Test the bins of the party with the lower party number first.
+            let (first, second) = if me < *other_id {
+                ((me, *other_id), (*other_id, me))
+            } else {
+                ((*other_id, me), (me, *other_id))
+            };
+            let values = if me < *other_id {
+                [outputs, masks].concat()
+            } else {
+                [masks, outputs].concat()
+            };
+            let link = &mut self.links[channel_num];
+            let holds = link.equal(channel, &values, rng)?;
+            let holds = link.additive_shares(channel, &holds, rng)?;
+            for ((r, j), holds) in [first, second].iter().zip(holds.chunks(nbins)) {
+                let weight = if j < r { 1 + nparties as u64 } else { 1 };
+                for (count, hold) in counts[r * nbins..][..nbins].iter_mut().zip(holds) {
+                    *count = count.wrapping_add(weight.wrapping_mul(*hold));
+                }
+            }
+        }
+
+        // Split the counts between parties 0 and 1.
+        if me > 1 {
+            let shares = (0..counts.len()).map(|_| rng.gen::<u64>()).collect_vec();
+            let to_0 = channel(channels, 0);
+            for (count, share) in counts.iter().zip(shares.iter()) {
+                to_0.write_u64(count.wrapping_sub(*share))?;
+            }
+            to_0.flush()?;
+            let to_1 = channel(channels, 1);
+            for share in shares.iter() {
+                to_1.write_u64(*share)?;
+            }
+            to_1.flush()?;
+            return Ok(channel(channels, 0).read_usize()?);
+        }
+        for (other_id, channel) in channels.iter_mut() {
+            if *other_id > 1 {
+                for count in counts.iter_mut() {
+                    *count = count.wrapping_add(channel.read_u64()?);
+                }
+            }
+        }
+
+        // Test whether each count lies between `t - 1` and `n - 1`. At most
+        // one of the tests succeeds, so the XOR of their results tells whether
+        // any does.
+        let targets = (self.threshold as u64 - 1..nparties as u64).collect_vec();
+        let values = counts
+            .iter()
+            .flat_map(|&count| {
+                targets.iter().map(move |&target| {
+                    if me == 0 {
+                        count
+                    } else {
+                        target.wrapping_sub(count)
+                    }
+                })
+            })
+            .collect_vec();
+        let other = if me == 0 { 1 } else { 0 };
+        let channel_num = channels
+            .iter()
+            .position(|(other_id, _)| *other_id == other)
+            .expect("there is a channel to every other party");
+        let channel = &mut channels[channel_num].1;
+        let link = &mut self.links[channel_num];
+        let found = link
+            .equal(channel, &values, rng)?
+            .chunks(targets.len())
+            .map(|bits| bits.iter().fold(false, |acc, bit| acc ^ bit))
+            .collect_vec();
+        let found = link.additive_shares(channel, &found, rng)?;
+        let share = found.iter().fold(0u64, |acc, x| acc.wrapping_add(*x));
+        let other_share = if me == 0 {
+            channel.write_u64(share)?;
+            channel.flush()?;
+            channel.read_u64()?
+        } else {
+            let other_share = channel.read_u64()?;
+            channel.write_u64(share)?;
+            channel.flush()?;
+            other_share
+        };
+        let count = share.wrapping_add(other_share) as usize;
+
+        if me == 0 {
+            for (other_id, channel) in channels.iter_mut() {
+                if *other_id > 1 {
+                    channel.write_usize(count)?;
+                    channel.flush()?;
+                }
+            }
+        }
+        Ok(count)
+    }
+}
+
+impl Link {
+    fn init<C: AbstractChannel, RNG: RngCore + CryptoRng + SeedableRng>(
+        me: PartyId,
+        other_id: PartyId,
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        if me < other_id {
+            let compare = KkrtCompareSender::init(channel, 64, rng)?;
+            let ot = AlszSender::init(channel, rng)?;
+            Ok(Link::Sender(compare, ot))
+        } else {
+            let compare = KkrtCompareReceiver::init(channel, 64, rng)?;
+            let ot = AlszReceiver::init(channel, rng)?;
+            Ok(Link::Receiver(compare, ot))
+        }
+    }
+
+    // Returns XOR shares of whether `values[i]` equals the other party's
+    // `values[i]`.
+    fn equal<C: AbstractChannel, RNG: RngCore + CryptoRng + SeedableRng>(
+        &mut self,
+        channel: &mut C,
+        values: &[u64],
+        rng: &mut RNG,
+    ) -> Result<Vec<bool>, Error> {
+        let shares = match self {
+            Link::Sender(compare, _) => compare.equal(channel, values, rng)?,
+            Link::Receiver(compare, _) => compare.equal(channel, values, rng)?,
+        };
+        Ok(shares)
+    }
+
+    // Converts XOR shares `bits` of bits into additive shares modulo `2^64`.
+    // Writing `a` for the sender's share and `b` for the receiver's, the bit
+    // is `a + b - 2ab`, where the sender gets `-r` and the receiver `r + ab`
+    // as shares of `ab` by offering `(r, r + a)` in an OT.
+    fn additive_shares<C: AbstractChannel, RNG: RngCore + CryptoRng + SeedableRng>(
+        &mut self,
+        channel: &mut C,
+        bits: &[bool],
+        rng: &mut RNG,
+    ) -> Result<Vec<u64>, Error> {
+        match self {
+            Link::Sender(_, ot) => {
+                let masks = (0..bits.len()).map(|_| rng.gen::<u64>()).collect_vec();
+                let messages = bits
+                    .iter()
+                    .zip(masks.iter())
+                    .map(|(&a, &r)| {
+                        let r_ = r.wrapping_add(a as u64);
+                        (Block::from(r as u128), Block::from(r_ as u128))
+                    })
+                    .collect_vec();
+                ot.send(channel, &messages, rng)?;
+                Ok(bits
+                    .iter()
+                    .zip(masks)
+                    .map(|(&a, r)| (a as u64).wrapping_add(r.wrapping_mul(2)))
+                    .collect())
+            }
+            Link::Receiver(_, ot) => {
+                let products = ot.receive(channel, bits, rng)?;
+                Ok(bits
+                    .iter()
+                    .zip(products)
+                    .map(|(&b, p)| (b as u64).wrapping_sub((u128::from(p) as u64).wrapping_mul(2)))
+                    .collect())
+            }
+        }
+    }
+}
+
+// Returns the channel to party `id`.
+fn channel<C>(channels: &mut [(PartyId, C)], id: PartyId) -> &mut C {
+    channels
+        .iter_mut()
+        .find(|(other_id, _)| *other_id == id)
+        .map(|(_, channel)| channel)
+        .expect("there is a channel to every other party")
+}
+
+// Returns the point at which the polynomials are evaluated for party `id`.
+fn point(id: PartyId) -> Block {
+    Block::from(id as u128 + 1)
+}
+
+// A pseudorandom function of `x` and `i`, computed as a two-block CBC-MAC.
+fn prf(key: &Aes128, x: Block, i: usize) -> Block {
+    key.encrypt(key.encrypt(x) ^ Block::from(i as u128))
+}
+
+// Returns `f(z)`, where `f` is the polynomial of degree `threshold - 2` with
+// `f(0) = 0` whose `i`th coefficient is `prf(key, x, i)`.
+fn shamir_share(key: &Aes128, x: Block, z: Block, threshold: usize) -> Block {
+    let mut share = Block::default();
+    let mut power = z;
+    for i in 1..threshold - 1 {
+        share ^= prf(key, x, i).gfmul(power);
+        power = power.gfmul(z);
+    }
+    share
 }
 
 fn secret_sharing_of_zero<R: Rng>(nparties: usize, rng: &mut R) -> Vec<Block512> {
@@ -186,10 +724,14 @@ fn secret_sharing_of_zero<R: Rng>(nparties: usize, rng: &mut R) -> Vec<Block512>
     shares
 }
 
+impl SemiHonest for Sender {}
+impl SemiHonest for Receiver {}
+impl SemiHonest for ThresholdParty {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use rand::Rng;
+    use rand::{seq::SliceRandom, Rng};
     use scuttlebutt::{AesRng, SyncChannel};
     use std::{
         io::{BufReader, BufWriter},
@@ -268,4 +810,133 @@ mod tests {
 
         assert_eq!(res, intersection);
     }
+
+    type Channels = Vec<(
+        PartyId,
+        SyncChannel<BufReader<UnixStream>, BufWriter<UnixStream>>,
+    )>;
+
+    // Returns, for each party, its channels to every other party.
+    fn create_channels(nparties: usize) -> Vec<Channels> {
+        let mut channels = (0..nparties)
+            .map(|_| (0..nparties).map(|_| None).collect_vec())
+            .collect_vec();
+        for i in 0..nparties {
+            for j in 0..nparties {
+                if i != j {
+                    let (s, r) = UnixStream::pair().unwrap();
+                    let left =
+                        SyncChannel::new(BufReader::new(s.try_clone().unwrap()), BufWriter::new(s));
+                    let right =
+                        SyncChannel::new(BufReader::new(r.try_clone().unwrap()), BufWriter::new(r));
+                    channels[i][j] = Some((j, left));
+                    channels[j][i] = Some((i, right));
+                }
+            }
+        }
+        channels
+            .into_iter()
+            .map(|cs| cs.into_iter().flatten().collect_vec())
+            .collect_vec()
+    }
+
+    // Runs `f` on a threshold party for each set in `sets`, returning the
+    // results of each party.
+    fn run_threshold<T, F>(sets: Vec<Vec<Block>>, threshold: usize, f: F) -> Vec<T>
+    where
+        T: Send + 'static,
+        F: Fn(&mut ThresholdParty, &[Block], &mut Channels, &mut AesRng) -> Result<T, Error>
+            + Copy
+            + Send
+            + 'static,
+    {
+        let handles = create_channels(sets.len())
+            .into_iter()
+            .zip(sets)
+            .enumerate()
+            .map(|(pid, (mut channels, set))| {
+                std::thread::spawn(move || {
+                    let mut rng = AesRng::new();
+                    let mut party =
+                        ThresholdParty::init(pid, threshold, &mut channels, &mut rng).unwrap();
+                    f(&mut party, &set, &mut channels, &mut rng).unwrap()
+                })
+            })
+            .collect_vec();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    }
+
+    fn _test_threshold(nparties: usize, threshold: usize) {
+        let mut rng = AesRng::new();
+        let set_size = 1 << 6;
+
+        // Give some elements to every group of at least two parties.
+        let mut sets = vec![Vec::new(); nparties];
+        let mut expected = BTreeSet::new();
+        for holders in (1..=nparties).rev() {
+            for group in (0..nparties).combinations(holders) {
+                let count = if holders == 1 { 0 } else { 2 * holders };
+                for _ in 0..count {
+                    let x = rng.gen::<Block>();
+                    for &p in group.iter() {
+                        sets[p].push(x);
+                    }
+                    if holders >= threshold {
+                        expected.insert(x);
+                    }
+                }
+            }
+        }
+        for set in sets.iter_mut() {
+            while set.len() < set_size {
+                set.push(rng.gen());
+            }
+            set.shuffle(&mut rng);
+        }
+
+        let results = run_threshold(sets.clone(), threshold, |party, set, channels, rng| {
+            party.intersect(set, channels, rng)
+        });
+        for (set, result) in sets.iter().zip(results) {
+            let mine = set
+                .iter()
+                .cloned()
+                .filter(|x| expected.contains(x))
+                .collect_vec();
+            assert_eq!(result, mine);
+        }
+
+        let expected_ = expected.iter().cloned().collect_vec();
+        let results = run_threshold(sets.clone(), threshold, |party, set, channels, rng| {
+            party.intersect_all(set, channels, rng)
+        });
+        for result in results.into_iter() {
+            assert_eq!(result, expected_);
+        }
+
+        let results = run_threshold(sets, threshold, |party, set, channels, rng| {
+            party.cardinality(set, channels, rng)
+        });
+        for result in results.into_iter() {
+            assert_eq!(result, expected.len());
+        }
+    }
+
+    #[test]
+    fn test_threshold() {
+        _test_threshold(4, 3);
+    }
+
+    #[test]
+    fn test_threshold_two() {
+        _test_threshold(4, 2);
+    }
+
+    #[test]
+    fn test_threshold_all() {
+        _test_threshold(4, 4);
+    }
 }
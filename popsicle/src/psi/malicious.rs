@@ -0,0 +1,289 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `popsicle`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Implementation of a maliciously secure two-party private set intersection
+//! protocol based on the verifiable Diffie-Hellman oblivious PRF from
+//! `ocelot::oprf::dh` (cf. <https://eprint.iacr.org/2021/1159>, §3).
+//!
+//! The parties first announce the sizes of their sets, and each checks the
+//! other's size against the bound it was configured with. The sender then
+//! picks a fresh PRF key `k`, commits to it by sending `k · G`, and evaluates
+//! `F(k, ·)` on the receiver's blinded inputs, proving that it used `k` for
+//! every one of them. Finally, the sender sends a tag `F(k, x)` for each `x` in
+//! its set, in random order, and the receiver outputs those of its inputs `y`
+//! for which `F(k, y)` is among the tags.
+//!
+//! A malicious receiver learns `F(k, ·)` on at most as many points as the size
+//! it announced, since the sender evaluates exactly that many, and each tag
+//! of an element it did not query looks random. A malicious sender cannot
+//! make the output depend on anything but a set of at most the size it
+//! announced, since it is bound to a single key by the proof, and a tag
+//! matches `F(k, y)` only if the sender computed it from `y` itself.
+//!
+//! The receiver fails with `Error::CheatingDetected` if the sender's proof
+//! does not verify, if the sender sends malformed group elements, or if its
+//! tags contain duplicates.
+
+use crate::{errors::Error, utils};
+use curve25519_dalek::scalar::Scalar;
+use ocelot::oprf::{DhReceiver, DhSender, Receiver as OprfReceiver, Sender as OprfSender};
+use rand::{seq::SliceRandom, CryptoRng, Rng};
+use scuttlebutt::{AbstractChannel, Block, Malicious, SemiHonest};
+use std::{collections::HashSet, io::ErrorKind};
+
+/// The type of values in the sender and receiver's sets.
+pub type Msg = Vec<u8>;
+
+/// The size of the sender's tags, in bytes.
+const TAG_SIZE: usize = 16;
+
+/// Maliciously secure private set intersection sender.
+pub struct Sender {
+    max_receiver_size: usize,
+}
+
+/// Maliciously secure private set intersection receiver.
+pub struct Receiver {
+    max_sender_size: usize,
+}
+
+// Converts an error from the underlying OPRF into an `Error`, treating any
+// invalid data from the other party as cheating.
+fn check_oprf(e: ocelot::Error) -> Error {
+    match e {
+        ocelot::Error::IoError(e) if e.kind() == ErrorKind::InvalidData => {
+            Error::CheatingDetected(format!("oblivious PRF: {}", e))
+        }
+        e => Error::from(e),
+    }
+}
+
+// Checks that the other party's set size `n` is at most `max`.
+fn check_size(n: usize, max: usize) -> Result<(), Error> {
+    if n > max {
+        return Err(Error::PsiProtocolError(format!(
+            "set size {} exceeds the maximum {}",
+            n, max
+        )));
+    }
+    Ok(())
+}
+
+impl Sender {
+    /// Makes a new PSI sender, which only runs the protocol with receivers
+    /// whose sets contain at most `max_receiver_size` elements.
+    pub fn new(max_receiver_size: usize) -> Self {
+        Self { max_receiver_size }
+    }
+
+    /// Run the PSI protocol over `inputs`.
+    pub fn send<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        inputs: &[Msg],
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<(), Error> {
+        // Duplicates would show up as duplicate tags, so remove them first.
+        let inputs = inputs
+            .iter()
+            .map(|input| utils::compress_input(input))
+            .collect::<HashSet<Block>>();
+
+        let nreceiver = channel.read_usize()?;
+        check_size(nreceiver, self.max_receiver_size)?;
+        channel.write_usize(inputs.len())?;
+        channel.flush()?;
+
+        let mut oprf = DhSender::init_with_key(channel, Scalar::random(rng), true)?;
+        oprf.send(channel, nreceiver, rng)?;
+
+        let mut tags = inputs
+            .into_iter()
+            .map(|input| oprf.compute((), input))
+            .collect::<Vec<_>>();
+        tags.shuffle(rng);
+        for tag in tags.iter() {
+            channel.write_bytes(tag.prefix(TAG_SIZE))?;
+        }
+        channel.flush()?;
+        Ok(())
+    }
+}
+
+impl Receiver {
+    /// Makes a new PSI receiver, which only runs the protocol with senders
+    /// whose sets contain at most `max_sender_size` elements.
+    pub fn new(max_sender_size: usize) -> Self {
+        Self { max_sender_size }
+    }
+
+    /// Run the PSI protocol over `inputs`.
+    pub fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        inputs: &[Msg],
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Vec<Msg>, Error> {
+        channel.write_usize(inputs.len())?;
+        channel.flush()?;
+        let nsender = channel.read_usize()?;
+        check_size(nsender, self.max_sender_size)?;
+
+        // This fails unless the sender commits to its key.
+        let mut oprf = DhReceiver::init(channel, rng).map_err(check_oprf)?;
+        let oprf_inputs = inputs
+            .iter()
+            .map(|input| utils::compress_input(input))
+            .collect::<Vec<Block>>();
+        let outputs = oprf
+            .receive(channel, &oprf_inputs, rng)
+            .map_err(check_oprf)?;
+
+        let mut tags = HashSet::with_capacity(nsender);
+        for _ in 0..nsender {
+            let tag = channel.read_vec(TAG_SIZE)?;
+            if !tags.insert(tag) {
+                return Err(Error::CheatingDetected("duplicate tag".to_string()));
+            }
+        }
+
+        let intersection = inputs
+            .iter()
+            .zip(outputs.iter())
+            .filter(|(_, output)| tags.contains(output.prefix(TAG_SIZE)))
+            .map(|(input, _)| input.clone())
+            .collect();
+        Ok(intersection)
+    }
+}
+
+impl SemiHonest for Sender {}
+impl Malicious for Sender {}
+impl SemiHonest for Receiver {}
+impl Malicious for Receiver {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::rand_vec_vec;
+    use scuttlebutt::{AesRng, Channel};
+    use std::{
+        io::{BufReader, BufWriter},
+        os::unix::net::UnixStream,
+    };
+
+    const ITEM_SIZE: usize = 8;
+    const SET_SIZE: usize = 1 << 8;
+
+    fn run_receiver<F>(inputs: &[Msg], max_sender_size: usize, sender: F) -> Result<Vec<Msg>, Error>
+    where
+        F: FnOnce(&mut Channel<BufReader<UnixStream>, BufWriter<UnixStream>>) + Send + 'static,
+    {
+        let (sender_, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let reader = BufReader::new(sender_.try_clone().unwrap());
+            let writer = BufWriter::new(sender_);
+            let mut channel = Channel::new(reader, writer);
+            sender(&mut channel);
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut psi = Receiver::new(max_sender_size);
+        let result = psi.receive(inputs, &mut channel, &mut rng);
+        drop(channel);
+        handle.join().unwrap();
+        result
+    }
+
+    #[test]
+    fn test_psi() {
+        let mut rng = AesRng::new();
+        let sender_inputs = rand_vec_vec(SET_SIZE, ITEM_SIZE, &mut rng);
+        let mut receiver_inputs = rand_vec_vec(SET_SIZE / 2, ITEM_SIZE, &mut rng);
+        receiver_inputs[..SET_SIZE / 4]
+            .clone_from_slice(&sender_inputs[SET_SIZE / 2..][..SET_SIZE / 4]);
+        let intersection = run_receiver(&receiver_inputs, SET_SIZE, move |channel| {
+            let mut rng = AesRng::new();
+            let mut psi = Sender::new(SET_SIZE / 2);
+            psi.send(&sender_inputs, channel, &mut rng).unwrap();
+        })
+        .unwrap();
+        assert_eq!(intersection, receiver_inputs[..SET_SIZE / 4]);
+    }
+
+    #[test]
+    fn test_set_size_bounds() {
+        let mut rng = AesRng::new();
+        let sender_inputs = rand_vec_vec(SET_SIZE, ITEM_SIZE, &mut rng);
+        let receiver_inputs = rand_vec_vec(SET_SIZE, ITEM_SIZE, &mut rng);
+        // The sender refuses a receiver with too many inputs...
+        let sender_inputs_ = sender_inputs.clone();
+        let result = run_receiver(&receiver_inputs, SET_SIZE, move |channel| {
+            let mut rng = AesRng::new();
+            let mut psi = Sender::new(SET_SIZE - 1);
+            match psi.send(&sender_inputs_, channel, &mut rng) {
+                Err(Error::PsiProtocolError(_)) => (),
+                _ => panic!("receiver set size not enforced"),
+            }
+        });
+        assert!(result.is_err());
+        // ... and vice versa.
+        let result = run_receiver(&receiver_inputs, SET_SIZE - 1, move |channel| {
+            let mut rng = AesRng::new();
+            let mut psi = Sender::new(SET_SIZE);
+            assert!(psi.send(&sender_inputs, channel, &mut rng).is_err());
+        });
+        match result {
+            Err(Error::PsiProtocolError(_)) => (),
+            _ => panic!("sender set size not enforced"),
+        }
+    }
+
+    // Runs the sender's side of the protocol up to the tags, using key `key`
+    // for the commitment and key `key_` to evaluate the PRF.
+    fn cheating_sender<C: AbstractChannel>(channel: &mut C, key: Scalar, key_: Scalar) -> DhSender {
+        let mut rng = AesRng::new();
+        let nreceiver = channel.read_usize().unwrap();
+        channel.write_usize(2).unwrap();
+        channel.flush().unwrap();
+        DhSender::init_with_key(channel, key, true).unwrap();
+        let mut oprf = DhSender::new(key_, true);
+        oprf.send(channel, nreceiver, &mut rng).unwrap();
+        oprf
+    }
+
+    #[test]
+    fn test_cheating_sender() {
+        let mut rng = AesRng::new();
+        let receiver_inputs = rand_vec_vec(SET_SIZE, ITEM_SIZE, &mut rng);
+        // A sender that evaluates the PRF with another key than the one it
+        // committed to is caught by the proof.
+        let result = run_receiver(&receiver_inputs, SET_SIZE, |channel| {
+            let mut rng = AesRng::new();
+            cheating_sender(channel, Scalar::random(&mut rng), Scalar::random(&mut rng));
+        });
+        match result {
+            Err(Error::CheatingDetected(_)) => (),
+            _ => panic!("inconsistent key not detected"),
+        }
+        // A sender that sends the same tag twice is caught as well.
+        let input = receiver_inputs[0].clone();
+        let result = run_receiver(&receiver_inputs, SET_SIZE, move |channel| {
+            let key = Scalar::random(&mut AesRng::new());
+            let oprf = cheating_sender(channel, key, key);
+            let tag = oprf.compute((), utils::compress_input(&input));
+            channel.write_bytes(tag.prefix(TAG_SIZE)).unwrap();
+            channel.write_bytes(tag.prefix(TAG_SIZE)).unwrap();
+            channel.flush().unwrap();
+        });
+        match result {
+            Err(Error::CheatingDetected(_)) => (),
+            _ => panic!("duplicate tags not detected"),
+        }
+    }
+}
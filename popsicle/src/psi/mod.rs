@@ -4,10 +4,16 @@
 // Copyright © 2019 Galois, Inc.
 // See LICENSE for licensing information.
 
+pub mod cardinality;
 pub mod kmprt;
+pub mod malicious;
+pub mod mutual;
 #[cfg(feature = "psty")]
 pub mod psty;
+pub mod psu;
 pub mod psz;
+pub mod stream;
+pub mod unbalanced;
 
 /// Private set intersection sender.
 pub type Sender = psz::Sender;
@@ -25,3 +31,31 @@ pub type ExtendedReceiver = psty::Receiver;
 pub type MultiPartySender = kmprt::Sender;
 /// Multi-party private set intersection receiver.
 pub type MultiPartyReceiver = kmprt::Receiver;
+/// Multi-party over-threshold private set intersection party.
+pub type ThresholdParty = kmprt::ThresholdParty;
+
+/// Maliciously secure private set intersection sender.
+pub type MaliciousSender = malicious::Sender;
+/// Maliciously secure private set intersection receiver.
+pub type MaliciousReceiver = malicious::Receiver;
+
+/// Unbalanced private set intersection sender.
+pub type UnbalancedSender = unbalanced::Sender;
+/// Unbalanced private set intersection receiver.
+pub type UnbalancedReceiver = unbalanced::Receiver;
+
+/// Private set union sender.
+pub type UnionSender = psu::Sender;
+/// Private set union receiver.
+pub type UnionReceiver = psu::Receiver;
+
+/// Streaming private set intersection sender.
+pub type StreamingSender = stream::Sender<psz::Sender>;
+/// Streaming private set intersection receiver.
+pub type StreamingReceiver = stream::Receiver<psz::Receiver>;
+#[cfg(feature = "psty")]
+/// Streaming extended private set intersection sender.
+pub type ExtendedStreamingSender = stream::Sender<psty::Sender>;
+#[cfg(feature = "psty")]
+/// Streaming extended private set intersection receiver.
+pub type ExtendedStreamingReceiver = stream::Receiver<psty::Receiver>;
@@ -0,0 +1,244 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `popsicle`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Mutual output for the two-party PSI protocols, and a consistency check of
+//! the result.
+//!
+//! In PSZ and PSTY only the receiver learns the intersection. In the mutual
+//! modes (`psz::Sender::send_mutual` and `psz::Receiver::receive_mutual`, and
+//! `compute_intersection_mutual` in `psty`), the receiver then reveals its
+//! result to the sender: the parties toss a fresh hash key, and the receiver
+//! sends the hashes of its elements in the intersection, in sorted order, so
+//! that the sender can find them among its own inputs. Both parties end up
+//! with the indices of the intersection in their own inputs. Since the sender
+//! already holds the elements it is told about, it learns nothing beyond the
+//! intersection, but it has to trust the receiver to report it faithfully.
+//!
+//! `verify` checks that both parties derived the same result, using
+//! commitments: each party commits to the sorted elements of its result, and
+//! only opens its commitment once it holds the other party's, so that neither
+//! party can simply echo the other. The check is only as good as the values
+//! it compares, though: the sender's result is derived entirely from the
+//! receiver's message, so `verify` catches a party whose final result differs
+//! from what was revealed, but it does **not** detect a receiver that omits
+//! elements of the intersection when revealing it. Hashes the sender does not
+//! find among its inputs, or which are revealed more than once, are rejected
+//! by the sender outright, with `Error::CheatingDetected`.
+
+use crate::{errors::Error, utils};
+use rand::{CryptoRng, Rng, RngCore};
+use scuttlebutt::{
+    cointoss,
+    commitment::{Commitment, ShaCommitment},
+    AbstractChannel,
+    Block,
+};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+// Reveal the receiver's result, given as the `indices` of the intersection in
+// its `inputs`, to the sender.
+pub(crate) fn reveal<C: AbstractChannel, RNG: CryptoRng + RngCore>(
+    inputs: &[Vec<u8>],
+    indices: &[usize],
+    channel: &mut C,
+    rng: &mut RNG,
+) -> Result<(), Error> {
+    let key = cointoss::receive(channel, &[rng.gen()])?[0];
+    let intersection = indices
+        .iter()
+        .map(|&i| inputs[i].clone())
+        .collect::<Vec<_>>();
+    let mut hashes = utils::compress_and_hash_inputs(&intersection, key);
+    hashes.sort_unstable_by_key(|h| u128::from(*h));
+    // Equal inputs hash alike, and the sender only accepts each hash once.
+    hashes.dedup();
+    channel.write_usize(hashes.len())?;
+    for h in hashes.iter() {
+        channel.write_block(h)?;
+    }
+    channel.flush()?;
+    Ok(())
+}
+
+// Learn the receiver's result, as the sorted indices of the intersection in
+// the sender's `inputs`. Fails if the receiver reveals an element we do not
+// hold, or the same element twice.
+pub(crate) fn learn<C: AbstractChannel, RNG: CryptoRng + RngCore>(
+    inputs: &[Vec<u8>],
+    channel: &mut C,
+    rng: &mut RNG,
+) -> Result<Vec<usize>, Error> {
+    let key = cointoss::send(channel, &[rng.gen()])?[0];
+    let positions = utils::compress_and_hash_inputs(inputs, key)
+        .into_iter()
+        .enumerate()
+        .map(|(i, h)| (h, i))
+        .collect::<HashMap<Block, usize>>();
+    let n = channel.read_usize()?;
+    let mut indices = Vec::with_capacity(std::cmp::min(n, inputs.len()));
+    let mut seen = HashSet::with_capacity(std::cmp::min(n, inputs.len()));
+    for _ in 0..n {
+        let h = channel.read_block()?;
+        match positions.get(&h) {
+            Some(i) => indices.push(*i),
+            None => {
+                return Err(Error::CheatingDetected(
+                    "the receiver revealed an element outside our set".to_string(),
+                ))
+            }
+        }
+        if !seen.insert(h) {
+            return Err(Error::CheatingDetected(
+                "the receiver revealed an element twice".to_string(),
+            ));
+        }
+    }
+    indices.sort_unstable();
+    Ok(indices)
+}
+
+/// Check that the other party derived the same intersection, given as the
+/// `indices` of its elements in `inputs`. Both parties call this after a
+/// mutual PSI protocol, each with its own inputs and result, and fail with
+/// `Error::CheatingDetected` if the results differ.
+///
+/// This does not detect omissions: a receiver that leaves elements out of the
+/// result it reveals, and then verifies that same result, passes the check.
+pub fn verify<C: AbstractChannel, RNG: CryptoRng + Rng>(
+    inputs: &[Vec<u8>],
+    indices: &[usize],
+    channel: &mut C,
+    rng: &mut RNG,
+) -> Result<(), Error> {
+    let elements = indices
+        .iter()
+        .map(|&i| inputs[i].as_slice())
+        .collect::<BTreeSet<&[u8]>>();
+    let commit = |seed| {
+        let mut commitment = ShaCommitment::new(seed);
+        commitment.input(&(elements.len() as u64).to_le_bytes());
+        for element in elements.iter() {
+            commitment.input(&(element.len() as u64).to_le_bytes());
+            commitment.input(element);
+        }
+        commitment.finish()
+    };
+
+    let seed = rng.gen::<[u8; 32]>();
+    channel.write_bytes(&commit(seed))?;
+    channel.flush()?;
+    let mut commitment = [0u8; 32];
+    channel.read_bytes(&mut commitment)?;
+    channel.write_bytes(&seed)?;
+    channel.flush()?;
+    let mut seed_ = [0u8; 32];
+    channel.read_bytes(&mut seed_)?;
+    if !ShaCommitment::check(&commitment, &commit(seed_)) {
+        return Err(Error::CheatingDetected(
+            "the parties derived different intersections".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scuttlebutt::{AesRng, Channel};
+    use std::{
+        io::{BufReader, BufWriter},
+        os::unix::net::UnixStream,
+    };
+
+    // Run `verify` with the given inputs and results of each party.
+    fn run(
+        inputs: Vec<Vec<u8>>,
+        indices: Vec<usize>,
+        inputs_: Vec<Vec<u8>>,
+        indices_: Vec<usize>,
+    ) -> (Result<(), Error>, Result<(), Error>) {
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            verify(&inputs, &indices, &mut channel, &mut rng)
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let result = verify(&inputs_, &indices_, &mut channel, &mut rng);
+        (handle.join().unwrap(), result)
+    }
+
+    #[test]
+    fn test_verify() {
+        let inputs = vec![vec![1], vec![2], vec![3]];
+        let inputs_ = vec![vec![3], vec![4], vec![1]];
+        // The same elements, at different positions.
+        let (result, result_) = run(inputs.clone(), vec![0, 2], inputs_.clone(), vec![0, 2]);
+        assert!(result.is_ok() && result_.is_ok());
+        // `[2]` is not in the other party's result.
+        match run(inputs, vec![0, 1, 2], inputs_, vec![0, 2]) {
+            (Err(Error::CheatingDetected(_)), Err(Error::CheatingDetected(_))) => (),
+            _ => panic!("different results not detected"),
+        }
+    }
+
+    #[test]
+    fn test_learn_duplicate_element() {
+        let inputs = vec![vec![1], vec![2], vec![3]];
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        // A cheating receiver reveals `[1]` twice, to inflate the result.
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let key = cointoss::receive(&mut channel, &[rng.gen()]).unwrap()[0];
+            let h = utils::compress_and_hash_inputs(&[vec![1]], key)[0];
+            channel.write_usize(2).unwrap();
+            channel.write_block(&h).unwrap();
+            channel.write_block(&h).unwrap();
+            channel.flush().unwrap();
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        match learn(&inputs, &mut channel, &mut rng) {
+            Err(Error::CheatingDetected(_)) => (),
+            _ => panic!("duplicate element not detected"),
+        }
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_learn_unknown_element() {
+        let inputs = vec![vec![1], vec![2], vec![3]];
+        let inputs_ = vec![vec![3], vec![4], vec![1]];
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        // A cheating receiver reveals `[4]`, which the sender does not hold.
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            reveal(&inputs_, &[0, 1], &mut channel, &mut rng).unwrap();
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        match learn(&inputs, &mut channel, &mut rng) {
+            Err(Error::CheatingDetected(_)) => (),
+            _ => panic!("unknown element not detected"),
+        }
+        handle.join().unwrap();
+    }
+}
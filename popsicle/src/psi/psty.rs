@@ -6,16 +6,36 @@
 
 //! Implementation of the Pinkas-Schneider-Tkachenko-Yanai "extended" private
 //! set intersection protocol (cf. <https://eprint.iacr.org/2019/241>).
+//!
+//! Besides computing the intersection or its cardinality, the parties can run
+//! circuit PSI: `SenderState::compute_circuit` and
+//! `ReceiverState::compute_circuit` give a user-supplied function the garbled
+//! wires of each of the receiver's cuckoo bins (see `CircuitBin`), namely
+//! whether the receiver's item in that bin is in the intersection, alongside
+//! the payloads both parties associate with that item, and reveal only the
+//! output of that function to the receiver. Alternatively, `compute_shares`
+//! hands each party XOR shares of these values, for use in some other secure
+//! computation. In the mutual mode (`compute_intersection_mutual`), the
+//! receiver reveals the intersection to the sender afterwards, so that both
+//! parties learn it (see `mutual`).
+//!
+//! The sender's payloads are programmed into the OPPRF: for an item `x` in bin
+//! `j`, the sender programs `t_j ⊕ (0 || p(x))` rather than the bin's target
+//! value `t_j`, so the receiver learns `p(x)` masked by `t_j` if and only if its
+//! item in bin `j` is `x`, and the circuit removes the mask.
 
-use crate::{cuckoo::CuckooHash, errors::Error, utils};
+use crate::{cuckoo::CuckooHash, errors::Error, psi::mutual, utils};
 use fancy_garbling::{
+    errors::TwopacError,
     twopac::semihonest::{Evaluator, Garbler},
     BinaryBundle,
+    BinaryGadgets,
     BundleGadgets,
     CrtBundle,
     CrtGadgets,
     Fancy,
     FancyInput,
+    HasModulus,
     Wire,
 };
 use itertools::Itertools;
@@ -25,6 +45,8 @@ use ocelot::{
 };
 use openssl::symm::{decrypt, encrypt, Cipher};
 use rand::{CryptoRng, Rng, RngCore, SeedableRng};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use scuttlebutt::{AbstractChannel, Block, Block512, SemiHonest};
 
 const NHASHES: usize = 3;
@@ -40,6 +62,41 @@ const PAD_LEN: usize = 16;
 /// The type of values in the sender and receiver's sets.
 pub type Msg = Vec<u8>;
 
+/// The maximum length of the sender's payloads in circuit PSI, in bytes.
+pub const MAX_PAYLOAD_LEN: usize = 64 - HASH_SIZE;
+
+/// The wires of one of the receiver's cuckoo bins in circuit PSI.
+///
+/// Payloads are given as bits, starting with the least significant bit of the
+/// first byte.
+pub struct CircuitBin<W: Clone + HasModulus> {
+    /// One if the receiver's item in this bin is in the intersection, and zero
+    /// otherwise (including when the bin is empty).
+    pub member: W,
+    /// The sender's payload associated with the receiver's item in this bin.
+    /// This is garbage unless `member` is one.
+    pub sender_payload: BinaryBundle<W>,
+    /// The receiver's payload associated with its item in this bin, or zero if
+    /// the bin is empty.
+    pub receiver_payload: BinaryBundle<W>,
+}
+
+/// One party's XOR shares of the values of one of the receiver's cuckoo bins
+/// in circuit PSI (see `CircuitBin`).
+///
+/// Payloads are packed into bytes, starting with the least significant bit of
+/// the first byte.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BinShares {
+    /// Share of whether the receiver's item in this bin is in the
+    /// intersection.
+    pub member: bool,
+    /// Share of the sender's payload.
+    pub sender_payload: Vec<u8>,
+    /// Share of the receiver's payload.
+    pub receiver_payload: Vec<u8>,
+}
+
 /// Private set intersection sender.
 pub struct Sender {
     opprf: KmprtSender,
@@ -48,6 +105,7 @@ pub struct Sender {
 /// State of the sender.
 pub struct SenderState {
     opprf_outputs: Vec<Block512>,
+    payload_len: usize,
 }
 
 /// Private set intersection receiver.
@@ -79,6 +137,41 @@ impl Sender {
         channel: &mut C,
         rng: &mut RNG,
     ) -> Result<SenderState, Error> {
+        self.send_(inputs, None, channel, rng)
+    }
+
+    /// Run the PSI protocol over `inputs`, associating `payloads[i]` with
+    /// `inputs[i]` in circuit PSI. The payloads must all have the same length,
+    /// of at most `MAX_PAYLOAD_LEN` bytes.
+    ///
+    /// The resulting state cannot be used with `receive_payloads`.
+    pub fn send_with_payloads<C: AbstractChannel, RNG: RngCore + CryptoRng + SeedableRng>(
+        &mut self,
+        inputs: &[Msg],
+        payloads: &[Msg],
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<SenderState, Error> {
+        self.send_(inputs, Some(payloads), channel, rng)
+    }
+
+    fn send_<C: AbstractChannel, RNG: RngCore + CryptoRng + SeedableRng>(
+        &mut self,
+        inputs: &[Msg],
+        payloads: Option<&[Msg]>,
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<SenderState, Error> {
+        let payload_len = match payloads {
+            Some(payloads) => check_payloads(inputs.len(), payloads)?,
+            None => 0,
+        };
+        if payload_len > MAX_PAYLOAD_LEN {
+            return Err(Error::PsiProtocolError(format!(
+                "payloads are at most {} bytes, got {}",
+                MAX_PAYLOAD_LEN, payload_len
+            )));
+        }
         // receive cuckoo hash info from sender
         let key = channel.read_block()?;
         let hashes = utils::compress_and_hash_inputs(inputs, key);
@@ -87,17 +180,23 @@ impl Sender {
         let nbins = channel.read_usize()?;
         let mut table = vec![Vec::new(); nbins];
 
-        for &x in &hashes {
-            let mut bins = Vec::with_capacity(NHASHES);
-            for h in 0..NHASHES {
-                let bin = CuckooHash::bin(x, h, nbins);
-                table[bin].push(x ^ Block::from(h as u128));
-                bins.push(bin);
+        let bins = utils::iter(&hashes)
+            .map(|&x| {
+                let mut bins = [0; NHASHES];
+                for (h, bin) in bins.iter_mut().enumerate() {
+                    *bin = CuckooHash::bin(x, h, nbins);
+                }
+                bins
+            })
+            .collect::<Vec<_>>();
+        for (i, (&x, bins)) in hashes.iter().zip(bins.iter()).enumerate() {
+            for (h, &bin) in bins.iter().enumerate() {
+                table[bin].push((x ^ Block::from(h as u128), Some(i)));
             }
             // if j = H1(y) = H2(y) for some y, then P2 adds a uniformly random element to
             // table2[j].
             if bins.iter().skip(1).all(|&x| x == bins[0]) {
-                table[bins[0]].push(rng.gen());
+                table[bins[0]].push((rng.gen(), None));
             }
         }
 
@@ -108,14 +207,24 @@ impl Sender {
             .into_iter()
             .zip_eq(ts.iter())
             .flat_map(|(bin, t)| {
-                // map all the points in a bin to the same tag
-                bin.into_iter().map(move |item| (item, *t))
+                // map all the points in a bin to the same tag, masking the
+                // payload of each point with that tag
+                bin.into_iter().map(move |(item, i)| {
+                    let mut value = [0u8; 64];
+                    if let (Some(payloads), Some(i)) = (payloads, i) {
+                        value[HASH_SIZE..HASH_SIZE + payload_len].copy_from_slice(&payloads[i]);
+                    }
+                    (item, *t ^ Block512::from(value))
+                })
             })
             .collect_vec();
 
         self.opprf.send(channel, &points, nbins, rng)?;
 
-        Ok(SenderState { opprf_outputs: ts })
+        Ok(SenderState {
+            opprf_outputs: ts,
+            payload_len,
+        })
     }
 }
 
@@ -144,10 +253,24 @@ impl SenderState {
         C: AbstractChannel,
         RNG: RngCore + CryptoRng + SeedableRng<Seed = Block>,
     {
-        let (mut gb, x, y) = self.compute_setup(channel, rng)?;
-        let outs = fancy_compute_intersection(&mut gb, &x, &y)?;
-        gb.outputs(&outs)?;
-        Ok(())
+        self.circuit(channel, rng, 0, 0, |_, bins| Ok(members(bins)))
+    }
+
+    /// Compute the intersection, and learn it from the receiver (see
+    /// `mutual`). `inputs` must be the inputs this state was computed from.
+    /// Returns the sorted indices of the intersection in `inputs`.
+    pub fn compute_intersection_mutual<C, RNG>(
+        &self,
+        inputs: &[Msg],
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Vec<usize>, Error>
+    where
+        C: AbstractChannel,
+        RNG: RngCore + CryptoRng + SeedableRng<Seed = Block>,
+    {
+        self.compute_intersection(channel, rng)?;
+        mutual::learn(inputs, channel, rng)
     }
 
     /// Compute the cardinality of the intersection.
@@ -156,13 +279,107 @@ impl SenderState {
         C: AbstractChannel,
         RNG: RngCore + CryptoRng + SeedableRng<Seed = Block>,
     {
-        let (mut gb, x, y) = self.compute_setup(channel, rng)?;
-        let (outs, _) = fancy_compute_cardinality(&mut gb, &x, &y)?;
+        self.circuit(channel, rng, 0, 0, |gb, bins| {
+            let (outs, _) = fancy_compute_cardinality(gb, &members(bins))?;
+            Ok(outs)
+        })
+    }
+
+    /// Run circuit PSI, calling `f` on the garbler and the wires of each of
+    /// the receiver's cuckoo bins. The values of the output wires returned by
+    /// `f` are revealed to the receiver, and nothing else is. The parties
+    /// start by exchanging the lengths of their payloads, so the receiver
+    /// must call `ReceiverState::compute_circuit` too.
+    pub fn compute_circuit<C, RNG, F>(
+        &self,
+        channel: &mut C,
+        rng: &mut RNG,
+        f: F,
+    ) -> Result<(), Error>
+    where
+        C: AbstractChannel,
+        RNG: RngCore + CryptoRng + SeedableRng<Seed = Block>,
+        F: FnOnce(
+            &mut Garbler<C, RNG, OtSender>,
+            &[CircuitBin<Wire>],
+        ) -> Result<Vec<Wire>, TwopacError>,
+    {
+        // Exchange payload lengths.
+        channel.write_usize(self.payload_len)?;
+        channel.flush()?;
+        let receiver_payload_len = channel.read_usize()?;
+        self.circuit(channel, rng, self.payload_len, receiver_payload_len, f)
+    }
+
+    // Run circuit PSI with payloads of `payload_len` bytes from the sender
+    // and `receiver_payload_len` bytes from the receiver, both of which the
+    // receiver must use too.
+    fn circuit<C, RNG, F>(
+        &self,
+        channel: &mut C,
+        rng: &mut RNG,
+        payload_len: usize,
+        receiver_payload_len: usize,
+        f: F,
+    ) -> Result<(), Error>
+    where
+        C: AbstractChannel,
+        RNG: RngCore + CryptoRng + SeedableRng<Seed = Block>,
+        F: FnOnce(
+            &mut Garbler<C, RNG, OtSender>,
+            &[CircuitBin<Wire>],
+        ) -> Result<Vec<Wire>, TwopacError>,
+    {
+        let mut gb = Garbler::<C, RNG, OtSender>::new(channel.clone(), RNG::from_seed(rng.gen()))?;
+        let nbytes = HASH_SIZE + payload_len;
+        let my_input_bits = encode_bytes(self.opprf_outputs.iter().map(|t| t.prefix(nbytes)));
+        let mods = vec![2; my_input_bits.len()];
+        let sender_inputs = gb.encode_many(&my_input_bits, &mods)?;
+        let mods = vec![2; self.opprf_outputs.len() * (nbytes + receiver_payload_len) * 8];
+        let receiver_inputs = gb.receive_many(&mods)?;
+        let bins = fancy_circuit_bins(
+            &mut gb,
+            &sender_inputs,
+            &receiver_inputs,
+            payload_len,
+            receiver_payload_len,
+        )?;
+        let outs = f(&mut gb, &bins)?;
         gb.outputs(&outs)?;
+        channel.flush()?;
         Ok(())
     }
 
+    /// Run circuit PSI, returning the sender's XOR shares of the values of
+    /// each of the receiver's cuckoo bins.
+    pub fn compute_shares<C, RNG>(
+        &self,
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Vec<BinShares>, Error>
+    where
+        C: AbstractChannel,
+        RNG: RngCore + CryptoRng + SeedableRng<Seed = Block>,
+    {
+        let mut mask_rng = RNG::from_seed(rng.gen());
+        let mut shares = Vec::new();
+        self.compute_circuit(channel, rng, |gb, bins| {
+            let (wires, sender_bits, receiver_bits) = flatten_bins(bins);
+            let masks = (0..wires.len())
+                .map(|_| u16::from(mask_rng.gen::<bool>()))
+                .collect::<Vec<u16>>();
+            let mask_wires = gb.encode_many(&masks, &vec![2; masks.len()])?;
+            shares = split_shares(&masks, sender_bits, receiver_bits);
+            xor_wires(gb, &wires, &mask_wires)
+        })?;
+        Ok(shares)
+    }
+
     /// Receive encrypted payloads from the Sender.
+    ///
+    /// Fails if this state was computed by `send_with_payloads`, whose OPPRF
+    /// outputs carry the sender's own payloads and cannot decrypt the
+    /// receiver's.
     pub fn receive_payloads<C>(
         &self,
         payload_len: usize,
@@ -171,6 +388,11 @@ impl SenderState {
     where
         C: AbstractChannel,
     {
+        if self.payload_len > 0 {
+            return Err(Error::PsiProtocolError(
+                "cannot receive payloads in a state computed with sender payloads".to_string(),
+            ));
+        }
         let mut payloads = Vec::new();
         for opprf_output in self.opprf_outputs.iter() {
             let iv = channel.read_vec(16)?;
@@ -266,17 +488,44 @@ impl ReceiverState {
         C: AbstractChannel,
         RNG: RngCore + CryptoRng + SeedableRng<Seed = Block>,
     {
-        let (mut ev, x, y) = self.compute_setup(channel, rng)?;
-        let outs = fancy_compute_intersection(&mut ev, &x, &y)?;
-        let mpc_outs = ev
-            .outputs(&outs)?
-            .expect("evaluator should produce outputs");
+        let indices = self.intersect(channel, rng)?;
+        Ok(indices
+            .into_iter()
+            .map(|i| self.inputs[i].clone())
+            .collect())
+    }
+
+    /// Compute the intersection, and reveal it to the sender (see `mutual`).
+    /// Returns the sorted indices of the intersection in the inputs.
+    pub fn compute_intersection_mutual<C, RNG>(
+        &self,
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Vec<usize>, Error>
+    where
+        C: AbstractChannel,
+        RNG: RngCore + CryptoRng + SeedableRng<Seed = Block>,
+    {
+        let mut indices = self.intersect(channel, rng)?;
+        indices.sort_unstable();
+        mutual::reveal(&self.inputs, &indices, channel, rng)?;
+        Ok(indices)
+    }
+
+    // Compute the indices of the intersection in the inputs, in the order of
+    // the cuckoo bins.
+    fn intersect<C, RNG>(&self, channel: &mut C, rng: &mut RNG) -> Result<Vec<usize>, Error>
+    where
+        C: AbstractChannel,
+        RNG: RngCore + CryptoRng + SeedableRng<Seed = Block>,
+    {
+        let mpc_outs = self.circuit(channel, &[], rng, 0, |_, bins| Ok(members(bins)))?;
 
         let mut intersection = Vec::new();
-        for (opt_item, in_intersection) in self.cuckoo.items.iter().zip_eq(mpc_outs.into_iter()) {
+        for (opt_item, in_intersection) in self.cuckoo.items.iter().zip_eq(mpc_outs) {
             if let Some(item) = opt_item {
                 if in_intersection == 1_u16 {
-                    intersection.push(self.inputs[item.input_index].clone());
+                    intersection.push(item.input_index);
                 }
             }
         }
@@ -293,14 +542,137 @@ impl ReceiverState {
         C: AbstractChannel,
         RNG: RngCore + CryptoRng + SeedableRng<Seed = Block>,
     {
-        let (mut ev, x, y) = self.compute_setup(channel, rng)?;
-        let (outs, mods) = fancy_compute_cardinality(&mut ev, &x, &y)?;
+        let mut mods = Vec::new();
+        let mpc_outs = self.circuit(channel, &[], rng, 0, |ev, bins| {
+            let (outs, mods_) = fancy_compute_cardinality(ev, &members(bins))?;
+            mods = mods_;
+            Ok(outs)
+        })?;
+
+        let cardinality = fancy_garbling::util::crt_inv(&mpc_outs, &mods);
+        Ok(cardinality as usize)
+    }
+
+    /// Run circuit PSI, calling `f` on the evaluator and the wires of each of
+    /// the cuckoo bins, and returning the values of the output wires returned
+    /// by `f`. If `payloads` is non-empty, `payloads[i]` is associated with the
+    /// `i`th input, and the payloads must all have the same length.
+    ///
+    /// Use `bin_indices` to map the bins back to the inputs. The parties
+    /// start by exchanging the lengths of their payloads, so the sender must
+    /// call `SenderState::compute_circuit` too.
+    pub fn compute_circuit<C, RNG, F>(
+        &self,
+        channel: &mut C,
+        payloads: &[Msg],
+        rng: &mut RNG,
+        f: F,
+    ) -> Result<Vec<u16>, Error>
+    where
+        C: AbstractChannel,
+        RNG: RngCore + CryptoRng + SeedableRng<Seed = Block>,
+        F: FnOnce(
+            &mut Evaluator<C, RNG, OtReceiver>,
+            &[CircuitBin<Wire>],
+        ) -> Result<Vec<Wire>, TwopacError>,
+    {
+        let payload_len = if payloads.is_empty() {
+            0
+        } else {
+            check_payloads(self.inputs.len(), payloads)?
+        };
+        // Exchange payload lengths.
+        let sender_payload_len = channel.read_usize()?;
+        channel.write_usize(payload_len)?;
+        channel.flush()?;
+        self.circuit(channel, payloads, rng, sender_payload_len, f)
+    }
+
+    // Run circuit PSI with `payloads` as in `compute_circuit` and payloads of
+    // `sender_payload_len` bytes from the sender, which the sender must use
+    // too.
+    fn circuit<C, RNG, F>(
+        &self,
+        channel: &mut C,
+        payloads: &[Msg],
+        rng: &mut RNG,
+        sender_payload_len: usize,
+        f: F,
+    ) -> Result<Vec<u16>, Error>
+    where
+        C: AbstractChannel,
+        RNG: RngCore + CryptoRng + SeedableRng<Seed = Block>,
+        F: FnOnce(
+            &mut Evaluator<C, RNG, OtReceiver>,
+            &[CircuitBin<Wire>],
+        ) -> Result<Vec<Wire>, TwopacError>,
+    {
+        let payload_len = payloads.first().map_or(0, |p| p.len());
+        let mut ev =
+            Evaluator::<C, RNG, OtReceiver>::new(channel.clone(), RNG::from_seed(rng.gen()))?;
+        let nbytes = HASH_SIZE + sender_payload_len;
+        let empty = vec![0u8; payload_len];
+        let my_inputs = self
+            .cuckoo
+            .items
+            .iter()
+            .zip_eq(self.opprf_outputs.iter())
+            .flat_map(|(opt_item, opprf_output)| {
+                let payload = match opt_item {
+                    Some(item) if payload_len > 0 => &payloads[item.input_index],
+                    _ => &empty,
+                };
+                std::iter::once(opprf_output.prefix(nbytes)).chain(std::iter::once(&payload[..]))
+            });
+        let my_input_bits = encode_bytes(my_inputs);
+        let mods = vec![2; self.cuckoo.nbins * nbytes * 8];
+        let sender_inputs = ev.receive_many(&mods)?;
+        let mods = vec![2; my_input_bits.len()];
+        let receiver_inputs = ev.encode_many(&my_input_bits, &mods)?;
+        let bins = fancy_circuit_bins(
+            &mut ev,
+            &sender_inputs,
+            &receiver_inputs,
+            sender_payload_len,
+            payload_len,
+        )?;
+        let outs = f(&mut ev, &bins)?;
         let mpc_outs = ev
             .outputs(&outs)?
             .expect("evaluator should produce outputs");
+        Ok(mpc_outs)
+    }
 
-        let cardinality = fancy_garbling::util::crt_inv(&mpc_outs, &mods);
-        Ok(cardinality as usize)
+    /// Run circuit PSI, returning the receiver's XOR shares of the values of
+    /// each of the cuckoo bins. `payloads` is as in `compute_circuit`.
+    pub fn compute_shares<C, RNG>(
+        &self,
+        channel: &mut C,
+        payloads: &[Msg],
+        rng: &mut RNG,
+    ) -> Result<Vec<BinShares>, Error>
+    where
+        C: AbstractChannel,
+        RNG: RngCore + CryptoRng + SeedableRng<Seed = Block>,
+    {
+        let mut lens = (0, 0);
+        let outs = self.compute_circuit(channel, payloads, rng, |ev, bins| {
+            let (wires, sender_bits, receiver_bits) = flatten_bins(bins);
+            let mask_wires = ev.receive_many(&vec![2; wires.len()])?;
+            lens = (sender_bits, receiver_bits);
+            xor_wires(ev, &wires, &mask_wires)
+        })?;
+        Ok(split_shares(&outs, lens.0, lens.1))
+    }
+
+    /// Returns, for each cuckoo bin, the index of the input placed in that
+    /// bin, or `None` if the bin is empty.
+    pub fn bin_indices(&self) -> Vec<Option<usize>> {
+        self.cuckoo
+            .items
+            .iter()
+            .map(|opt_item| opt_item.as_ref().map(|item| item.input_index))
+            .collect()
     }
 
     /// Send encrypted payloads to the Receiver, who can only decrypt a payload if they
@@ -342,6 +714,80 @@ impl ReceiverState {
     }
 }
 
+// Check that there is one payload per input and that they all have the same
+// length, returning that length.
+fn check_payloads(ninputs: usize, payloads: &[Msg]) -> Result<usize, Error> {
+    if payloads.len() != ninputs {
+        return Err(Error::InvalidPayloadsLength);
+    }
+    let payload_len = payloads.first().map_or(0, |p| p.len());
+    if !payloads.iter().all(|p| p.len() == payload_len) {
+        return Err(Error::InvalidPayloadsLength);
+    }
+    Ok(payload_len)
+}
+
+fn encode_bytes<'a, I: Iterator<Item = &'a [u8]>>(bytes: I) -> Vec<u16> {
+    bytes
+        .flat_map(|bytes| bytes.iter())
+        .flat_map(|byte| (0..8).map(move |i| u16::from((byte >> i) & 1_u8)))
+        .collect()
+}
+
+fn decode_bits(bits: &[u16]) -> Vec<u8> {
+    bits.chunks(8)
+        .map(|bits| {
+            bits.iter()
+                .enumerate()
+                .fold(0, |byte, (i, bit)| byte | ((*bit as u8) << i))
+        })
+        .collect()
+}
+
+// The membership wires of `bins`.
+fn members<W: Clone + HasModulus>(bins: &[CircuitBin<W>]) -> Vec<W> {
+    bins.iter().map(|bin| bin.member.clone()).collect()
+}
+
+// Flatten the wires of `bins`, returning them alongside the number of bits of
+// the sender's and receiver's payloads.
+fn flatten_bins<W: Clone + HasModulus>(bins: &[CircuitBin<W>]) -> (Vec<W>, usize, usize) {
+    let sender_bits = bins.first().map_or(0, |bin| bin.sender_payload.size());
+    let receiver_bits = bins.first().map_or(0, |bin| bin.receiver_payload.size());
+    let wires = bins
+        .iter()
+        .flat_map(|bin| {
+            std::iter::once(&bin.member)
+                .chain(bin.sender_payload.wires())
+                .chain(bin.receiver_payload.wires())
+                .cloned()
+        })
+        .collect();
+    (wires, sender_bits, receiver_bits)
+}
+
+// Split the flattened values of the bins back into per-bin shares.
+fn split_shares(bits: &[u16], sender_bits: usize, receiver_bits: usize) -> Vec<BinShares> {
+    bits.chunks(1 + sender_bits + receiver_bits)
+        .map(|bits| BinShares {
+            member: bits[0] == 1,
+            sender_payload: decode_bits(&bits[1..1 + sender_bits]),
+            receiver_payload: decode_bits(&bits[1 + sender_bits..]),
+        })
+        .collect()
+}
+
+fn xor_wires<F: Fancy>(
+    f: &mut F,
+    xs: &[F::Item],
+    ys: &[F::Item],
+) -> Result<Vec<F::Item>, F::Error> {
+    xs.iter()
+        .zip_eq(ys.iter())
+        .map(|(x, y)| f.xor(x, y))
+        .collect()
+}
+
 fn encode_inputs(opprf_outputs: &[Block512]) -> Vec<u16> {
     opprf_outputs
         .iter()
@@ -353,21 +799,38 @@ fn encode_inputs(opprf_outputs: &[Block512]) -> Vec<u16> {
         .collect()
 }
 
-/// Fancy function to compute the intersection and return encoded vector of 0/1 masks.
-fn fancy_compute_intersection<F: Fancy>(
+/// Fancy function to compute the wires of each bin, given the sender's inputs
+/// (the target values and masked payloads) and the receiver's inputs (the
+/// OPPRF outputs and its payloads), with payloads of the given lengths in
+/// bytes.
+fn fancy_circuit_bins<F: Fancy>(
     f: &mut F,
     sender_inputs: &[F::Item],
     receiver_inputs: &[F::Item],
-) -> Result<Vec<F::Item>, F::Error> {
-    assert_eq!(sender_inputs.len(), receiver_inputs.len());
+    sender_payload_len: usize,
+    receiver_payload_len: usize,
+) -> Result<Vec<CircuitBin<F::Item>>, F::Error> {
+    let tag_bits = HASH_SIZE * 8;
+    let sender_bits = tag_bits + sender_payload_len * 8;
+    let receiver_bits = sender_bits + receiver_payload_len * 8;
     sender_inputs
-        .chunks(HASH_SIZE * 8)
-        .zip_eq(receiver_inputs.chunks(HASH_SIZE * 8))
+        .chunks(sender_bits)
+        .zip_eq(receiver_inputs.chunks(receiver_bits))
         .map(|(xs, ys)| {
-            f.eq_bundles(
-                &BinaryBundle::new(xs.to_vec()),
-                &BinaryBundle::new(ys.to_vec()),
-            )
+            let member = f.eq_bundles(
+                &BinaryBundle::new(xs[..tag_bits].to_vec()),
+                &BinaryBundle::new(ys[..tag_bits].to_vec()),
+            )?;
+            let sender_payload = f.bin_xor(
+                &BinaryBundle::new(xs[tag_bits..].to_vec()),
+                &BinaryBundle::new(ys[tag_bits..sender_bits].to_vec()),
+            )?;
+            let receiver_payload = BinaryBundle::new(ys[sender_bits..].to_vec());
+            Ok(CircuitBin {
+                member,
+                sender_payload,
+                receiver_payload,
+            })
         })
         .collect()
 }
@@ -376,31 +839,17 @@ fn fancy_compute_intersection<F: Fancy>(
 /// along with the moduli of that value.
 fn fancy_compute_cardinality<F: Fancy>(
     f: &mut F,
-    sender_inputs: &[F::Item],
-    receiver_inputs: &[F::Item],
+    eqs: &[F::Item],
 ) -> Result<(Vec<F::Item>, Vec<u16>), F::Error> {
-    assert_eq!(sender_inputs.len(), receiver_inputs.len());
-
-    let eqs = sender_inputs
-        .chunks(HASH_SIZE * 8)
-        .zip_eq(receiver_inputs.chunks(HASH_SIZE * 8))
-        .map(|(xs, ys)| {
-            f.eq_bundles(
-                &BinaryBundle::new(xs.to_vec()),
-                &BinaryBundle::new(ys.to_vec()),
-            )
-        })
-        .collect::<Result<Vec<F::Item>, F::Error>>()?;
-
     let qs = fancy_garbling::util::primes_with_width(16);
     let q = fancy_garbling::util::product(&qs);
     let mut acc = f.crt_constant_bundle(0, q)?;
     let one = f.crt_constant_bundle(1, q)?;
 
-    for b in eqs.into_iter() {
+    for b in eqs.iter() {
         let b_ws = one
             .iter()
-            .map(|w| f.mul(w, &b))
+            .map(|w| f.mul(w, b))
             .collect::<Result<Vec<F::Item>, F::Error>>()?;
         let b_crt = CrtBundle::new(b_ws);
         acc = f.crt_add(&acc, &b_crt)?;
@@ -457,6 +906,127 @@ mod tests {
         assert_eq!(cardinality, SET_SIZE);
     }
 
+    #[test]
+    fn reordered_intersection() {
+        let mut rng = AesRng::new();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let sender_inputs = rand_vec_vec(SET_SIZE, ITEM_SIZE, &mut rng);
+        // The same elements as the sender, in reverse order.
+        let receiver_inputs = sender_inputs.iter().cloned().rev().collect_vec();
+
+        std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut psi = Sender::init(&mut channel, &mut rng).unwrap();
+
+            let state = psi.send(&sender_inputs, &mut channel, &mut rng).unwrap();
+            state.compute_cardinality(&mut channel, &mut rng).unwrap();
+        });
+
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut psi = Receiver::init(&mut channel, &mut rng).unwrap();
+
+        let state = psi
+            .receive(&receiver_inputs, &mut channel, &mut rng)
+            .unwrap();
+        let cardinality = state.compute_cardinality(&mut channel, &mut rng).unwrap();
+
+        assert_eq!(cardinality, SET_SIZE);
+    }
+
+    #[test]
+    fn circuit_twice() {
+        let mut rng = AesRng::new();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let sender_inputs = rand_vec_vec(SET_SIZE, ITEM_SIZE, &mut rng);
+        let receiver_inputs = sender_inputs.clone();
+        // Fail rather than hang if the sender does not flush its outputs.
+        receiver
+            .set_read_timeout(Some(std::time::Duration::from_secs(10)))
+            .unwrap();
+
+        std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut psi = Sender::init(&mut channel, &mut rng).unwrap();
+
+            let state = psi.send(&sender_inputs, &mut channel, &mut rng).unwrap();
+            for _ in 0..2 {
+                state.compute_cardinality(&mut channel, &mut rng).unwrap();
+                // Wait for the receiver before going on.
+                assert!(channel.read_bool().unwrap());
+            }
+        });
+
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut psi = Receiver::init(&mut channel, &mut rng).unwrap();
+
+        let state = psi
+            .receive(&receiver_inputs, &mut channel, &mut rng)
+            .unwrap();
+        for _ in 0..2 {
+            let cardinality = state.compute_cardinality(&mut channel, &mut rng).unwrap();
+            assert_eq!(cardinality, SET_SIZE);
+            channel.write_bool(true).unwrap();
+            channel.flush().unwrap();
+        }
+    }
+
+    #[test]
+    fn mutual() {
+        let mut rng = AesRng::new();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let sender_inputs = rand_vec_vec(SET_SIZE, ITEM_SIZE, &mut rng);
+        // Every other sender input, in reverse order, and as many others.
+        let mut receiver_inputs = sender_inputs.iter().step_by(2).cloned().rev().collect_vec();
+        receiver_inputs.extend(rand_vec_vec(SET_SIZE / 2, ITEM_SIZE, &mut rng));
+
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut psi = Sender::init(&mut channel, &mut rng).unwrap();
+
+            let state = psi.send(&sender_inputs, &mut channel, &mut rng).unwrap();
+            let indices = state
+                .compute_intersection_mutual(&sender_inputs, &mut channel, &mut rng)
+                .unwrap();
+            mutual::verify(&sender_inputs, &indices, &mut channel, &mut rng).unwrap();
+            indices
+        });
+
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut psi = Receiver::init(&mut channel, &mut rng).unwrap();
+
+        let state = psi
+            .receive(&receiver_inputs, &mut channel, &mut rng)
+            .unwrap();
+        let indices = state
+            .compute_intersection_mutual(&mut channel, &mut rng)
+            .unwrap();
+        mutual::verify(&receiver_inputs, &indices, &mut channel, &mut rng).unwrap();
+
+        assert_eq!(indices, (0..SET_SIZE / 2).collect_vec());
+        assert_eq!(
+            handle.join().unwrap(),
+            (0..SET_SIZE).step_by(2).collect_vec()
+        );
+    }
+
     #[test]
     fn payloads() {
         let payload_size = 16;
@@ -496,4 +1066,190 @@ mod tests {
             assert!(received_payloads.contains(payload));
         }
     }
+
+    #[test]
+    fn intersection_with_sender_payloads() {
+        let mut rng = AesRng::new();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let sender_inputs = rand_vec_vec(SET_SIZE, ITEM_SIZE, &mut rng);
+        let payloads = rand_vec_vec(SET_SIZE, 4, &mut rng);
+        let mut receiver_inputs = sender_inputs[..SET_SIZE / 2].to_vec();
+        receiver_inputs.extend(rand_vec_vec(SET_SIZE / 2, ITEM_SIZE, &mut rng));
+
+        std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut psi = Sender::init(&mut channel, &mut rng).unwrap();
+            let state = psi
+                .send_with_payloads(&sender_inputs, &payloads, &mut channel, &mut rng)
+                .unwrap();
+            state.compute_intersection(&mut channel, &mut rng).unwrap();
+        });
+
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut psi = Receiver::init(&mut channel, &mut rng).unwrap();
+        let state = psi
+            .receive(&receiver_inputs, &mut channel, &mut rng)
+            .unwrap();
+        let intersection = state
+            .compute_intersection(&mut channel, &mut rng)
+            .unwrap();
+        assert_eq!(intersection.len(), SET_SIZE / 2);
+    }
+
+    #[test]
+    fn payloads_after_sender_payloads() {
+        let mut rng = AesRng::new();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let sender_inputs = rand_vec_vec(SET_SIZE, ITEM_SIZE, &mut rng);
+        let payloads = rand_vec_vec(SET_SIZE, 4, &mut rng);
+        let receiver_inputs = sender_inputs.clone();
+
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(receiver.try_clone().unwrap());
+            let writer = BufWriter::new(receiver);
+            let mut channel = Channel::new(reader, writer);
+            let mut psi = Receiver::init(&mut channel, &mut rng).unwrap();
+            psi.receive(&receiver_inputs, &mut channel, &mut rng)
+                .unwrap();
+        });
+
+        let reader = BufReader::new(sender.try_clone().unwrap());
+        let writer = BufWriter::new(sender);
+        let mut channel = Channel::new(reader, writer);
+        let mut psi = Sender::init(&mut channel, &mut rng).unwrap();
+        let state = psi
+            .send_with_payloads(&sender_inputs, &payloads, &mut channel, &mut rng)
+            .unwrap();
+        handle.join().unwrap();
+        match state.receive_payloads(16, &mut channel) {
+            Err(Error::PsiProtocolError(_)) => (),
+            _ => panic!("payloads received in a state with sender payloads"),
+        }
+    }
+
+    #[test]
+    fn circuit_sum() {
+        let payload_size = 4;
+
+        let mut rng = AesRng::new();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let sender_inputs = rand_vec_vec(SET_SIZE, ITEM_SIZE, &mut rng);
+        let mut receiver_inputs = rand_vec_vec(SET_SIZE, ITEM_SIZE, &mut rng);
+        receiver_inputs[..SET_SIZE / 2].clone_from_slice(&sender_inputs[..SET_SIZE / 2]);
+        let payloads = rand_vec_vec(SET_SIZE, payload_size, &mut rng);
+        let expected = payloads[..SET_SIZE / 2]
+            .iter()
+            .map(|p| u32::from_le_bytes([p[0], p[1], p[2], p[3]]))
+            .fold(0u32, |sum, p| sum.wrapping_add(p));
+
+        fn sum<F: Fancy>(
+            f: &mut F,
+            bins: &[CircuitBin<F::Item>],
+        ) -> Result<Vec<F::Item>, F::Error> {
+            let mut acc = f.bin_constant_bundle(0, 32)?;
+            for bin in bins {
+                let masked = f.mask(&bin.member, &bin.sender_payload)?;
+                acc = f.bin_addition_no_carry(&acc, &BinaryBundle::new(masked.wires().to_vec()))?;
+            }
+            Ok(acc.wires().to_vec())
+        }
+
+        std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut psi = Sender::init(&mut channel, &mut rng).unwrap();
+            let state = psi
+                .send_with_payloads(&sender_inputs, &payloads, &mut channel, &mut rng)
+                .unwrap();
+            state.compute_circuit(&mut channel, &mut rng, sum).unwrap();
+        });
+
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut psi = Receiver::init(&mut channel, &mut rng).unwrap();
+        let state = psi
+            .receive(&receiver_inputs, &mut channel, &mut rng)
+            .unwrap();
+        let outs = state
+            .compute_circuit(&mut channel, &[], &mut rng, sum)
+            .unwrap();
+
+        assert_eq!(
+            fancy_garbling::util::u128_from_bits(&outs),
+            u128::from(expected)
+        );
+    }
+
+    #[test]
+    fn circuit_shares() {
+        let payload_size = 3;
+
+        let mut rng = AesRng::new();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let sender_inputs = rand_vec_vec(SET_SIZE, ITEM_SIZE, &mut rng);
+        let mut receiver_inputs = rand_vec_vec(SET_SIZE, ITEM_SIZE, &mut rng);
+        receiver_inputs[..SET_SIZE / 2].clone_from_slice(&sender_inputs[..SET_SIZE / 2]);
+        let sender_payloads = rand_vec_vec(SET_SIZE, payload_size, &mut rng);
+        let receiver_payloads = rand_vec_vec(SET_SIZE, payload_size + 1, &mut rng);
+        let sender_payloads_ = sender_payloads.clone();
+
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut psi = Sender::init(&mut channel, &mut rng).unwrap();
+            let state = psi
+                .send_with_payloads(&sender_inputs, &sender_payloads_, &mut channel, &mut rng)
+                .unwrap();
+            state.compute_shares(&mut channel, &mut rng).unwrap()
+        });
+
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut psi = Receiver::init(&mut channel, &mut rng).unwrap();
+        let state = psi
+            .receive(&receiver_inputs, &mut channel, &mut rng)
+            .unwrap();
+        let receiver_shares = state
+            .compute_shares(&mut channel, &receiver_payloads, &mut rng)
+            .unwrap();
+        let sender_shares = handle.join().unwrap();
+
+        let xor = |xs: &[u8], ys: &[u8]| xs.iter().zip(ys).map(|(x, y)| x ^ y).collect_vec();
+        let mut nmembers = 0;
+        for ((s, r), index) in sender_shares
+            .iter()
+            .zip_eq(receiver_shares.iter())
+            .zip_eq(state.bin_indices())
+        {
+            let member = s.member ^ r.member;
+            assert_eq!(member, matches!(index, Some(i) if i < SET_SIZE / 2));
+            let receiver_payload = xor(&s.receiver_payload, &r.receiver_payload);
+            match index {
+                Some(i) => assert_eq!(receiver_payload, receiver_payloads[i]),
+                None => assert_eq!(receiver_payload, vec![0; payload_size + 1]),
+            }
+            if member {
+                nmembers += 1;
+                let i = index.unwrap();
+                let sender_payload = xor(&s.sender_payload, &r.sender_payload);
+                assert_eq!(sender_payload, sender_payloads[i]);
+            }
+        }
+        assert_eq!(nmembers, SET_SIZE / 2);
+    }
 }
@@ -0,0 +1,349 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `popsicle`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Implementation of semi-honest two-party private set union, where the
+//! receiver learns `X ∪ Y` and the sender learns nothing, following the
+//! reverse-OPPRF approach of Kolesnikov-Rosulek-Trieu-Wang (cf.
+//! <https://eprint.iacr.org/2019/776>), with the shuffle of Jia et al. (cf.
+//! <https://eprint.iacr.org/2022/157>).
+//!
+//! The sender cuckoo-hashes its set `X` and the receiver hashes its set `Y`
+//! into the same bins using every hash function, as in PSTY. The receiver
+//! picks a random target `t_j` for each bin `j` and programs it into the
+//! OPPRF at every item in that bin, so the sender's OPPRF output for its item
+//! `x_j` in bin `j` equals `t_j` if and only if `x_j ∈ Y`. A batched private
+//! equality test then leaves the parties with XOR shares of whether each bin
+//! holds an element of `X \ Y`.
+//!
+//! Revealing these bits directly would tell the receiver which of the
+//! sender's bins hold an element of `Y`, and, since the bin of an element
+//! depends on the rest of the sender's set, something about `X ∩ Y` that the
+//! union does not. So the sender first shuffles the shares of the bins,
+//! alongside its items, with an oblivious permutation that the receiver does
+//! not know. Only then are the bits opened to the receiver, which picks, by
+//! random OT, the keys that decrypt the sender's items in the bins holding an
+//! element of `X \ Y`.
+//!
+//! Besides the union, the receiver learns the number of bins, which only
+//! depends on the size of the sender's set.
+
+use crate::{cuckoo::CuckooHash, errors::Error, utils};
+use itertools::Itertools;
+use ocelot::{
+    compare::{KkrtCompareReceiver, KkrtCompareSender},
+    oprf::{KmprtReceiver, KmprtSender},
+    ot::{AlszReceiver, AlszSender, RandomReceiver, RandomSender, Receiver as _, Sender as _},
+    shuffle::{AlszPeer, AlszPermuter},
+};
+use rand::{seq::SliceRandom, CryptoRng, Rng, RngCore, SeedableRng};
+use scuttlebutt::{AbstractChannel, AesRng, Block, Block512, SemiHonest};
+use std::convert::TryInto;
+
+const NHASHES: usize = 3;
+// How many bits of the OPPRF outputs to use for the equality tests. An
+// element of `X \ Y` is missing from the union with probability about
+// `2^-EQUALITY_BITS` per bin.
+const EQUALITY_BITS: usize = 64;
+
+/// The longest element supported, in bytes. The items in the sender's bins
+/// are encoded in a `Block512`, alongside a flag and their length.
+pub const MAX_ELEMENT_LEN: usize = 62;
+
+/// The type of values in the sender and receiver's sets.
+pub type Msg = Vec<u8>;
+
+/// Private set union sender.
+pub struct Sender {
+    opprf: KmprtReceiver,
+    compare: KkrtCompareSender,
+    permuter: AlszPermuter,
+    ot: AlszSender,
+}
+
+/// Private set union receiver.
+pub struct Receiver {
+    opprf: KmprtSender,
+    compare: KkrtCompareReceiver,
+    peer: AlszPeer,
+    ot: AlszReceiver,
+}
+
+impl Sender {
+    /// Initialize the PSU sender.
+    pub fn init<C: AbstractChannel, RNG: RngCore + CryptoRng + SeedableRng>(
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let opprf = KmprtReceiver::init(channel, rng)?;
+        let compare = KkrtCompareSender::init(channel, EQUALITY_BITS, rng)?;
+        let permuter = AlszPermuter::init(channel, rng)?;
+        let ot = AlszSender::init(channel, rng)?;
+        Ok(Self {
+            opprf,
+            compare,
+            permuter,
+            ot,
+        })
+    }
+
+    /// Run the PSU protocol over `inputs`, each of at most `MAX_ELEMENT_LEN`
+    /// bytes.
+    pub fn send<C: AbstractChannel, RNG: RngCore + CryptoRng + SeedableRng>(
+        &mut self,
+        inputs: &[Msg],
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<(), Error> {
+        check_elements(inputs)?;
+        let key = rng.gen();
+        let hashed_inputs = utils::compress_and_hash_inputs(inputs, key);
+        let cuckoo = CuckooHash::new(&hashed_inputs, NHASHES)?;
+
+        // Send cuckoo hash info to receiver.
+        channel.write_block(&key)?;
+        channel.write_usize(cuckoo.nbins)?;
+        channel.flush()?;
+
+        let table = cuckoo
+            .items
+            .iter()
+            .map(|opt_item| match opt_item {
+                Some(item) => item.entry,
+                None => rng.gen(),
+            })
+            .collect::<Vec<Block>>();
+        let opprf_outputs = self.opprf.receive(channel, &table, rng)?;
+
+        // Our shares of whether the item in each bin is in the receiver's set.
+        let xs = opprf_outputs.iter().map(equality_input).collect_vec();
+        let eqs = self.compare.equal(channel, &xs, rng)?;
+
+        // Flip our share for every occupied bin, so that the flag is set
+        // exactly for the bins holding an element of `X \ Y`, and never for
+        // the empty bins.
+        let shares = cuckoo
+            .items
+            .iter()
+            .zip_eq(eqs)
+            .map(|(opt_item, eq)| match opt_item {
+                Some(item) => encode(!eq, &inputs[item.input_index]),
+                None => encode(eq, &[]),
+            })
+            .collect_vec();
+
+        let mut perm = (0..shares.len()).collect_vec();
+        perm.shuffle(rng);
+        let shares = self.permuter.permute(channel, &perm, &shares, rng)?;
+
+        for share in shares.iter() {
+            channel.write_bool(flag(share))?;
+        }
+        channel.flush()?;
+
+        let keys = self.ot.send_random(channel, shares.len(), rng)?;
+        for (share, (_, k1)) in shares.iter().zip_eq(keys) {
+            channel.write_block512(&(*share ^ pad(k1)))?;
+        }
+        channel.flush()?;
+        Ok(())
+    }
+}
+
+impl Receiver {
+    /// Initialize the PSU receiver.
+    pub fn init<C: AbstractChannel, RNG: RngCore + CryptoRng + SeedableRng>(
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Self, Error> {
+        let opprf = KmprtSender::init(channel, rng)?;
+        let compare = KkrtCompareReceiver::init(channel, EQUALITY_BITS, rng)?;
+        let peer = AlszPeer::init(channel, rng)?;
+        let ot = AlszReceiver::init(channel, rng)?;
+        Ok(Self {
+            opprf,
+            compare,
+            peer,
+            ot,
+        })
+    }
+
+    /// Run the PSU protocol over `inputs`, each of at most `MAX_ELEMENT_LEN`
+    /// bytes, returning the union of both sets: `inputs` followed by the
+    /// sender's elements not in `inputs`, in random order.
+    pub fn receive<C: AbstractChannel, RNG: RngCore + CryptoRng + SeedableRng>(
+        &mut self,
+        inputs: &[Msg],
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Vec<Msg>, Error> {
+        check_elements(inputs)?;
+        let key = channel.read_block()?;
+        let hashes = utils::compress_and_hash_inputs(inputs, key);
+        let nbins = channel.read_usize()?;
+
+        // map inputs to table using all hash functions
+        let mut table = vec![Vec::new(); nbins];
+        for &x in hashes.iter() {
+            let mut bins = [0; NHASHES];
+            for (h, bin) in bins.iter_mut().enumerate() {
+                *bin = CuckooHash::bin(x, h, nbins);
+                table[*bin].push(x ^ Block::from(h as u128));
+            }
+            // if j = H1(y) = H2(y) for some y, then P2 adds a uniformly random element to
+            // table2[j].
+            if bins.iter().skip(1).all(|&x| x == bins[0]) {
+                table[bins[0]].push(rng.gen());
+            }
+        }
+
+        // select the target values
+        let ts = (0..nbins).map(|_| rng.gen::<Block512>()).collect_vec();
+        let points = table
+            .into_iter()
+            .zip_eq(ts.iter())
+            .flat_map(|(bin, t)| bin.into_iter().map(move |item| (item, *t)))
+            .collect_vec();
+        self.opprf.send(channel, &points, nbins, rng)?;
+
+        let ys = ts.iter().map(equality_input).collect_vec();
+        let eqs = self.compare.equal(channel, &ys, rng)?;
+        let shares = eqs.into_iter().map(|eq| encode(eq, &[])).collect_vec();
+        let shares = self.peer.permute(channel, &shares, rng)?;
+
+        let flags = shares
+            .iter()
+            .map(|share| Ok(channel.read_bool()? ^ flag(share)))
+            .collect::<Result<Vec<bool>, Error>>()?;
+        let keys = self.ot.receive_random(channel, &flags, rng)?;
+
+        let mut union = inputs.to_vec();
+        for ((share, flag), key) in shares.iter().zip(flags).zip(keys) {
+            let ciphertext = channel.read_block512()?;
+            if flag {
+                union.push(decode(&(ciphertext ^ pad(key) ^ *share))?);
+            }
+        }
+        Ok(union)
+    }
+}
+
+fn check_elements(inputs: &[Msg]) -> Result<(), Error> {
+    match inputs.iter().find(|x| x.len() > MAX_ELEMENT_LEN) {
+        Some(x) => Err(Error::PsiProtocolError(format!(
+            "elements are at most {} bytes, got {}",
+            MAX_ELEMENT_LEN,
+            x.len()
+        ))),
+        None => Ok(()),
+    }
+}
+
+// The bits of an OPPRF output compared in the equality tests.
+fn equality_input(output: &Block512) -> u64 {
+    u64::from_le_bytes(output.prefix(8).try_into().unwrap())
+}
+
+// Encode a share of a bin, consisting of the share of the flag, in the lowest
+// bit of the first byte, and the share of the length and bytes of its item.
+fn encode(flag: bool, item: &[u8]) -> Block512 {
+    let mut bytes = [0u8; 64];
+    bytes[0] = flag as u8;
+    bytes[1] = item.len() as u8;
+    bytes[2..2 + item.len()].copy_from_slice(item);
+    Block512::from(bytes)
+}
+
+fn flag(share: &Block512) -> bool {
+    share.prefix(1)[0] & 1 == 1
+}
+
+fn decode(bin: &Block512) -> Result<Msg, Error> {
+    let bytes = bin.prefix(64);
+    let len = bytes[1] as usize;
+    if len > MAX_ELEMENT_LEN {
+        return Err(Error::IoError(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("invalid element length {}", len),
+        )));
+    }
+    Ok(bytes[2..2 + len].to_vec())
+}
+
+// Expand a random OT message into a pad for the item in a bin.
+fn pad(key: Block) -> Block512 {
+    AesRng::from_seed(key).gen()
+}
+
+impl SemiHonest for Sender {}
+impl SemiHonest for Receiver {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::rand_vec_vec;
+    use scuttlebutt::Channel;
+    use std::{
+        collections::BTreeSet,
+        io::{BufReader, BufWriter},
+        os::unix::net::UnixStream,
+    };
+
+    const ITEM_SIZE: usize = 8;
+    const SET_SIZE: usize = 1 << 8;
+
+    fn run(sender_inputs: Vec<Msg>, receiver_inputs: Vec<Msg>) -> Vec<Msg> {
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut psu = Sender::init(&mut channel, &mut rng).unwrap();
+            psu.send(&sender_inputs, &mut channel, &mut rng).unwrap();
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut psu = Receiver::init(&mut channel, &mut rng).unwrap();
+        psu.receive(&receiver_inputs, &mut channel, &mut rng)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_psu() {
+        let mut rng = AesRng::new();
+        let shared = rand_vec_vec(SET_SIZE / 2, ITEM_SIZE, &mut rng);
+        let mut sender_inputs = shared.clone();
+        sender_inputs.extend(rand_vec_vec(SET_SIZE / 2, ITEM_SIZE, &mut rng));
+        let mut receiver_inputs = shared;
+        receiver_inputs.extend(rand_vec_vec(SET_SIZE / 2, ITEM_SIZE, &mut rng));
+
+        let union = run(sender_inputs.clone(), receiver_inputs.clone());
+        assert_eq!(union.len(), 3 * SET_SIZE / 2);
+        assert_eq!(&union[..SET_SIZE], &receiver_inputs[..]);
+        let expected = sender_inputs
+            .into_iter()
+            .chain(receiver_inputs)
+            .collect::<BTreeSet<Msg>>();
+        assert_eq!(union.into_iter().collect::<BTreeSet<Msg>>(), expected);
+    }
+
+    #[test]
+    fn test_psu_lengths() {
+        // Elements of any length up to the maximum, including the empty one.
+        let sender_inputs = (0..=MAX_ELEMENT_LEN).map(|n| vec![1; n]).collect_vec();
+        let receiver_inputs = (0..=MAX_ELEMENT_LEN)
+            .step_by(2)
+            .map(|n| vec![1; n])
+            .collect_vec();
+        let union = run(sender_inputs.clone(), receiver_inputs);
+        assert_eq!(
+            union.into_iter().collect::<BTreeSet<Msg>>(),
+            sender_inputs.into_iter().collect::<BTreeSet<Msg>>()
+        );
+    }
+}
@@ -9,15 +9,35 @@
 //! Kolesnikov-Kumaresan-Rosulek-Trieu (cf. <https://eprint.iacr.org/2016/799>).
 //!
 //! The current implementation does not hash the output of the (relaxed) OPRF.
+//!
+//! This protocol reveals the intersection itself. PSI-cardinality and PSI-sum
+//! cannot be built on the OPRF, since the receiver would learn which of its own
+//! inputs match; use the separate Diffie-Hellman based protocol in
+//! `cardinality` for those.
+//!
+//! In the mutual mode (`Sender::send_mutual` and `Receiver::receive_mutual`),
+//! the receiver reveals the intersection to the sender afterwards, so that
+//! both parties learn it (see `mutual`).
+//!
+//! `Sender::session` and `Receiver::session` run the protocol over several
+//! sets in turn, each as one chunk of a single chunked KKRT session (see
+//! `ocelot::oprf::kkrt`), as the streaming protocol in `stream` does with its
+//! partitions.
 
 use crate::{
-    cuckoo::{compute_masksize, CuckooHash},
+    cuckoo::{compute_masksize, compute_nbins, CuckooHash},
+    psi::mutual,
     utils,
     Error,
 };
 use itertools::Itertools;
-use ocelot::oprf::{self, Receiver as OprfReceiver, Sender as OprfSender};
+use ocelot::{
+    oprf::{self, kkrt, Receiver as OprfReceiver, Sender as OprfSender},
+    ot::{AlszReceiver as OtReceiver, AlszSender as OtSender},
+};
 use rand::{seq::SliceRandom, CryptoRng, Rng, RngCore};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use scuttlebutt::{cointoss, AbstractChannel, Block, Block512, SemiHonest};
 use std::collections::{HashMap, HashSet};
 
@@ -51,32 +71,43 @@ impl Sender {
     ) -> Result<(), Error> {
         let key = cointoss::send(channel, &[rng.gen()])?[0];
         let inputs = utils::compress_and_hash_inputs(inputs, key);
-        let masksize = compute_masksize(inputs.len())?;
         let nbins = channel.read_usize()?;
         let seeds = self.oprf.send(channel, nbins, rng)?;
+        let oprf = &self.oprf;
+        send_encodings(
+            &inputs,
+            &seeds,
+            |seed, input| oprf.compute(seed, input),
+            channel,
+            rng,
+        )
+    }
 
-        // For each hash function `hᵢ`, construct set `Hᵢ = {F(k_{hᵢ(x)}, x ||
-        // i) | x ∈ X)}`, randomly permute it, and send it to the receiver.
-        let mut encoded = Block512::default();
-        let mut indices = (0..inputs.len()).collect_vec();
-        for i in 0..NHASHES {
-            // shuffle the indices in order to send out of order
-            indices.shuffle(rng);
-
-            let hidx = Block::from(i as u128);
-            for &j in &indices {
-                // Compute `bin := hᵢ(x)`.
-                let bin = CuckooHash::bin(inputs[j], i, nbins);
-
-                // Compute `F(k_{hᵢ(x)}, x || i)` and chop off extra bytes.
-                self.oprf.encode(inputs[j] ^ hidx, &mut encoded);
-                encoded ^= seeds[bin];
+    /// Start a chunked session (see `ocelot::oprf::kkrt`), in which the PSI
+    /// protocol runs over several sets of at most `set_size` items in turn,
+    /// all through the same OPRF session. The receiver must use the same set
+    /// size.
+    pub fn session<C: AbstractChannel>(
+        &mut self,
+        channel: &mut C,
+        set_size: usize,
+    ) -> Result<SenderSession<'_>, Error> {
+        let chunk_size = compute_nbins(set_size, NHASHES)?;
+        let oprf = self.oprf.session(channel, chunk_size)?;
+        Ok(SenderSession { oprf })
+    }
 
-                channel.write_bytes(&encoded.prefix(masksize))?;
-            }
-        }
-        channel.flush()?;
-        Ok(())
+    /// Run the PSI protocol over `inputs`, and learn the intersection from the
+    /// receiver (see `mutual`). Returns the sorted indices of the intersection
+    /// in `inputs`.
+    pub fn send_mutual<C: AbstractChannel, RNG: CryptoRng + RngCore>(
+        &mut self,
+        inputs: &[Vec<u8>],
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Vec<usize>, Error> {
+        self.send(inputs, channel, rng)?;
+        mutual::learn(inputs, channel, rng)
     }
 
     /// Run the PSI protocol over `inputs`. Returns a random key for each input which can
@@ -92,25 +123,19 @@ impl Sender {
         let inputs = utils::compress_and_hash_inputs(inputs, key);
         let nbins = channel.read_usize()?;
         let seeds = self.oprf.send(channel, nbins, rng)?;
+        let oprf = &self.oprf;
+        let compute = |seed, input| oprf.compute(seed, input);
         let payloads = (0..inputs.len()).map(|_| rng.gen::<Block>()).collect_vec();
 
         // For each hash function `hᵢ`, construct set `Hᵢ = {F(k_{hᵢ(x)}, x ||
         // i) | x ∈ X)}`, randomly permute it, and send it to the receiver.
-        let mut encoded = Block512::default();
         let mut indices = (0..inputs.len()).collect_vec();
         for i in 0..NHASHES {
             // shuffle the indices in order to send out of order
             indices.shuffle(rng);
 
-            let hidx = Block::from(i as u128);
-            for &j in &indices {
-                // Compute `bin := hᵢ(x)`.
-                let bin = CuckooHash::bin(inputs[j], i, nbins);
-
-                // Compute `F(k_{hᵢ(x)}, x || i)` and chop off extra bytes.
-                self.oprf.encode(inputs[j] ^ hidx, &mut encoded);
-                encoded ^= seeds[bin];
-
+            let encodings = encode(&compute, &inputs, &indices, i, &seeds);
+            for (&j, encoded) in indices.iter().zip(encodings.iter()) {
                 let tag = &encoded.as_ref()[0..masksize];
                 let key = &encoded.as_ref()[masksize..masksize + 16];
 
@@ -127,6 +152,99 @@ impl Sender {
     }
 }
 
+/// A chunked PSI session from the sender's point-of-view (see
+/// `Sender::session`).
+pub struct SenderSession<'a> {
+    oprf: kkrt::SenderSession<'a, OtReceiver>,
+}
+
+impl<'a> SenderSession<'a> {
+    /// Run the PSI protocol over `inputs`, as the next set of the session.
+    pub fn send<C: AbstractChannel, RNG: CryptoRng + RngCore>(
+        &mut self,
+        inputs: &[Vec<u8>],
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<(), Error> {
+        let key = cointoss::send(channel, &[rng.gen()])?[0];
+        let inputs = utils::compress_and_hash_inputs(inputs, key);
+        let seeds = self.oprf.next_chunk(channel, rng)?.ok_or_else(|| {
+            Error::PsiProtocolError("the receiver ended the session early".to_string())
+        })?;
+        let oprf = &self.oprf;
+        send_encodings(
+            &inputs,
+            &seeds,
+            |seed, input| oprf.compute(seed, input),
+            channel,
+            rng,
+        )
+    }
+
+    /// End the session, which the receiver must have ended as well.
+    pub fn finish<C: AbstractChannel, RNG: CryptoRng + RngCore>(
+        mut self,
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<(), Error> {
+        match self.oprf.next_chunk(channel, rng)? {
+            None => Ok(()),
+            Some(_) => Err(Error::PsiProtocolError(
+                "the receiver did not end the session".to_string(),
+            )),
+        }
+    }
+}
+
+// For each hash function `hᵢ`, construct set `Hᵢ = {F(k_{hᵢ(x)}, x || i) | x ∈
+// X)}`, randomly permute it, and send it to the receiver, where `compute`
+// computes the OPRF on a seed and an input.
+fn send_encodings<C, RNG, F>(
+    inputs: &[Block],
+    seeds: &[Block512],
+    compute: F,
+    channel: &mut C,
+    rng: &mut RNG,
+) -> Result<(), Error>
+where
+    C: AbstractChannel,
+    RNG: CryptoRng + RngCore,
+    F: Fn(Block512, Block) -> Block512 + Sync,
+{
+    let masksize = compute_masksize(inputs.len())?;
+    let mut indices = (0..inputs.len()).collect_vec();
+    for i in 0..NHASHES {
+        // shuffle the indices in order to send out of order
+        indices.shuffle(rng);
+
+        for encoded in encode(&compute, inputs, &indices, i, seeds) {
+            // Chop off extra bytes.
+            channel.write_bytes(encoded.prefix(masksize))?;
+        }
+    }
+    channel.flush()?;
+    Ok(())
+}
+
+// Compute `F(k_{hᵢ(x)}, x || i)` for hash function `i` and each input `x =
+// inputs[j]`, for `j` in `indices`, given the OPRF `seeds` of each bin.
+fn encode<F: Fn(Block512, Block) -> Block512 + Sync>(
+    compute: &F,
+    inputs: &[Block],
+    indices: &[usize],
+    i: usize,
+    seeds: &[Block512],
+) -> Vec<Block512> {
+    let hidx = Block::from(i as u128);
+    utils::iter(indices)
+        .map(|&j| {
+            // Compute `bin := hᵢ(x)`.
+            let bin = CuckooHash::bin(inputs[j], i, seeds.len());
+            compute(seeds[bin], inputs[j] ^ hidx)
+        })
+        .collect()
+}
+
 impl Receiver {
     /// Initialize the PSI receiver.
     pub fn init<C: AbstractChannel, RNG: CryptoRng + RngCore>(
@@ -144,34 +262,49 @@ impl Receiver {
         channel: &mut C,
         rng: &mut RNG,
     ) -> Result<Vec<Vec<u8>>, Error> {
-        let n = inputs.len();
-        let masksize = compute_masksize(n)?;
-
-        let (tbl, outputs) = self.perform_oprfs(inputs, channel, rng)?;
+        let indices = self.intersect(inputs, channel, rng)?;
+        Ok(indices.into_iter().map(|i| inputs[i].clone()).collect())
+    }
 
-        // Receive all the sets from the sender.
-        let mut hs = vec![HashSet::with_capacity(n); NHASHES];
-        for h in hs.iter_mut() {
-            for _ in 0..n {
-                let buf = channel.read_vec(masksize)?;
-                h.insert(buf);
-            }
-        }
+    /// Run the PSI protocol over `inputs`, and reveal the intersection to the
+    /// sender (see `mutual`). Returns the sorted indices of the intersection
+    /// in `inputs`.
+    pub fn receive_mutual<C: AbstractChannel, RNG: CryptoRng + RngCore>(
+        &mut self,
+        inputs: &[Vec<u8>],
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Vec<usize>, Error> {
+        let mut indices = self.intersect(inputs, channel, rng)?;
+        indices.sort_unstable();
+        mutual::reveal(inputs, &indices, channel, rng)?;
+        Ok(indices)
+    }
 
-        // Iterate through each input/output pair and see whether it exists in
-        // the appropriate set.
-        let mut intersection = Vec::with_capacity(n);
-        for (opt_item, output) in tbl.items.iter().zip(outputs.into_iter()) {
-            if let Some(item) = opt_item {
-                let prefix = output.prefix(masksize);
-                if hs[item.hash_index].contains(prefix) {
-                    let val = inputs[item.input_index].clone();
-                    intersection.push(val);
-                }
-            }
-        }
+    // Run the PSI protocol over `inputs`, returning the indices of the
+    // intersection in `inputs`, in the order of the cuckoo bins.
+    fn intersect<C: AbstractChannel, RNG: CryptoRng + RngCore>(
+        &mut self,
+        inputs: &[Vec<u8>],
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Vec<usize>, Error> {
+        let (tbl, outputs) = self.perform_oprfs(inputs, channel, rng)?;
+        receive_encodings(inputs.len(), &tbl, &outputs, channel)
+    }
 
-        Ok(intersection)
+    /// Start a chunked session (see `ocelot::oprf::kkrt`), in which the PSI
+    /// protocol runs over several sets of at most `set_size` items in turn,
+    /// all through the same OPRF session. Fails if the sender uses a
+    /// different set size.
+    pub fn session<C: AbstractChannel>(
+        &mut self,
+        channel: &mut C,
+        set_size: usize,
+    ) -> Result<ReceiverSession<'_>, Error> {
+        let chunk_size = compute_nbins(set_size, NHASHES)?;
+        let oprf = self.oprf.session(channel, chunk_size)?;
+        Ok(ReceiverSession { oprf })
     }
 
     /// Run the PSI protocol over `inputs`, receiving a vector of tuples consisting of
@@ -207,28 +340,26 @@ impl Receiver {
 
         // Iterate through each input/output pair and see whether it exists in
         // the appropriate set.
-        let mut intersection = HashMap::with_capacity(n);
-
-        for (opt_item, output) in tbl.items.iter().zip(outputs.into_iter()) {
-            if let Some(item) = opt_item {
+        let intersection = utils::iter(&tbl.items)
+            .zip(utils::iter(&outputs))
+            .filter_map(|(opt_item, output)| {
+                let item = opt_item.as_ref()?;
                 let tag = &output.as_ref()[0..masksize];
 
                 // if the tag is present, decrypt the payload using F(x).
-                if let Some(ct) = hs[item.hash_index].get(tag) {
-                    let val = inputs[item.input_index].clone();
-                    let key = &output.as_ref()[masksize..masksize + 16];
-                    let payload_bytes = scuttlebutt::utils::xor(ct.as_ref(), key);
-                    let payload =
-                        Block::try_from_slice(&payload_bytes).expect("it is exactly 16 bytes long");
-                    intersection.insert(val, payload);
-                }
-            }
-        }
+                let ct = hs[item.hash_index].get(tag)?;
+                let val = inputs[item.input_index].clone();
+                let key = &output.as_ref()[masksize..masksize + 16];
+                let payload_bytes = scuttlebutt::utils::xor(ct.as_ref(), key);
+                let payload =
+                    Block::try_from_slice(&payload_bytes).expect("it is exactly 16 bytes long");
+                Some((val, payload))
+            })
+            .collect();
 
         Ok(intersection)
     }
 
-    // Helper to do computation common to both receive and receive_payloads
     fn perform_oprfs<C: AbstractChannel, RNG: CryptoRng + RngCore>(
         &mut self,
         inputs: &[Vec<u8>],
@@ -252,26 +383,96 @@ impl Receiver {
         channel.write_usize(nbins)?;
         channel.flush()?;
 
-        // Extract inputs from cuckoo hash.
-        let oprf_inputs = tbl
-            .items
-            .iter()
-            .map(|opt_item| {
-                if let Some(item) = opt_item {
-                    item.entry
-                } else {
-                    // No item found, so use the "default" item.
-                    Block::default()
-                }
-            })
-            .collect::<Vec<Block>>();
-
-        let oprf_outputs = self.oprf.receive(channel, &oprf_inputs, rng)?;
+        let oprf_outputs = self.oprf.receive(channel, &oprf_inputs(&tbl), rng)?;
 
         Ok((tbl, oprf_outputs))
     }
 }
 
+/// A chunked PSI session from the receiver's point-of-view (see
+/// `Receiver::session`).
+///
+/// The session must be ended with `finish`, which tells the sender that no
+/// more sets are coming.
+pub struct ReceiverSession<'a> {
+    oprf: kkrt::ReceiverSession<'a, OtSender>,
+}
+
+impl<'a> ReceiverSession<'a> {
+    /// Run the PSI protocol over `inputs`, as the next set of the session,
+    /// returning the intersection with the sender's set.
+    pub fn receive<C: AbstractChannel, RNG: CryptoRng + RngCore>(
+        &mut self,
+        inputs: &[Vec<u8>],
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        let key = cointoss::receive(channel, &[rng.gen()])?[0];
+        let hashed = utils::compress_and_hash_inputs(inputs, key);
+        let tbl = CuckooHash::new(&hashed, NHASHES)?;
+        // The session sends the number of bins along with the OPRF inputs.
+        let outputs = self.oprf.next_chunk(channel, &oprf_inputs(&tbl), rng)?;
+        let indices = receive_encodings(inputs.len(), &tbl, &outputs, channel)?;
+        Ok(indices.into_iter().map(|i| inputs[i].clone()).collect())
+    }
+
+    /// End the session.
+    pub fn finish<C: AbstractChannel>(self, channel: &mut C) -> Result<(), Error> {
+        self.oprf.finish(channel)?;
+        Ok(())
+    }
+}
+
+// Extract the OPRF inputs from the cuckoo hash.
+fn oprf_inputs(tbl: &CuckooHash) -> Vec<Block> {
+    tbl.items
+        .iter()
+        .map(|opt_item| {
+            if let Some(item) = opt_item {
+                item.entry
+            } else {
+                // No item found, so use the "default" item.
+                Block::default()
+            }
+        })
+        .collect()
+}
+
+// Receive the sender's sets of encodings of its `n` items, and return the
+// indices of our inputs whose OPRF `outputs` are among them, in the order of
+// the cuckoo bins.
+fn receive_encodings<C: AbstractChannel>(
+    n: usize,
+    tbl: &CuckooHash,
+    outputs: &[Block512],
+    channel: &mut C,
+) -> Result<Vec<usize>, Error> {
+    let masksize = compute_masksize(n)?;
+
+    // Receive all the sets from the sender.
+    let mut hs = vec![HashSet::with_capacity(n); NHASHES];
+    for h in hs.iter_mut() {
+        for _ in 0..n {
+            let buf = channel.read_vec(masksize)?;
+            h.insert(buf);
+        }
+    }
+
+    // Iterate through each input/output pair and see whether it exists in
+    // the appropriate set.
+    let intersection = utils::iter(&tbl.items)
+        .zip(utils::iter(outputs))
+        .filter_map(|(opt_item, output)| match opt_item {
+            Some(item) if hs[item.hash_index].contains(output.prefix(masksize)) => {
+                Some(item.input_index)
+            }
+            _ => None,
+        })
+        .collect();
+
+    Ok(intersection)
+}
+
 impl SemiHonest for Sender {}
 impl SemiHonest for Receiver {}
 
@@ -314,6 +515,129 @@ mod tests {
         assert_eq!(intersection.len(), SET_SIZE);
     }
 
+    #[test]
+    fn test_psi_reordered_intersection() {
+        let mut rng = AesRng::new();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let sender_inputs = rand_vec_vec(SET_SIZE, ITEM_SIZE, &mut rng);
+        // The same elements as the sender, at different positions.
+        let mut receiver_inputs = sender_inputs.clone();
+        receiver_inputs.shuffle(&mut rng);
+        let expected = receiver_inputs.clone();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut psi = Sender::init(&mut channel, &mut rng).unwrap();
+            psi.send(&sender_inputs, &mut channel, &mut rng).unwrap();
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut psi = Receiver::init(&mut channel, &mut rng).unwrap();
+        let intersection = psi
+            .receive(&receiver_inputs, &mut channel, &mut rng)
+            .unwrap();
+        handle.join().unwrap();
+        assert_eq!(
+            intersection.into_iter().sorted().collect_vec(),
+            expected.into_iter().sorted().collect_vec()
+        );
+    }
+
+    #[test]
+    fn test_session() {
+        let mut rng = AesRng::new();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let set_size = 1 << 8;
+        // Three pairs of sets, each intersecting in its first half.
+        let sets = (0..3)
+            .map(|_| {
+                let sender_inputs = rand_vec_vec(set_size, ITEM_SIZE, &mut rng);
+                let mut receiver_inputs = rand_vec_vec(set_size, ITEM_SIZE, &mut rng);
+                receiver_inputs[..set_size / 2].clone_from_slice(&sender_inputs[..set_size / 2]);
+                (sender_inputs, receiver_inputs)
+            })
+            .collect_vec();
+        let (sender_sets, receiver_sets): (Vec<_>, Vec<_>) = sets.into_iter().unzip();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut psi = Sender::init(&mut channel, &mut rng).unwrap();
+            let mut session = psi.session(&mut channel, set_size).unwrap();
+            for inputs in sender_sets.iter() {
+                session.send(inputs, &mut channel, &mut rng).unwrap();
+            }
+            session.finish(&mut channel, &mut rng).unwrap();
+            // The sender is still usable after the session ends.
+            psi.send(&sender_sets[0], &mut channel, &mut rng).unwrap();
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut psi = Receiver::init(&mut channel, &mut rng).unwrap();
+        let mut session = psi.session(&mut channel, set_size).unwrap();
+        for inputs in receiver_sets.iter() {
+            let mut intersection = session.receive(inputs, &mut channel, &mut rng).unwrap();
+            let mut expected = inputs[..set_size / 2].to_vec();
+            intersection.sort();
+            expected.sort();
+            assert_eq!(intersection, expected);
+        }
+        session.finish(&mut channel).unwrap();
+        let intersection = psi
+            .receive(&receiver_sets[0], &mut channel, &mut rng)
+            .unwrap();
+        assert_eq!(intersection.len(), set_size / 2);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_mutual() {
+        let mut rng = AesRng::new();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let intersection_size = SET_SIZE / 4;
+        let intersection = rand_vec_vec(intersection_size, ITEM_SIZE, &mut rng);
+        // The intersection comes last in the sender's inputs, and first in the
+        // receiver's.
+        let mut sender_inputs = rand_vec_vec(SET_SIZE - intersection_size, ITEM_SIZE, &mut rng);
+        let others = rand_vec_vec(SET_SIZE - intersection_size, ITEM_SIZE, &mut rng);
+        sender_inputs.extend(intersection.clone());
+        let mut receiver_inputs = intersection;
+        receiver_inputs.extend(others);
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut psi = Sender::init(&mut channel, &mut rng).unwrap();
+            let indices = psi
+                .send_mutual(&sender_inputs, &mut channel, &mut rng)
+                .unwrap();
+            mutual::verify(&sender_inputs, &indices, &mut channel, &mut rng).unwrap();
+            indices
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut psi = Receiver::init(&mut channel, &mut rng).unwrap();
+        let indices = psi
+            .receive_mutual(&receiver_inputs, &mut channel, &mut rng)
+            .unwrap();
+        mutual::verify(&receiver_inputs, &indices, &mut channel, &mut rng).unwrap();
+        assert_eq!(indices, (0..intersection_size).collect_vec());
+        assert_eq!(
+            handle.join().unwrap(),
+            (SET_SIZE - intersection_size..SET_SIZE).collect_vec()
+        );
+    }
+
     #[test]
     fn test_payloads() {
         let mut rng = AesRng::new();
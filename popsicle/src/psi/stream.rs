@@ -0,0 +1,743 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `popsicle`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Disk-backed streaming private set intersection, for sets that do not fit
+//! in memory.
+//!
+//! Both parties read their inputs from an iterator (or a reader, using the
+//! format of `write_item`) in a single pass, and hash each input into one of
+//! `npartitions` partitions under a coin-tossed key, appending it to a
+//! temporary file for that partition. Since equal inputs land in the same
+//! partition on both sides, the intersection is the union of the
+//! intersections of corresponding partitions. The parties then run an
+//! in-memory PSI protocol (such as `psz` or `psty`) on one pair of partitions
+//! at a time, and the receiver hands each intersection element to the caller
+//! as soon as its partition is done. With `psz`, the partitions are the
+//! chunks of a single chunked OPRF session (see `psz::Sender::session`). With
+//! `psty`, each partition runs the OPPRF afresh, since the chunked sessions of
+//! `ocelot::oprf::kmprt` split their inputs by a key of their own, which does
+//! not line up with our partitions.
+//!
+//! To hide how the inputs are distributed over the partitions, each partition
+//! is padded with random dummy items to a public bound computed from the set
+//! sizes and the number of partitions, such that a partition overflows with
+//! probability at most `2^-40`. Peak memory is thus bounded by the in-memory
+//! protocol's footprint on a single partition of about `max(n_s, n_r) /
+//! npartitions` items, regardless of the total set sizes. Note that every
+//! partition is opened for writing at once, so `npartitions` should stay well
+//! below the limit on open files.
+//!
+//! Temporary files are created in a fresh subdirectory of the directory the
+//! parties are configured with, and are removed as soon as each partition has
+//! been processed, or when the protocol fails.
+
+#[cfg(feature = "psty")]
+use crate::psi::psty;
+use crate::{errors::Error, psi::psz, utils};
+use rand::{CryptoRng, Rng, RngCore, SeedableRng};
+use scuttlebutt::{cointoss, AbstractChannel, AesHash, Block, SemiHonest};
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// The type of values in the sender and receiver's sets.
+pub type Msg = Vec<u8>;
+
+/// The statistical security parameter bounding the probability that a
+/// partition overflows.
+const STAT_SEC: usize = 40;
+
+/// The size of the dummy items padding each partition, in bytes.
+const DUMMY_SIZE: usize = 32;
+
+/// The maximum size of an item read or written by `ItemReader` and
+/// `write_item`, in bytes.
+pub const MAX_ITEM_SIZE: usize = 1 << 20;
+
+/// A PSI sender which can run on one partition of its set at a time.
+pub trait PartitionSender {
+    /// Run the PSI protocol over each of `npartitions` partitions of `size`
+    /// items in turn, where `partition(i, rng)` returns the items in partition
+    /// `i`.
+    fn send_partitions<C, RNG, P>(
+        &mut self,
+        npartitions: usize,
+        size: usize,
+        partition: P,
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<(), Error>
+    where
+        C: AbstractChannel,
+        RNG: RngCore + CryptoRng + SeedableRng<Seed = Block>,
+        P: FnMut(usize, &mut RNG) -> Result<Vec<Msg>, Error>;
+}
+
+/// A PSI receiver which can run on one partition of its set at a time.
+pub trait PartitionReceiver {
+    /// Run the PSI protocol over each of `npartitions` partitions of `size`
+    /// items in turn, where `partition(i, rng)` returns the items in partition
+    /// `i`, calling `f` on each element of the intersection of a partition
+    /// with the sender's as soon as that partition is done.
+    fn receive_partitions<C, RNG, P, F>(
+        &mut self,
+        npartitions: usize,
+        size: usize,
+        partition: P,
+        f: F,
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<(), Error>
+    where
+        C: AbstractChannel,
+        RNG: RngCore + CryptoRng + SeedableRng<Seed = Block>,
+        P: FnMut(usize, &mut RNG) -> Result<Vec<Msg>, Error>,
+        F: FnMut(Msg) -> Result<(), Error>;
+}
+
+impl PartitionSender for psz::Sender {
+    fn send_partitions<C, RNG, P>(
+        &mut self,
+        npartitions: usize,
+        size: usize,
+        mut partition: P,
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<(), Error>
+    where
+        C: AbstractChannel,
+        RNG: RngCore + CryptoRng + SeedableRng<Seed = Block>,
+        P: FnMut(usize, &mut RNG) -> Result<Vec<Msg>, Error>,
+    {
+        let mut session = self.session(channel, size)?;
+        for i in 0..npartitions {
+            let inputs = partition(i, rng)?;
+            session.send(&inputs, channel, rng)?;
+        }
+        session.finish(channel, rng)
+    }
+}
+
+impl PartitionReceiver for psz::Receiver {
+    fn receive_partitions<C, RNG, P, F>(
+        &mut self,
+        npartitions: usize,
+        size: usize,
+        mut partition: P,
+        mut f: F,
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<(), Error>
+    where
+        C: AbstractChannel,
+        RNG: RngCore + CryptoRng + SeedableRng<Seed = Block>,
+        P: FnMut(usize, &mut RNG) -> Result<Vec<Msg>, Error>,
+        F: FnMut(Msg) -> Result<(), Error>,
+    {
+        let mut session = self.session(channel, size)?;
+        for i in 0..npartitions {
+            let inputs = partition(i, rng)?;
+            for item in session.receive(&inputs, channel, rng)? {
+                f(item)?;
+            }
+        }
+        session.finish(channel)
+    }
+}
+
+#[cfg(feature = "psty")]
+impl PartitionSender for psty::Sender {
+    fn send_partitions<C, RNG, P>(
+        &mut self,
+        npartitions: usize,
+        _size: usize,
+        mut partition: P,
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<(), Error>
+    where
+        C: AbstractChannel,
+        RNG: RngCore + CryptoRng + SeedableRng<Seed = Block>,
+        P: FnMut(usize, &mut RNG) -> Result<Vec<Msg>, Error>,
+    {
+        for i in 0..npartitions {
+            let inputs = partition(i, rng)?;
+            let state = self.send(&inputs, channel, rng)?;
+            state.compute_intersection(channel, rng)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "psty")]
+impl PartitionReceiver for psty::Receiver {
+    fn receive_partitions<C, RNG, P, F>(
+        &mut self,
+        npartitions: usize,
+        _size: usize,
+        mut partition: P,
+        mut f: F,
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<(), Error>
+    where
+        C: AbstractChannel,
+        RNG: RngCore + CryptoRng + SeedableRng<Seed = Block>,
+        P: FnMut(usize, &mut RNG) -> Result<Vec<Msg>, Error>,
+        F: FnMut(Msg) -> Result<(), Error>,
+    {
+        for i in 0..npartitions {
+            let inputs = partition(i, rng)?;
+            let state = self.receive(&inputs, channel, rng)?;
+            for item in state.compute_intersection(channel, rng)? {
+                f(item)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write `item` to `writer`, prefixed with its length. Fails if `item` is
+/// longer than `MAX_ITEM_SIZE`.
+pub fn write_item<W: Write>(writer: &mut W, item: &[u8]) -> io::Result<()> {
+    if item.len() > MAX_ITEM_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("item too large: {} > {}", item.len(), MAX_ITEM_SIZE),
+        ));
+    }
+    writer.write_all(&(item.len() as u64).to_le_bytes())?;
+    writer.write_all(item)
+}
+
+/// An iterator over the items in a reader, in the format of `write_item`.
+/// Yields an error for any item longer than `MAX_ITEM_SIZE`.
+pub struct ItemReader<R> {
+    reader: R,
+}
+
+impl<R: Read> ItemReader<R> {
+    /// Make a new iterator over the items in `reader`.
+    pub fn new(reader: R) -> Self {
+        Self { reader }
+    }
+}
+
+impl<R: Read> Iterator for ItemReader<R> {
+    type Item = io::Result<Msg>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut len = [0u8; 8];
+        // Only a clean end of input before the length ends the iteration.
+        match self.reader.read(&mut len[..1]) {
+            Ok(0) => return None,
+            Ok(_) => (),
+            Err(e) => return Some(Err(e)),
+        }
+        let result = self.reader.read_exact(&mut len[1..]).and_then(|()| {
+            let len = u64::from_le_bytes(len);
+            if len > MAX_ITEM_SIZE as u64 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("item too large: {} > {}", len, MAX_ITEM_SIZE),
+                ));
+            }
+            let mut item = vec![0u8; len as usize];
+            self.reader.read_exact(&mut item)?;
+            Ok(item)
+        });
+        Some(result)
+    }
+}
+
+// Returns a bound on the number of items in each of `npartitions` partitions
+// of a set of `n` items, which is exceeded by any partition with probability
+// at most `2^-STAT_SEC`. By the Chernoff bound, a partition of expected size
+// `μ` holds at least `μ + t` items with probability at most `exp(-t² / (2μ +
+// t))`, and we take a union bound over the partitions.
+fn partition_size(n: usize, npartitions: usize) -> usize {
+    let mu = n.div_ceil(npartitions);
+    let lambda = STAT_SEC as f64 * std::f64::consts::LN_2 + (npartitions as f64).ln();
+    let t = (lambda + (lambda * lambda + 8.0 * mu as f64 * lambda).sqrt()) / 2.0;
+    mu + t.ceil() as usize
+}
+
+fn check_npartitions(npartitions: usize) -> Result<(), Error> {
+    if npartitions == 0 {
+        return Err(Error::PsiProtocolError(
+            "the number of partitions must be positive".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// A set split into partitions stored in temporary files.
+struct Partitions {
+    dir: PathBuf,
+    counts: Vec<usize>,
+}
+
+impl Partitions {
+    // Split `inputs` into `npartitions` partitions, by their hash under `key`,
+    // in a fresh subdirectory of `parent`.
+    fn new<I, RNG>(
+        parent: &Path,
+        key: Block,
+        npartitions: usize,
+        inputs: I,
+        rng: &mut RNG,
+    ) -> Result<Self, Error>
+    where
+        I: Iterator<Item = io::Result<Msg>>,
+        RNG: RngCore,
+    {
+        let dir = parent.join(format!("popsicle-{:032x}", rng.gen::<u128>()));
+        fs::create_dir(&dir)?;
+        // From here on, dropping `partitions` cleans up after us.
+        let mut partitions = Self {
+            dir,
+            counts: vec![0; npartitions],
+        };
+        let mut writers = (0..npartitions)
+            .map(|i| File::create(partitions.path(i)).map(BufWriter::new))
+            .collect::<io::Result<Vec<_>>>()?;
+        let aes = AesHash::new(key);
+        for input in inputs {
+            let input = input?;
+            let h = aes.cr_hash(Block::default(), utils::compress_input(&input));
+            let i = (u128::from(h) % npartitions as u128) as usize;
+            write_item(&mut writers[i], &input)?;
+            partitions.counts[i] += 1;
+        }
+        for writer in writers.iter_mut() {
+            writer.flush()?;
+        }
+        Ok(partitions)
+    }
+
+    fn path(&self, i: usize) -> PathBuf {
+        self.dir.join(i.to_string())
+    }
+
+    fn len(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    // Read partition `i` padded to `size` items, and remove its file.
+    fn read<RNG: RngCore>(&self, i: usize, size: usize, rng: &mut RNG) -> Result<Vec<Msg>, Error> {
+        if self.counts[i] > size {
+            return Err(Error::PsiProtocolError(format!(
+                "partition {} holds {} items, more than the bound {}",
+                i, self.counts[i], size
+            )));
+        }
+        let path = self.path(i);
+        let mut items =
+            ItemReader::new(BufReader::new(File::open(&path)?)).collect::<io::Result<Vec<_>>>()?;
+        fs::remove_file(&path)?;
+        // The dummies only match the other party's items with negligible
+        // probability, so they never show up in the intersection.
+        items.resize_with(size, || {
+            let mut dummy = vec![0u8; DUMMY_SIZE];
+            rng.fill_bytes(&mut dummy);
+            dummy
+        });
+        Ok(items)
+    }
+}
+
+impl Drop for Partitions {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Streaming private set intersection sender.
+pub struct Sender<PSI> {
+    psi: PSI,
+    npartitions: usize,
+    dir: PathBuf,
+}
+
+/// Streaming private set intersection receiver.
+pub struct Receiver<PSI> {
+    psi: PSI,
+    npartitions: usize,
+    dir: PathBuf,
+}
+
+impl<PSI: PartitionSender + SemiHonest> Sender<PSI> {
+    /// Make a new streaming PSI sender from an initialized in-memory `psi`
+    /// sender, which splits its set into `npartitions` partitions stored in
+    /// `dir`. The receiver must use the same number of partitions, which
+    /// must be positive.
+    pub fn new<P: AsRef<Path>>(psi: PSI, npartitions: usize, dir: P) -> Result<Self, Error> {
+        check_npartitions(npartitions)?;
+        Ok(Self {
+            psi,
+            npartitions,
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+
+    /// Run the PSI protocol over `inputs`.
+    pub fn send<C, RNG, I>(
+        &mut self,
+        inputs: I,
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<(), Error>
+    where
+        C: AbstractChannel,
+        RNG: RngCore + CryptoRng + SeedableRng<Seed = Block>,
+        I: IntoIterator<Item = Msg>,
+    {
+        self.send_(inputs.into_iter().map(Ok), channel, rng)
+    }
+
+    /// Run the PSI protocol over the items in `reader`, in the format of
+    /// `write_item`.
+    pub fn send_from<C, RNG, R>(
+        &mut self,
+        reader: R,
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<(), Error>
+    where
+        C: AbstractChannel,
+        RNG: RngCore + CryptoRng + SeedableRng<Seed = Block>,
+        R: Read,
+    {
+        self.send_(ItemReader::new(reader), channel, rng)
+    }
+
+    fn send_<C, RNG, I>(&mut self, inputs: I, channel: &mut C, rng: &mut RNG) -> Result<(), Error>
+    where
+        C: AbstractChannel,
+        RNG: RngCore + CryptoRng + SeedableRng<Seed = Block>,
+        I: Iterator<Item = io::Result<Msg>>,
+    {
+        channel.write_usize(self.npartitions)?;
+        channel.flush()?;
+        let key = cointoss::send(channel, &[rng.gen()])?[0];
+        let partitions = Partitions::new(&self.dir, key, self.npartitions, inputs, rng)?;
+
+        channel.write_usize(partitions.len())?;
+        channel.flush()?;
+        let nreceiver = channel.read_usize()?;
+        let size = partition_size(partitions.len().max(nreceiver), self.npartitions);
+
+        self.psi.send_partitions(
+            self.npartitions,
+            size,
+            |i, rng| partitions.read(i, size, rng),
+            channel,
+            rng,
+        )
+    }
+}
+
+impl<PSI: PartitionReceiver + SemiHonest> Receiver<PSI> {
+    /// Make a new streaming PSI receiver from an initialized in-memory `psi`
+    /// receiver, which splits its set into `npartitions` partitions stored in
+    /// `dir`. The sender must use the same number of partitions, which must
+    /// be positive.
+    pub fn new<P: AsRef<Path>>(psi: PSI, npartitions: usize, dir: P) -> Result<Self, Error> {
+        check_npartitions(npartitions)?;
+        Ok(Self {
+            psi,
+            npartitions,
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+
+    /// Run the PSI protocol over `inputs`, calling `f` on each element of the
+    /// intersection as soon as it is found.
+    pub fn receive<C, RNG, I, F>(
+        &mut self,
+        inputs: I,
+        channel: &mut C,
+        rng: &mut RNG,
+        f: F,
+    ) -> Result<(), Error>
+    where
+        C: AbstractChannel,
+        RNG: RngCore + CryptoRng + SeedableRng<Seed = Block>,
+        I: IntoIterator<Item = Msg>,
+        F: FnMut(Msg) -> Result<(), Error>,
+    {
+        self.receive_(inputs.into_iter().map(Ok), channel, rng, f)
+    }
+
+    /// Run the PSI protocol over the items in `reader`, writing the
+    /// intersection to `writer` as it is found. Both use the format of
+    /// `write_item`.
+    pub fn receive_from<C, RNG, R, W>(
+        &mut self,
+        reader: R,
+        mut writer: W,
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<(), Error>
+    where
+        C: AbstractChannel,
+        RNG: RngCore + CryptoRng + SeedableRng<Seed = Block>,
+        R: Read,
+        W: Write,
+    {
+        self.receive_(ItemReader::new(reader), channel, rng, |item| {
+            write_item(&mut writer, &item).map_err(Error::from)
+        })?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn receive_<C, RNG, I, F>(
+        &mut self,
+        inputs: I,
+        channel: &mut C,
+        rng: &mut RNG,
+        f: F,
+    ) -> Result<(), Error>
+    where
+        C: AbstractChannel,
+        RNG: RngCore + CryptoRng + SeedableRng<Seed = Block>,
+        I: Iterator<Item = io::Result<Msg>>,
+        F: FnMut(Msg) -> Result<(), Error>,
+    {
+        let npartitions = channel.read_usize()?;
+        if npartitions != self.npartitions {
+            return Err(Error::PsiProtocolError(format!(
+                "sender uses {} partitions, expected {}",
+                npartitions, self.npartitions
+            )));
+        }
+        let key = cointoss::receive(channel, &[rng.gen()])?[0];
+        let partitions = Partitions::new(&self.dir, key, self.npartitions, inputs, rng)?;
+
+        let nsender = channel.read_usize()?;
+        channel.write_usize(partitions.len())?;
+        channel.flush()?;
+        let size = partition_size(partitions.len().max(nsender), self.npartitions);
+
+        self.psi.receive_partitions(
+            self.npartitions,
+            size,
+            |i, rng| partitions.read(i, size, rng),
+            f,
+            channel,
+            rng,
+        )
+    }
+}
+
+impl<PSI: SemiHonest> SemiHonest for Sender<PSI> {}
+impl<PSI: SemiHonest> SemiHonest for Receiver<PSI> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::rand_vec_vec;
+    use rand::seq::SliceRandom;
+    use scuttlebutt::{AesRng, Channel};
+    use std::{
+        collections::HashSet,
+        io::{BufReader, BufWriter},
+        os::unix::net::UnixStream,
+    };
+
+    const ITEM_SIZE: usize = 8;
+    const SET_SIZE: usize = 1 << 10;
+    const NPARTITIONS: usize = 4;
+
+    type TestChannel = Channel<BufReader<UnixStream>, BufWriter<UnixStream>>;
+
+    // Returns a fresh directory for the temporary files of one party.
+    fn temp_dir(rng: &mut AesRng) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("popsicle-test-{:032x}", rng.gen::<u128>()));
+        fs::create_dir(&dir).unwrap();
+        dir
+    }
+
+    // Checks that `dir` is empty, and removes it.
+    fn check_temp_dir(dir: PathBuf) {
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir(dir).unwrap();
+    }
+
+    // Returns the sender and receiver inputs, with an intersection of half the
+    // receiver's set in random positions, and the intersection itself.
+    fn inputs(rng: &mut AesRng) -> (Vec<Msg>, Vec<Msg>, HashSet<Msg>) {
+        let mut sender_inputs = rand_vec_vec(SET_SIZE, ITEM_SIZE, rng);
+        let mut receiver_inputs = rand_vec_vec(SET_SIZE / 2, ITEM_SIZE, rng);
+        receiver_inputs[..SET_SIZE / 4].clone_from_slice(&sender_inputs[..SET_SIZE / 4]);
+        let intersection = sender_inputs[..SET_SIZE / 4].iter().cloned().collect();
+        sender_inputs.shuffle(rng);
+        receiver_inputs.shuffle(rng);
+        (sender_inputs, receiver_inputs, intersection)
+    }
+
+    #[test]
+    fn test_psi() {
+        let mut rng = AesRng::new();
+        let (sender_inputs, receiver_inputs, expected) = inputs(&mut rng);
+        let sender_dir = temp_dir(&mut rng);
+        let receiver_dir = temp_dir(&mut rng);
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let sender_dir_ = sender_dir.clone();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let psi = psz::Sender::init(&mut channel, &mut rng).unwrap();
+            let mut psi = Sender::new(psi, NPARTITIONS, &sender_dir_).unwrap();
+            psi.send(sender_inputs, &mut channel, &mut rng).unwrap();
+        });
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let psi = psz::Receiver::init(&mut channel, &mut rng).unwrap();
+        let mut psi = Receiver::new(psi, NPARTITIONS, &receiver_dir).unwrap();
+        let mut intersection = HashSet::new();
+        psi.receive(receiver_inputs, &mut channel, &mut rng, |item| {
+            assert!(intersection.insert(item));
+            Ok(())
+        })
+        .unwrap();
+        handle.join().unwrap();
+        assert_eq!(intersection, expected);
+        check_temp_dir(sender_dir);
+        check_temp_dir(receiver_dir);
+    }
+
+    // Runs the protocol over inputs read from and written to readers and
+    // writers, with the in-memory protocols initialized by `init_sender` and
+    // `init_receiver`.
+    fn psi_from_reader<S, R>(
+        init_sender: fn(&mut TestChannel, &mut AesRng) -> Result<S, Error>,
+        init_receiver: fn(&mut TestChannel, &mut AesRng) -> Result<R, Error>,
+    ) where
+        S: PartitionSender + SemiHonest + 'static,
+        R: PartitionReceiver + SemiHonest,
+    {
+        let mut rng = AesRng::new();
+        let (sender_inputs, receiver_inputs, expected) = inputs(&mut rng);
+        let mut sender_file = Vec::new();
+        for item in sender_inputs.iter() {
+            write_item(&mut sender_file, item).unwrap();
+        }
+        let mut receiver_file = Vec::new();
+        for item in receiver_inputs.iter() {
+            write_item(&mut receiver_file, item).unwrap();
+        }
+        let sender_dir = temp_dir(&mut rng);
+        let receiver_dir = temp_dir(&mut rng);
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let sender_dir_ = sender_dir.clone();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let psi = init_sender(&mut channel, &mut rng).unwrap();
+            let mut psi = Sender::new(psi, NPARTITIONS, &sender_dir_).unwrap();
+            psi.send_from(io::Cursor::new(sender_file), &mut channel, &mut rng)
+                .unwrap();
+        });
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let psi = init_receiver(&mut channel, &mut rng).unwrap();
+        let mut psi = Receiver::new(psi, NPARTITIONS, &receiver_dir).unwrap();
+        let mut output = Vec::new();
+        psi.receive_from(
+            io::Cursor::new(receiver_file),
+            &mut output,
+            &mut channel,
+            &mut rng,
+        )
+        .unwrap();
+        handle.join().unwrap();
+        let intersection = ItemReader::new(io::Cursor::new(output))
+            .collect::<io::Result<HashSet<_>>>()
+            .unwrap();
+        assert_eq!(intersection, expected);
+        check_temp_dir(sender_dir);
+        check_temp_dir(receiver_dir);
+    }
+
+    #[test]
+    fn test_psi_from_reader() {
+        psi_from_reader(psz::Sender::init, psz::Receiver::init);
+    }
+
+    #[cfg(feature = "psty")]
+    #[test]
+    fn test_psty_from_reader() {
+        psi_from_reader(psty::Sender::init, psty::Receiver::init);
+    }
+
+    #[test]
+    fn test_item_reader() {
+        let mut file = Vec::new();
+        write_item(&mut file, &[1, 2, 3]).unwrap();
+        write_item(&mut file, &[]).unwrap();
+        let items = ItemReader::new(file.as_slice())
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(items, vec![vec![1, 2, 3], vec![]]);
+        // A truncated item is an error, not the end of the input.
+        let mut items = ItemReader::new(&file[..file.len() - 9]);
+        assert!(items.next().unwrap().is_err());
+        // An oversized length is rejected before allocating the item.
+        assert!(write_item(&mut Vec::new(), &vec![0; MAX_ITEM_SIZE + 1]).is_err());
+        let len = u64::MAX.to_le_bytes();
+        let mut items = ItemReader::new(&len[..]);
+        assert!(items.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_no_partitions() {
+        let mut rng = AesRng::new();
+        let dir = temp_dir(&mut rng);
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            psz::Sender::init(&mut channel, &mut rng).unwrap()
+        });
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let psi = psz::Receiver::init(&mut channel, &mut rng).unwrap();
+        let psi_ = handle.join().unwrap();
+        match Receiver::new(psi, 0, &dir) {
+            Err(Error::PsiProtocolError(_)) => (),
+            _ => panic!("zero partitions not rejected"),
+        }
+        match Sender::new(psi_, 0, &dir) {
+            Err(Error::PsiProtocolError(_)) => (),
+            _ => panic!("zero partitions not rejected"),
+        }
+        check_temp_dir(dir);
+    }
+
+    #[test]
+    fn test_partition_size() {
+        // The bound grows sublinearly in the expected partition size.
+        for &(n, npartitions) in &[(0, 1), (1, 1), (1 << 10, 4), (1 << 20, 1 << 8)] {
+            let mu = usize::div_ceil(n, npartitions);
+            let size = partition_size(n, npartitions);
+            assert!(size > mu);
+            assert!(size <= mu + 2 * ((mu as f64).sqrt() as usize) * 8 + 64);
+        }
+    }
+}
@@ -0,0 +1,450 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `popsicle`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Implementation of unbalanced private set intersection, where the sender's
+//! set is much larger than the receiver's, following
+//! Kiss-Liu-Schneider-Asokan-Pinkas (cf. <https://eprint.iacr.org/2017/670>).
+//!
+//! The protocol uses the Diffie-Hellman based oblivious PRF `F(k, x) = H(x,
+//! k · H'(x))` from `ocelot::oprf::dh`. The sender's key `k` is reusable, which
+//! splits the protocol into two phases:
+//!
+//! * Offline, the sender encodes its set under `F(k, ·)` once, and inserts the
+//!   encodings into a Bloom filter (see `Filter`). The filter can be cached on
+//!   disk alongside the key (`Sender::write` and `Sender::read`), sent to the
+//!   receiver in full (`Sender::send_filter`), and extended with new items and
+//!   shipped incrementally (`Sender::insert` and `Sender::send_update`).
+//! * Online, the receiver evaluates the oblivious PRF on each of its inputs
+//!   `y`, and looks `F(k, y)` up in the filter. The cost of this phase only
+//!   depends on the size of the receiver's set.
+//!
+//! The filter is sized by the sender's capacity, and the number of items in it
+//! is never sent, so the receiver only learns an upper bound on the size of
+//! the sender's set. Each of the receiver's inputs not in the sender's set is
+//! reported as being in the intersection with probability about
+//! `2^-fpr_bits` (see `Sender::new`). The sender answers at most
+//! `Sender::max_queries` queries per run of the online phase.
+
+use crate::{errors::Error, utils};
+use curve25519_dalek::scalar::Scalar;
+use ocelot::oprf::{DhReceiver, DhSender, Receiver as OprfReceiver, Sender as OprfSender};
+use rand::{CryptoRng, Rng};
+use scuttlebutt::{AbstractChannel, Block, Block512, Channel, SemiHonest};
+use std::{
+    collections::BTreeSet,
+    io::{Read, Write},
+};
+
+/// The type of values in the sender and receiver's sets.
+pub type Msg = Vec<u8>;
+
+/// The default maximum number of queries the sender answers per run of the
+/// online phase.
+pub const DEFAULT_MAX_QUERIES: usize = 1 << 20;
+
+/// A Bloom filter over oblivious PRF encodings.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Filter {
+    nhashes: usize,
+    words: Vec<u64>,
+}
+
+impl Filter {
+    /// Makes a new empty filter with room for `capacity` items, such that
+    /// looking up an item not in the filter succeeds with probability about
+    /// `2^-fpr_bits` once it holds `capacity` items.
+    pub fn new(capacity: usize, fpr_bits: usize) -> Self {
+        // An optimal Bloom filter uses `log2(1/ε)` hash functions and `log2(e)
+        // · log2(1/ε)` bits per item for a false positive rate of `ε`.
+        let nbits = (capacity.max(1) as f64 * fpr_bits as f64 * std::f64::consts::LOG2_E).ceil();
+        let nwords = (nbits as usize).div_ceil(64);
+        Self {
+            nhashes: fpr_bits,
+            words: vec![0; nwords],
+        }
+    }
+
+    /// Returns the size of the filter, in bytes.
+    pub fn size(&self) -> usize {
+        self.words.len() * 8
+    }
+
+    // Returns the bit positions of `encoding`, using double hashing.
+    fn positions(&self, encoding: &Block512) -> impl Iterator<Item = usize> {
+        let mut h1 = [0u8; 8];
+        let mut h2 = [0u8; 8];
+        h1.copy_from_slice(&encoding.as_ref()[0..8]);
+        h2.copy_from_slice(&encoding.as_ref()[8..16]);
+        let h1 = u64::from_le_bytes(h1);
+        let h2 = u64::from_le_bytes(h2) | 1;
+        let nbits = (self.words.len() * 64) as u64;
+        (0..self.nhashes as u64)
+            .map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % nbits) as usize)
+    }
+
+    // Inserts `encoding`, recording the indices of the words that changed in
+    // `updated`.
+    fn insert(&mut self, encoding: &Block512, updated: &mut BTreeSet<usize>) {
+        for pos in self.positions(encoding).collect::<Vec<usize>>() {
+            let (word, bit) = (pos / 64, pos % 64);
+            if self.words[word] & (1 << bit) == 0 {
+                self.words[word] |= 1 << bit;
+                updated.insert(word);
+            }
+        }
+    }
+
+    fn contains(&self, encoding: &Block512) -> bool {
+        self.positions(encoding)
+            .all(|pos| self.words[pos / 64] & (1 << (pos % 64)) != 0)
+    }
+
+    /// Sends the filter over `channel`.
+    pub fn send<C: AbstractChannel>(&self, channel: &mut C) -> Result<(), Error> {
+        channel.write_usize(self.nhashes)?;
+        channel.write_usize(self.words.len())?;
+        for word in self.words.iter() {
+            channel.write_u64(*word)?;
+        }
+        channel.flush()?;
+        Ok(())
+    }
+
+    /// Receives a filter sent with `send` from `channel`.
+    pub fn receive<C: AbstractChannel>(channel: &mut C) -> Result<Self, Error> {
+        let nhashes = channel.read_usize()?;
+        let nwords = channel.read_usize()?;
+        if nhashes == 0 || nwords == 0 {
+            return Err(Error::PsiProtocolError("invalid filter".to_string()));
+        }
+        let words = (0..nwords)
+            .map(|_| channel.read_u64())
+            .collect::<Result<Vec<u64>, std::io::Error>>()?;
+        Ok(Self { nhashes, words })
+    }
+
+    /// Writes the filter to `writer`, e.g., to cache it on disk.
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        self.send(&mut Channel::new(std::io::empty(), writer))
+    }
+
+    /// Reads a filter written with `write` from `reader`.
+    pub fn read<R: Read>(reader: &mut R) -> Result<Self, Error> {
+        Self::receive(&mut Channel::new(reader, std::io::sink()))
+    }
+}
+
+/// Unbalanced private set intersection sender.
+pub struct Sender {
+    oprf: DhSender,
+    filter: Filter,
+    nitems: usize,
+    updated: BTreeSet<usize>,
+    max_queries: usize,
+}
+
+/// Unbalanced private set intersection receiver.
+pub struct Receiver {
+    filter: Filter,
+}
+
+impl Sender {
+    /// Makes a new sender with a fresh key and an empty filter, with room for
+    /// `capacity` items (see `Filter::new`). Choose `fpr_bits` to be at least
+    /// the statistical security parameter plus the logarithm of the size of
+    /// the receiver's sets.
+    pub fn new<RNG: CryptoRng + Rng>(capacity: usize, fpr_bits: usize, rng: &mut RNG) -> Self {
+        Self {
+            oprf: DhSender::new(Scalar::random(rng), false),
+            filter: Filter::new(capacity, fpr_bits),
+            nitems: 0,
+            updated: BTreeSet::new(),
+            max_queries: DEFAULT_MAX_QUERIES,
+        }
+    }
+
+    /// Returns the number of items inserted into the filter.
+    pub fn len(&self) -> usize {
+        self.nitems
+    }
+
+    /// Returns whether no items have been inserted into the filter.
+    pub fn is_empty(&self) -> bool {
+        self.nitems == 0
+    }
+
+    /// Returns the maximum number of queries answered per run of the online
+    /// phase.
+    pub fn max_queries(&self) -> usize {
+        self.max_queries
+    }
+
+    /// Sets the maximum number of queries answered per run of the online
+    /// phase. `send` fails if the receiver asks for more.
+    pub fn set_max_queries(&mut self, max_queries: usize) {
+        self.max_queries = max_queries;
+    }
+
+    /// Encodes `inputs` and inserts them into the filter. This is the bulk of
+    /// the sender's offline work.
+    pub fn insert(&mut self, inputs: &[Msg]) {
+        for input in inputs.iter() {
+            let encoding = self.oprf.compute((), utils::compress_input(input));
+            self.filter.insert(&encoding, &mut self.updated);
+        }
+        self.nitems += inputs.len();
+    }
+
+    /// Returns the filter.
+    pub fn filter(&self) -> &Filter {
+        &self.filter
+    }
+
+    /// Sends the whole filter to the receiver.
+    pub fn send_filter<C: AbstractChannel>(&mut self, channel: &mut C) -> Result<(), Error> {
+        self.filter.send(channel)?;
+        self.updated.clear();
+        Ok(())
+    }
+
+    /// Sends the parts of the filter that changed since the last call to
+    /// `send_filter` or `send_update` to the receiver.
+    pub fn send_update<C: AbstractChannel>(&mut self, channel: &mut C) -> Result<(), Error> {
+        channel.write_usize(self.updated.len())?;
+        for &index in self.updated.iter() {
+            channel.write_usize(index)?;
+            channel.write_u64(self.filter.words[index])?;
+        }
+        channel.flush()?;
+        self.updated.clear();
+        Ok(())
+    }
+
+    /// Run the online phase of the PSI protocol, answering one query of the
+    /// receiver. Fails if the receiver asks for more than `max_queries`
+    /// oblivious PRF evaluations.
+    pub fn send<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &self,
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<(), Error> {
+        let mut oprf = DhSender::init_with_key(channel, self.oprf.key(), false)?;
+        let n = channel.read_usize()?;
+        if n > self.max_queries {
+            return Err(Error::PsiProtocolError(format!(
+                "too many queries: {} > {}",
+                n, self.max_queries
+            )));
+        }
+        oprf.send(channel, n, rng)?;
+        Ok(())
+    }
+
+    /// Writes the key, the number of items and the filter to `writer`, e.g.,
+    /// to cache them on disk. Note that this writes the key in the clear.
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        writer.write_all(self.oprf.key().as_bytes())?;
+        writer.write_all(&(self.nitems as u64).to_le_bytes())?;
+        self.filter.write(writer)
+    }
+
+    /// Reads a sender written with `write` from `reader`.
+    pub fn read<R: Read>(reader: &mut R) -> Result<Self, Error> {
+        let mut key = [0u8; 32];
+        reader.read_exact(&mut key)?;
+        let key = Scalar::from_canonical_bytes(key)
+            .ok_or_else(|| Error::PsiProtocolError("invalid key".to_string()))?;
+        let mut nitems = [0u8; 8];
+        reader.read_exact(&mut nitems)?;
+        let filter = Filter::read(reader)?;
+        Ok(Self {
+            oprf: DhSender::new(key, false),
+            filter,
+            nitems: u64::from_le_bytes(nitems) as usize,
+            updated: BTreeSet::new(),
+            max_queries: DEFAULT_MAX_QUERIES,
+        })
+    }
+}
+
+impl Receiver {
+    /// Makes a new receiver from a filter, e.g., one read from disk.
+    pub fn new(filter: Filter) -> Self {
+        Self { filter }
+    }
+
+    /// Receives the whole filter from the sender.
+    pub fn receive_filter<C: AbstractChannel>(channel: &mut C) -> Result<Self, Error> {
+        let filter = Filter::receive(channel)?;
+        Ok(Self { filter })
+    }
+
+    /// Receives an update to the filter from the sender.
+    pub fn receive_update<C: AbstractChannel>(&mut self, channel: &mut C) -> Result<(), Error> {
+        let nupdated = channel.read_usize()?;
+        for _ in 0..nupdated {
+            let index = channel.read_usize()?;
+            let word = channel.read_u64()?;
+            if index >= self.filter.words.len() {
+                return Err(Error::PsiProtocolError(format!(
+                    "filter update out of range: {} ≥ {}",
+                    index,
+                    self.filter.words.len()
+                )));
+            }
+            self.filter.words[index] = word;
+        }
+        Ok(())
+    }
+
+    /// Returns the filter.
+    pub fn filter(&self) -> &Filter {
+        &self.filter
+    }
+
+    /// Run the online phase of the PSI protocol over `inputs`.
+    pub fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &self,
+        inputs: &[Msg],
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Vec<Msg>, Error> {
+        let mut oprf = DhReceiver::init_unverified(channel)?;
+        channel.write_usize(inputs.len())?;
+        let oprf_inputs = inputs
+            .iter()
+            .map(|input| utils::compress_input(input))
+            .collect::<Vec<Block>>();
+        let outputs = oprf.receive(channel, &oprf_inputs, rng)?;
+        let intersection = inputs
+            .iter()
+            .zip(outputs.iter())
+            .filter(|(_, output)| self.filter.contains(output))
+            .map(|(input, _)| input.clone())
+            .collect();
+        Ok(intersection)
+    }
+}
+
+impl SemiHonest for Sender {}
+impl SemiHonest for Receiver {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::rand_vec_vec;
+    use scuttlebutt::AesRng;
+    use std::{
+        io::{BufReader, BufWriter},
+        os::unix::net::UnixStream,
+    };
+
+    const ITEM_SIZE: usize = 8;
+    const SENDER_SET_SIZE: usize = 1 << 12;
+    const RECEIVER_SET_SIZE: usize = 1 << 6;
+    const FPR_BITS: usize = 40 + 6;
+
+    #[test]
+    fn test_unbalanced() {
+        let mut rng = AesRng::new();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let sender_inputs = rand_vec_vec(SENDER_SET_SIZE, ITEM_SIZE, &mut rng);
+        let mut receiver_inputs = rand_vec_vec(RECEIVER_SET_SIZE, ITEM_SIZE, &mut rng);
+        receiver_inputs[..RECEIVER_SET_SIZE / 2]
+            .clone_from_slice(&sender_inputs[..RECEIVER_SET_SIZE / 2]);
+        let new_inputs = rand_vec_vec(RECEIVER_SET_SIZE / 4, ITEM_SIZE, &mut rng);
+        let nnew = new_inputs.len();
+        receiver_inputs[RECEIVER_SET_SIZE / 2..RECEIVER_SET_SIZE / 2 + nnew]
+            .clone_from_slice(&new_inputs);
+
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let mut psi = Sender::new(SENDER_SET_SIZE * 2, FPR_BITS, &mut rng);
+            psi.insert(&sender_inputs);
+
+            // Cache the sender on "disk" and restore it.
+            let mut cache = Vec::new();
+            psi.write(&mut cache).unwrap();
+            let mut psi = Sender::read(&mut cache.as_slice()).unwrap();
+            assert_eq!(psi.len(), SENDER_SET_SIZE);
+
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            psi.send_filter(&mut channel).unwrap();
+            psi.send(&mut channel, &mut rng).unwrap();
+            psi.insert(&new_inputs);
+            psi.send_update(&mut channel).unwrap();
+            psi.send(&mut channel, &mut rng).unwrap();
+        });
+
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut psi = Receiver::receive_filter(&mut channel).unwrap();
+        let intersection = psi
+            .receive(&receiver_inputs, &mut channel, &mut rng)
+            .unwrap();
+        assert_eq!(intersection, receiver_inputs[..RECEIVER_SET_SIZE / 2]);
+
+        psi.receive_update(&mut channel).unwrap();
+        let intersection = psi
+            .receive(&receiver_inputs, &mut channel, &mut rng)
+            .unwrap();
+        assert_eq!(
+            intersection,
+            receiver_inputs[..RECEIVER_SET_SIZE / 2 + nnew]
+        );
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_max_queries() {
+        let mut rng = AesRng::new();
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let receiver_inputs = rand_vec_vec(RECEIVER_SET_SIZE, ITEM_SIZE, &mut rng);
+
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let mut psi = Sender::new(SENDER_SET_SIZE, FPR_BITS, &mut rng);
+            psi.set_max_queries(RECEIVER_SET_SIZE - 1);
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            psi.send_filter(&mut channel).unwrap();
+            assert!(psi.send(&mut channel, &mut rng).is_err());
+        });
+
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let psi = Receiver::receive_filter(&mut channel).unwrap();
+        assert!(psi
+            .receive(&receiver_inputs, &mut channel, &mut rng)
+            .is_err());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_filter() {
+        let mut rng = AesRng::new();
+        let mut psi = Sender::new(SENDER_SET_SIZE, FPR_BITS, &mut rng);
+        // The filter only depends on the capacity, not on how many items
+        // the sender inserted.
+        let mut empty = Vec::new();
+        psi.filter().write(&mut empty).unwrap();
+        psi.insert(&rand_vec_vec(SENDER_SET_SIZE, ITEM_SIZE, &mut rng));
+        assert_eq!(psi.len(), SENDER_SET_SIZE);
+        let mut cache = Vec::new();
+        psi.filter().write(&mut cache).unwrap();
+        assert_eq!(cache.len(), empty.len());
+        let filter = Filter::read(&mut cache.as_slice()).unwrap();
+        assert_eq!(&filter, psi.filter());
+        assert!(filter.size() < SENDER_SET_SIZE * FPR_BITS * 3 / 16);
+        assert!(Filter::read(&mut &cache[..cache.len() - 1]).is_err());
+    }
+}
@@ -5,36 +5,65 @@
 // See LICENSE for licensing information.
 
 use rand::{CryptoRng, Rng};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use scuttlebutt::{AesHash, Block};
 use sha2::{Digest, Sha256};
 
+// Iterate over a slice either serially or, when the `parallel` feature is
+// enabled, on the `rayon` thread pool, as in `ocelot`. Call sites only use
+// methods shared by `Iterator` and `IndexedParallelIterator` (`map`, `zip`,
+// `filter`, `filter_map`, `count`, `collect`), which produce results in the
+// same order in both modes, and never touch a channel, so that the messages
+// sent do not depend on the mode.
+#[cfg(not(feature = "parallel"))]
+#[inline]
+pub fn iter<T: Sync>(v: &[T]) -> std::slice::Iter<'_, T> {
+    v.iter()
+}
+#[cfg(feature = "parallel")]
+#[inline]
+pub fn iter<T: Sync>(v: &[T]) -> rayon::slice::Iter<'_, T> {
+    v.par_iter()
+}
+
 // Compress an arbitrary vector into a 128-bit chunk, leaving the final 8-bits
 // as zero. We need to leave 8 bits free in order to add in the hash index when
 // running the OPRF (cf. <https://eprint.iacr.org/2016/799>, §5.2).
+//
+// The result only depends on the input itself, and not on its position in
+// `inputs`, since the two parties must map equal elements to the same chunk
+// wherever they occur in their sets.
 pub fn compress_and_hash_inputs(inputs: &[Vec<u8>], key: Block) -> Vec<Block> {
-    let mut hasher = Sha256::new(); // XXX can we do better than using SHA-256?
     let aes = AesHash::new(key);
     let mask = Block::from(0xFFFF_FFFF_FFFF_FFFF_FFFF_FFFF_FFFF_FF00);
-    inputs
-        .iter()
-        .enumerate()
-        .map(|(i, input)| {
+    iter(inputs)
+        .map(|input| {
             let mut digest = [0u8; 16];
             if input.len() <= 16 {
                 // Map `input` directly to a `Block`.
                 digest[0..input.len()].copy_from_slice(input);
             } else {
                 // Hash `input` first.
-                hasher.input(input);
-                let h = hasher.result_reset();
+                // XXX can we do better than using SHA-256?
+                let h = Sha256::digest(input);
                 digest[0..16].copy_from_slice(&h[0..16]);
             }
-            let block = aes.cr_hash(Block::from(i as u128), Block::from(digest));
+            let block = aes.cr_hash(Block::default(), Block::from(digest));
             block & mask
         })
         .collect::<Vec<Block>>()
 }
 
+// Compress an arbitrary vector into a 128-bit chunk. Unlike
+// `compress_and_hash_inputs`, this does not take a key, so it can be used when
+// OPRF outputs are compared across sessions.
+pub fn compress_input(input: &[u8]) -> Block {
+    let mut digest = [0u8; 16];
+    digest.copy_from_slice(&Sha256::digest(input)[0..16]);
+    Block::from(digest)
+}
+
 #[allow(dead_code)] // used in tests
 pub fn rand_vec<RNG: CryptoRng + Rng>(n: usize, rng: &mut RNG) -> Vec<u8> {
     (0..n).map(|_| rng.gen()).collect()
@@ -55,7 +84,12 @@ mod tests {
         let mut rng = AesRng::new();
         let key = rng.gen::<Block>();
         let inputs = rand_vec_vec(13, 16, &mut rng);
-        let _ = compress_and_hash_inputs(&inputs, key);
+        let hashes = compress_and_hash_inputs(&inputs, key);
+        let mut reversed = inputs.clone();
+        reversed.reverse();
+        let mut hashes_ = compress_and_hash_inputs(&reversed, key);
+        hashes_.reverse();
+        assert_eq!(hashes, hashes_);
     }
 }
 
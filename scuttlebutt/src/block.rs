@@ -67,6 +67,35 @@ impl Block {
         }
     }
 
+    /// Multiplication in `GF(2^128)`, using the reduction polynomial `x^128 +
+    /// x^7 + x^2 + x + 1`. Bit `i` of the block (viewed as a little-endian
+    /// `u128`) is the coefficient of `x^i`.
+    #[inline]
+    pub fn gfmul(self, rhs: Self) -> Self {
+        let (lo, hi) = self.clmul(rhs);
+        let (lo, hi) = (u128::from(lo), u128::from(hi));
+        // Fold `hi · x^128 = hi · (x^7 + x^2 + x + 1)` into `lo`, which may
+        // spill over another seven bits that we fold in the same way.
+        let over = (hi >> 127) ^ (hi >> 126) ^ (hi >> 121);
+        let hi = hi ^ over;
+        Block::from(lo ^ hi ^ (hi << 1) ^ (hi << 2) ^ (hi << 7))
+    }
+
+    /// Inversion in `GF(2^128)`, computed as `self^(2^128 - 2)`. The inverse of
+    /// zero is zero.
+    #[inline]
+    pub fn gfinv(self) -> Self {
+        // The exponent is `2 + 4 + ... + 2^127`, so multiply together the
+        // first 127 repeated squarings of `self`.
+        let mut power = self;
+        let mut result = Block::from(1u128);
+        for _ in 1..128 {
+            power = power.gfmul(power);
+            result = result.gfmul(power);
+        }
+        result
+    }
+
     /// Hash an elliptic curve point `pt` and tweak `tweak`.
     ///
     /// Computes the hash by computing `E_{pt}(tweak)`, where `E` is AES-256.
@@ -359,6 +388,30 @@ mod tests {
         assert_eq!(x, z);
     }
 
+    #[test]
+    fn test_gfmul() {
+        let x = rand::random::<Block>();
+        let y = rand::random::<Block>();
+        let z = rand::random::<Block>();
+        let one = Block::from(1u128);
+        assert_eq!(x.gfmul(one), x);
+        assert_eq!(x.gfmul(y), y.gfmul(x));
+        assert_eq!(x.gfmul(y ^ z), x.gfmul(y) ^ x.gfmul(z));
+        assert_eq!(x.gfmul(y).gfmul(z), x.gfmul(y.gfmul(z)));
+        // `x^127 · x = x^7 + x^2 + x + 1`.
+        assert_eq!(
+            Block::from(1u128 << 127).gfmul(Block::from(2u128)),
+            Block::from(0x87u128)
+        );
+    }
+
+    #[test]
+    fn test_gfinv() {
+        let x = rand::random::<Block>();
+        assert_eq!(x.gfmul(x.gfinv()), Block::from(1u128));
+        assert_eq!(Block::default().gfinv(), Block::default());
+    }
+
     #[test]
     fn test_lsb() {
         let x = rand::random::<Block>();
@@ -46,6 +46,15 @@ pub use crate::channel::{
 };
 
 /// A marker trait denoting that the given scheme is semi-honest secure.
+///
+/// A scheme built from other schemes should only implement this trait when
+/// its components do, which is done by bounding the implementation on the
+/// components' markers.
 pub trait SemiHonest {}
 /// A marker trait denoting that the given scheme is maliciously secure.
+///
+/// A scheme whose malicious security relies on that of its components should
+/// bound the scheme type itself (and not just this implementation) on the
+/// components being `Malicious`, so that instantiating it with semi-honest
+/// components is rejected at compile time.
 pub trait Malicious: SemiHonest {}